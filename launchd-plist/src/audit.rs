@@ -0,0 +1,479 @@
+use std::cmp::Reverse;
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::{Component, Path};
+
+use serde::Serialize;
+
+use crate::discovery::{FileSystem, LaunchdEntryLocation, LaunchdEntryType};
+use crate::launchd_job::{KeepAlive, LaunchdJob};
+
+/// Where programs shouldn't be run from
+static TEMP_DIRS: [&str; 5] = [
+    "/tmp",
+    "/private/tmp",
+    "/var/tmp",
+    "/private/var/tmp",
+    "/Users/Shared",
+];
+
+static SHELLS: [&str; 8] = ["sh", "bash", "zsh", "dash", "ksh", "csh", "tcsh", "fish"];
+
+/// Interpreters and the flag that takes a script as its argument
+static INTERPRETERS: [(&str, &str); 6] = [
+    ("python", "-c"),
+    ("perl", "-e"),
+    ("ruby", "-e"),
+    ("node", "-e"),
+    ("osascript", "-e"),
+    ("php", "-r"),
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Unusual, worth a look
+    Low,
+    /// Could be abused, or is how malware usually persists
+    Medium,
+    /// Someone other than root can change what runs, or it's disguised
+    High,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_ascii_lowercase())
+    }
+}
+
+/// Something about a job that persistence tends to look like
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    /// e.g. writable-program
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl Finding {
+    fn new<M: Into<String>>(severity: Severity, rule: &'static str, message: M) -> Self {
+        Self {
+            severity,
+            rule,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.rule, self.message)
+    }
+}
+
+/// Look for risky persistence in a job. fs is what the job's paths are
+/// in, location and entry_type where its plist is. Worst first
+pub fn audit<F: FileSystem + ?Sized>(
+    fs: &F,
+    label: &str,
+    location: &LaunchdEntryLocation,
+    entry_type: &LaunchdEntryType,
+    job: &LaunchdJob,
+) -> Vec<Finding> {
+    let mut findings = vec![];
+
+    if label.starts_with("com.apple.") && *location != LaunchdEntryLocation::System {
+        findings.push(Finding::new(
+            Severity::High,
+            "apple-label",
+            format!("{} outside /System, looks like Apple's but isn't", label),
+        ));
+    }
+
+    // Relative ones are looked up in PATH, nothing to check
+    if let Some(program) = job.executable().map(Path::new).filter(|p| p.is_absolute()) {
+        findings.extend(program_findings(fs, program, location));
+    }
+
+    let keeps_alive = match &job.keep_alive {
+        Some(KeepAlive::Always(keep_alive)) => *keep_alive,
+        Some(KeepAlive::Conditions(_)) => true,
+        None => false,
+    };
+    let as_root = job.user_name.as_deref().unwrap_or("root") == "root";
+
+    if *entry_type == LaunchdEntryType::Daemon
+        && *location != LaunchdEntryLocation::System
+        && job.run_at_load == Some(true)
+        && keeps_alive
+        && as_root
+    {
+        findings.push(Finding::new(
+            Severity::Medium,
+            "root-keepalive",
+            "third party daemon started at load and kept alive as root",
+        ));
+    }
+
+    if let Some(args) = &job.program_arguments {
+        findings.extend(inline_code(args));
+    }
+
+    findings.sort_by_key(|f| Reverse(f.severity));
+    findings
+}
+
+/// The worst of findings, None if there aren't any
+pub fn max_severity(findings: &[Finding]) -> Option<Severity> {
+    findings.iter().map(|f| f.severity).max()
+}
+
+fn program_findings<F: FileSystem + ?Sized>(
+    fs: &F,
+    program: &Path,
+    location: &LaunchdEntryLocation,
+) -> Vec<Finding> {
+    let mut findings = vec![];
+    let display = program.display();
+
+    if TEMP_DIRS.iter().any(|d| program.starts_with(d)) {
+        findings.push(Finding::new(
+            Severity::High,
+            "temp-program",
+            format!("{} is in a temporary or shared directory", display),
+        ));
+    }
+
+    let hidden = program.components().any(|c| match c {
+        Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+        _ => false,
+    });
+
+    if hidden {
+        findings.push(Finding::new(
+            Severity::High,
+            "hidden-program",
+            format!("{} is hidden, or in a hidden directory", display),
+        ));
+    }
+
+    match fs.stat(program) {
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            findings.push(Finding::new(
+                Severity::Medium,
+                "missing-program",
+                format!("{} doesn't exist, whoever creates it runs", display),
+            ));
+            return findings;
+        }
+        Err(_) => return findings,
+        Ok(_) => {}
+    }
+
+    // The program, and every directory it could be swapped out from
+    for path in program.ancestors() {
+        let stat = match fs.stat(path) {
+            Ok(stat) => stat,
+            Err(_) => continue,
+        };
+
+        // Others can't replace what's in a sticky directory (e.g. /tmp)
+        let sticky = path != program && stat.mode & 0o1000 != 0;
+
+        if stat.mode & 0o002 != 0 && !sticky {
+            findings.push(Finding::new(
+                Severity::High,
+                "writable-program",
+                format!("{} is world-writable", path.display()),
+            ));
+            break;
+        }
+
+        // Users can of course change their own agents
+        if stat.uid != 0 && *location != LaunchdEntryLocation::User {
+            findings.push(Finding::new(
+                Severity::High,
+                "writable-program",
+                format!("{} is owned by uid {}, not root", path.display(), stat.uid),
+            ));
+            break;
+        }
+    }
+
+    findings
+}
+
+/// bash -c '...', python3 -c '...', osascript -e '...' and so on,
+/// also through env
+fn inline_code(args: &[String]) -> Option<Finding> {
+    let mut args = args.iter().map(|a| a.as_str());
+    let mut name = base_name(args.next()?);
+
+    if name == "env" {
+        name = base_name(args.find(|a| !a.starts_with('-') && !a.contains('='))?);
+    }
+
+    let rest: Vec<&str> = args.collect();
+
+    let flag = rest.iter().position(|a| {
+        if SHELLS.contains(&name.as_str()) {
+            // -c, or with other options like -lc
+            a.starts_with('-') && !a.starts_with("--") && a.ends_with('c')
+        } else {
+            INTERPRETERS
+                .iter()
+                .any(|(interpreter, flag)| name == *interpreter && a == flag)
+        }
+    })?;
+
+    let code = rest.get(flag + 1).copied().unwrap_or_default();
+    let mut snippet: String = code.chars().take(60).collect();
+    if snippet.len() < code.len() {
+        snippet.push('…');
+    }
+
+    Some(Finding::new(
+        Severity::Medium,
+        "inline-code",
+        format!("runs {} {} {}", name, rest[flag], snippet),
+    ))
+}
+
+/// File name without a version, e.g. python3.11 is python
+fn base_name(path: &str) -> String {
+    let name = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    name.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::{self, ErrorKind};
+    use std::path::{Path, PathBuf};
+
+    use crate::audit::{audit, max_severity, Finding, Severity};
+    use crate::discovery::{FileStat, FileSystem, LaunchdEntryLocation, LaunchdEntryType};
+    use crate::launchd_job::{KeepAlive, LaunchdJob};
+
+    /// Only has stat
+    struct FakeFs(HashMap<PathBuf, FileStat>);
+
+    impl FakeFs {
+        fn new(files: &[(&str, u32, u32)]) -> Self {
+            let mut stats: HashMap<PathBuf, FileStat> = files
+                .iter()
+                .map(|(p, mode, uid)| {
+                    (
+                        PathBuf::from(p),
+                        FileStat {
+                            mode: *mode,
+                            uid: *uid,
+                        },
+                    )
+                })
+                .collect();
+
+            // Directories above them are root's and 0755, unless given
+            for path in files.iter().map(|(p, _, _)| Path::new(p)) {
+                for dir in path.ancestors().skip(1) {
+                    stats.entry(dir.to_path_buf()).or_insert(FileStat {
+                        mode: 0o755,
+                        uid: 0,
+                    });
+                }
+            }
+
+            Self(stats)
+        }
+    }
+
+    impl FileSystem for FakeFs {
+        fn read_dir(&self, _: &Path) -> io::Result<Vec<PathBuf>> {
+            Ok(vec![])
+        }
+
+        fn read(&self, _: &Path) -> io::Result<Vec<u8>> {
+            Err(ErrorKind::NotFound.into())
+        }
+
+        fn is_dir(&self, _: &Path) -> bool {
+            false
+        }
+
+        fn stat(&self, path: &Path) -> io::Result<FileStat> {
+            self.0.get(path).copied().ok_or(ErrorKind::NotFound.into())
+        }
+    }
+
+    fn job(args: &[&str]) -> LaunchdJob {
+        LaunchdJob {
+            program_arguments: Some(args.iter().map(|a| a.to_string()).collect()),
+            ..Default::default()
+        }
+    }
+
+    fn rules(findings: &[Finding]) -> Vec<(Severity, &str)> {
+        findings.iter().map(|f| (f.severity, f.rule)).collect()
+    }
+
+    #[test]
+    fn programs() {
+        let fs = FakeFs::new(&[
+            ("/usr/local/bin/ok", 0o755, 0),
+            ("/usr/local/bin/writable", 0o777, 0),
+            ("/Users/me/bin/tool", 0o755, 501),
+            ("/tmp", 0o1777, 0),
+            ("/tmp/payload", 0o755, 0),
+            ("/Library/.hidden/agent", 0o755, 0),
+            ("/opt/open", 0o777, 0),
+            ("/opt/open/tool", 0o755, 0),
+        ]);
+
+        let global = LaunchdEntryLocation::Global;
+        let user = LaunchdEntryLocation::User;
+        let daemon = LaunchdEntryType::Daemon;
+        let agent = LaunchdEntryType::Agent;
+
+        let cases = vec![
+            ("/usr/local/bin/ok", &global, vec![]),
+            (
+                "/usr/local/bin/writable",
+                &global,
+                vec![(Severity::High, "writable-program")],
+            ),
+            (
+                "/opt/open/tool",
+                &global,
+                vec![(Severity::High, "writable-program")],
+            ),
+            (
+                "/Users/me/bin/tool",
+                &global,
+                vec![(Severity::High, "writable-program")],
+            ),
+            ("/Users/me/bin/tool", &user, vec![]),
+            (
+                "/tmp/payload",
+                &user,
+                vec![(Severity::High, "temp-program")],
+            ),
+            (
+                "/Library/.hidden/agent",
+                &global,
+                vec![(Severity::High, "hidden-program")],
+            ),
+            (
+                "/usr/local/bin/gone",
+                &global,
+                vec![(Severity::Medium, "missing-program")],
+            ),
+            // Looked up in PATH
+            ("tool", &global, vec![]),
+        ];
+
+        for (program, location, expected) in cases {
+            let entry_type = if *location == user { &agent } else { &daemon };
+            let findings = audit(&fs, "com.example", location, entry_type, &job(&[program]));
+            assert_eq!(rules(&findings), expected, "{}", program);
+        }
+
+        let findings = audit(
+            &fs,
+            "com.example",
+            &global,
+            &daemon,
+            &job(&["/usr/local/bin/writable"]),
+        );
+        assert_eq!(
+            findings[0].to_string(),
+            "high: writable-program: /usr/local/bin/writable is world-writable"
+        );
+    }
+
+    #[test]
+    fn jobs() {
+        let fs = FakeFs::new(&[("/usr/local/bin/ok", 0o755, 0)]);
+        let global = LaunchdEntryLocation::Global;
+        let system = LaunchdEntryLocation::System;
+        let daemon = LaunchdEntryType::Daemon;
+
+        let findings = audit(
+            &fs,
+            "com.apple.updater",
+            &global,
+            &daemon,
+            &job(&["/usr/local/bin/ok"]),
+        );
+        assert_eq!(rules(&findings), vec![(Severity::High, "apple-label")]);
+        assert!(audit(
+            &fs,
+            "com.apple.updater",
+            &system,
+            &daemon,
+            &job(&["/usr/local/bin/ok"])
+        )
+        .is_empty());
+
+        let mut keep_alive = job(&["/usr/local/bin/ok"]);
+        keep_alive.run_at_load = Some(true);
+        keep_alive.keep_alive = Some(KeepAlive::Always(true));
+
+        let findings = audit(&fs, "com.example", &global, &daemon, &keep_alive);
+        assert_eq!(rules(&findings), vec![(Severity::Medium, "root-keepalive")]);
+
+        keep_alive.user_name = Some("nobody".to_string());
+        assert!(audit(&fs, "com.example", &global, &daemon, &keep_alive).is_empty());
+    }
+
+    #[test]
+    fn inline_code() {
+        let fs = FakeFs::new(&[
+            ("/bin/bash", 0o755, 0),
+            ("/usr/bin/env", 0o755, 0),
+            ("/usr/bin/python3", 0o755, 0),
+            ("/usr/bin/osascript", 0o755, 0),
+        ]);
+
+        let cases = vec![
+            (
+                vec!["/bin/bash", "-c", "curl x | sh"],
+                Some("runs bash -c curl x | sh"),
+            ),
+            (vec!["/bin/bash", "-lc", "true"], Some("runs bash -lc true")),
+            (
+                vec!["/usr/bin/env", "FOO=1", "python3.11", "-c", "print(1)"],
+                Some("runs python -c print(1)"),
+            ),
+            (
+                vec!["/usr/bin/osascript", "-e", "display dialog \"hi\""],
+                Some("runs osascript -e display dialog \"hi\""),
+            ),
+            (vec!["/bin/bash", "/usr/local/bin/script.sh"], None),
+            (vec!["/usr/bin/python3", "-m", "http.server"], None),
+        ];
+
+        for (args, expected) in cases {
+            let findings = audit(
+                &fs,
+                "com.example",
+                &LaunchdEntryLocation::User,
+                &LaunchdEntryType::Agent,
+                &job(&args),
+            );
+
+            let messages: Vec<&str> = findings.iter().map(|f| f.message.as_str()).collect();
+            assert_eq!(
+                messages,
+                expected.into_iter().collect::<Vec<&str>>(),
+                "{:?}",
+                args
+            );
+            assert_eq!(max_severity(&findings), expected.map(|_| Severity::Medium));
+        }
+    }
+}
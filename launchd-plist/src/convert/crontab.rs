@@ -0,0 +1,442 @@
+use indexmap::IndexMap;
+
+use crate::convert::{calendar_intervals, expand_field, Conversion};
+use crate::error::PlistError;
+use crate::error::PlistError::ValueError;
+use crate::launchd_job::{CalendarInterval, LaunchdJob, OneOrMany};
+use crate::lint::Diagnostic;
+
+static MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+static WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Variables cron itself reads, rather than passing to commands
+static CRON_VARIABLES: [&str; 4] = ["MAILTO", "MAILFROM", "CRON_TZ", "RANDOM_DELAY"];
+
+/// Convert a crontab, each entry becomes a job labelled `<label_prefix>.<n>`
+/// (n counts entries from 1). A system crontab (/etc/crontab) has a user
+/// name after the schedule. Commands run with $SHELL (default /bin/sh)
+/// and the variables set above them
+pub fn from_crontab(
+    text: &str,
+    label_prefix: &str,
+    system: bool,
+) -> Result<Vec<Conversion>, PlistError> {
+    let mut shell = "/bin/sh".to_string();
+    let mut environment: IndexMap<String, String> = IndexMap::new();
+    let mut ignored: Vec<Diagnostic> = vec![];
+    let mut conversions = vec![];
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let path = format!("line {}", i + 1);
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let is_entry = line.starts_with('@')
+            || line.starts_with('*')
+            || line.starts_with(|c: char| c.is_ascii_digit());
+
+        if !is_entry {
+            let (name, value) = line.split_once('=').ok_or_else(|| {
+                ValueError(path.clone(), "not an entry or a variable".to_string())
+            })?;
+            let (name, value) = (name.trim(), unquote(value.trim()));
+
+            if name == "SHELL" {
+                shell = value;
+            } else if CRON_VARIABLES.contains(&name) {
+                ignored.push(Diagnostic::warning(
+                    path,
+                    format!("{} is not supported", name),
+                ));
+            } else {
+                environment.insert(name.to_string(), value);
+            }
+
+            continue;
+        }
+
+        let mut conversion =
+            entry(line, system, &shell).map_err(|e| ValueError(path.clone(), e))?;
+
+        let job = &mut conversion.job;
+        job.label = Some(format!("{}.{}", label_prefix, conversions.len() + 1));
+
+        if !environment.is_empty() {
+            job.environment_variables = Some(environment.clone());
+        }
+
+        for diagnostic in conversion.unsupported.iter_mut() {
+            diagnostic.path = path.clone();
+        }
+
+        // Variables cron would have used go with the next entry
+        conversion.unsupported.splice(0..0, ignored.drain(..));
+
+        conversions.push(conversion);
+    }
+
+    Ok(conversions)
+}
+
+/// One entry, without a label or environment yet
+fn entry(line: &str, system: bool, shell: &str) -> Result<Conversion, String> {
+    let (schedule, rest) = match line.split_once(char::is_whitespace) {
+        Some((nickname, rest)) if nickname.starts_with('@') => {
+            let schedule = match nickname {
+                "@reboot" => None,
+                "@yearly" | "@annually" => Some("0 0 1 1 *"),
+                "@monthly" => Some("0 0 1 * *"),
+                "@weekly" => Some("0 0 * * 0"),
+                "@daily" | "@midnight" => Some("0 0 * * *"),
+                "@hourly" => Some("0 * * * *"),
+                _ => return Err(format!("{} is not a schedule", nickname)),
+            };
+
+            (schedule.map(|s| s.to_string()), rest)
+        }
+        _ => {
+            let (fields, rest) = split_fields(line, 5);
+            (Some(fields.join(" ")), rest)
+        }
+    };
+
+    let (user, command) = if system {
+        let (user, command) = split_fields(rest, 1);
+        (user.first().map(|u| u.to_string()), command)
+    } else {
+        (None, rest)
+    };
+
+    let command = command.trim();
+    if command.is_empty() {
+        return Err("no command".to_string());
+    }
+
+    let mut unsupported = vec![];
+    if command.contains('%') {
+        unsupported.push(Diagnostic::warning(
+            "",
+            "% (newline and standard input) is not supported, it is passed to the shell as is",
+        ));
+    }
+
+    let start_calendar_interval = match schedule {
+        Some(schedule) => {
+            let mut intervals = schedule_intervals(&schedule)?;
+
+            Some(if intervals.len() == 1 {
+                OneOrMany::One(intervals.remove(0))
+            } else {
+                OneOrMany::Many(intervals)
+            })
+        }
+        None => None,
+    };
+
+    Ok(Conversion {
+        job: LaunchdJob {
+            user_name: user,
+            program_arguments: Some(vec![
+                shell.to_string(),
+                "-c".to_string(),
+                command.to_string(),
+            ]),
+            run_at_load: start_calendar_interval
+                .as_ref()
+                .map_or(Some(true), |_| None),
+            start_calendar_interval,
+            ..Default::default()
+        },
+        unsupported,
+    })
+}
+
+fn schedule_intervals(schedule: &str) -> Result<Vec<CalendarInterval>, String> {
+    let fields: Vec<&str> = schedule.split_whitespace().collect();
+    if fields.len() != 5 {
+        return Err(format!("expected 5 schedule fields, got {}", fields.len()));
+    }
+
+    let minute = expand_field(fields[0], 0, 59, &[], "-")?;
+    let hour = expand_field(fields[1], 0, 23, &[], "-")?;
+    let mut day = expand_field(fields[2], 1, 31, &[], "-")?;
+    let month = expand_field(fields[3], 1, 12, &MONTHS, "-")?;
+    let mut weekday = expand_field(fields[4], 0, 7, &WEEKDAYS, "-")?;
+
+    // Like Vixie cron, a day or weekday starting with * (e.g. */2) has to
+    // match along with the other. launchd only ORs them, which is fine when
+    // one of them is every day anyway
+    if fields[2].starts_with('*') || fields[4].starts_with('*') {
+        if day.as_ref().is_some_and(|d| d.len() == 31) {
+            day = None;
+        }
+
+        if weekday
+            .as_ref()
+            .is_some_and(|w| (0..7).all(|d| w.contains(&d) || w.contains(&(d + 7))))
+        {
+            weekday = None;
+        }
+
+        if day.is_some() && weekday.is_some() {
+            return Err(format!(
+                "day {} must match along with weekday {}, launchd runs on either",
+                fields[2], fields[4]
+            ));
+        }
+    }
+
+    calendar_intervals(month, day, weekday, hour, minute)
+}
+
+/// The first n whitespace separated fields, and the rest of the line
+fn split_fields(line: &str, n: usize) -> (Vec<&str>, &str) {
+    let mut fields = vec![];
+    let mut rest = line.trim_start();
+
+    while fields.len() < n && !rest.is_empty() {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        fields.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+
+    (fields, rest)
+}
+
+fn unquote(value: &str) -> String {
+    let quoted = value.len() >= 2
+        && ((value.starts_with('"') && value.ends_with('"'))
+            || (value.starts_with('\'') && value.ends_with('\'')));
+
+    if quoted {
+        value[1..value.len() - 1].to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use crate::convert::crontab::from_crontab;
+    use crate::launchd_job::{CalendarInterval, LaunchdJob, OneOrMany};
+    use crate::lint::Diagnostic;
+
+    static CRONTAB: &str = r#"
+# Backups
+SHELL=/bin/bash
+MAILTO=ops@example.com
+PATH = "/usr/local/bin:/usr/bin:/bin"
+
+30 3 * * *   /usr/local/bin/backup --quiet
+*/20 9-17 * * mon-fri  check-queue > /dev/null 2>&1
+@reboot      /usr/local/bin/warm-cache
+0 0 1,15 * sun  date +%Y > /tmp/stamp
+"#;
+
+    #[test]
+    fn crontab() {
+        let conversions = from_crontab(CRONTAB, "com.example.cron", false).unwrap();
+        assert_eq!(conversions.len(), 4);
+
+        let env: IndexMap<String, String> = vec![(
+            "PATH".to_string(),
+            "/usr/local/bin:/usr/bin:/bin".to_string(),
+        )]
+        .into_iter()
+        .collect();
+
+        let bash = |command: &str| {
+            Some(vec![
+                "/bin/bash".to_string(),
+                "-c".to_string(),
+                command.to_string(),
+            ])
+        };
+
+        assert_eq!(
+            conversions[0].job,
+            LaunchdJob {
+                label: Some("com.example.cron.1".to_string()),
+                program_arguments: bash("/usr/local/bin/backup --quiet"),
+                environment_variables: Some(env.clone()),
+                start_calendar_interval: Some(OneOrMany::One(CalendarInterval {
+                    minute: Some(30),
+                    hour: Some(3),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            conversions[0].unsupported,
+            vec![Diagnostic::warning("line 4", "MAILTO is not supported")]
+        );
+
+        // 3 minutes * 9 hours * 5 weekdays
+        let intervals = conversions[1].job.start_calendar_interval.as_ref().unwrap();
+        assert_eq!(intervals.as_slice().len(), 135);
+        assert_eq!(
+            intervals.as_slice()[0],
+            CalendarInterval {
+                minute: Some(0),
+                hour: Some(9),
+                weekday: Some(1),
+                ..Default::default()
+            }
+        );
+        assert!(conversions[1].unsupported.is_empty());
+
+        assert_eq!(
+            conversions[2].job,
+            LaunchdJob {
+                label: Some("com.example.cron.3".to_string()),
+                program_arguments: bash("/usr/local/bin/warm-cache"),
+                environment_variables: Some(env),
+                run_at_load: Some(true),
+                ..Default::default()
+            }
+        );
+
+        // Day or weekday, like launchd
+        let days: Vec<(Option<i64>, Option<i64>)> = conversions[3]
+            .job
+            .start_calendar_interval
+            .as_ref()
+            .unwrap()
+            .as_slice()
+            .iter()
+            .map(|i| (i.day, i.weekday))
+            .collect();
+        assert_eq!(days, vec![(Some(1), Some(0)), (Some(15), Some(0))]);
+        assert_eq!(
+            conversions[3].unsupported,
+            vec![Diagnostic::warning(
+                "line 10",
+                "% (newline and standard input) is not supported, it is passed to the shell as is"
+            )]
+        );
+    }
+
+    #[test]
+    fn system_crontab() {
+        let conversions = from_crontab("@hourly root /usr/sbin/periodic", "cron", true).unwrap();
+
+        assert_eq!(conversions[0].job.user_name.as_deref(), Some("root"));
+        assert_eq!(
+            conversions[0].job.program_arguments,
+            Some(vec![
+                "/bin/sh".to_string(),
+                "-c".to_string(),
+                "/usr/sbin/periodic".to_string()
+            ])
+        );
+        assert_eq!(
+            conversions[0].job.start_calendar_interval,
+            Some(OneOrMany::One(CalendarInterval {
+                minute: Some(0),
+                ..Default::default()
+            }))
+        );
+    }
+
+    #[test]
+    fn month_and_weekday() {
+        let conversions =
+            from_crontab("0 9 * jan,jul mon ls\n0 9 1 jul mon ls", "cron", false).unwrap();
+
+        // Mondays in January and July, like launchd
+        assert_eq!(
+            conversions[0].job.start_calendar_interval,
+            Some(OneOrMany::Many(
+                [1, 7]
+                    .iter()
+                    .map(|month| CalendarInterval {
+                        month: Some(*month),
+                        weekday: Some(1),
+                        hour: Some(9),
+                        minute: Some(0),
+                        ..Default::default()
+                    })
+                    .collect()
+            ))
+        );
+
+        // In July, the 1st or Mondays
+        assert_eq!(
+            conversions[1].job.start_calendar_interval,
+            Some(OneOrMany::One(CalendarInterval {
+                month: Some(7),
+                day: Some(1),
+                weekday: Some(1),
+                hour: Some(9),
+                minute: Some(0),
+                ..Default::default()
+            }))
+        );
+    }
+
+    #[test]
+    fn starred_day_or_weekday() {
+        let days = |crontab: &str| -> Vec<(Option<i64>, Option<i64>)> {
+            from_crontab(crontab, "cron", false).unwrap()[0]
+                .job
+                .start_calendar_interval
+                .as_ref()
+                .unwrap()
+                .as_slice()
+                .iter()
+                .map(|i| (i.day, i.weekday))
+                .collect()
+        };
+
+        // Every day either way, so only the weekday restricts it
+        assert_eq!(days("0 0 */1 * 1 ls"), vec![(None, Some(1))]);
+        assert_eq!(days("0 0 1 * */1 ls"), vec![(Some(1), None)]);
+        assert_eq!(
+            days("0 0 */10 * * ls"),
+            vec![
+                (Some(1), None),
+                (Some(11), None),
+                (Some(21), None),
+                (Some(31), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let cases = vec![
+            ("hello", "line 1: not an entry or a variable"),
+            ("@sometimes ls", "line 1: @sometimes is not a schedule"),
+            ("* * * *", "line 1: no command"),
+            ("61 * * * * ls", "line 1: 61 is out of range 0-59"),
+            (
+                "0 0 */2 * 1 ls",
+                "line 1: day */2 must match along with weekday 1, launchd runs on either",
+            ),
+            (
+                "0 0 1 * */2 ls",
+                "line 1: day 1 must match along with weekday */2, launchd runs on either",
+            ),
+        ];
+
+        for (crontab, expected) in cases {
+            assert_eq!(
+                from_crontab(crontab, "cron", false)
+                    .unwrap_err()
+                    .to_string(),
+                expected,
+                "{}",
+                crontab
+            );
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::launchd_job::{CalendarInterval, LaunchdJob};
+use crate::lint::Diagnostic;
+
+/// crontab(5) entries to jobs
+pub mod crontab;
+
+/// systemd .service and .timer units to a job, and back
+pub mod systemd;
+
+/// More StartCalendarInterval entries than this is probably a mistake
+const MAX_INTERVALS: usize = 1000;
+
+/// A job converted from something else, and what was left out
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conversion {
+    pub job: LaunchdJob,
+    /// A warning for each directive that has no launchd equivalent
+    pub unsupported: Vec<Diagnostic>,
+}
+
+/// Values a calendar field matches, None for a lone `*`. Takes `*`,
+/// numbers or names, ranges (a<range>b), steps (`*/n`, `a/n`, `a<range>b/n`)
+/// and comma separated lists of those
+fn expand_field(
+    field: &str,
+    min: i64,
+    max: i64,
+    names: &[&str],
+    range: &str,
+) -> Result<Option<Vec<i64>>, String> {
+    if field == "*" {
+        return Ok(None);
+    }
+
+    let value = |v: &str| -> Result<i64, String> {
+        let by_name = names
+            .iter()
+            .position(|n| n.eq_ignore_ascii_case(v))
+            .map(|i| i as i64 + min);
+
+        match by_name.or_else(|| v.parse().ok()) {
+            Some(n) if n >= min && n <= max => Ok(n),
+            Some(n) => Err(format!("{} is out of range {}-{}", n, min, max)),
+            None => Err(format!("{} is not a number", v)),
+        }
+    };
+
+    let mut values = vec![];
+
+    for part in field.split(',') {
+        let (span, step) = match part.split_once('/') {
+            Some((span, step)) => {
+                let step: i64 = step
+                    .parse()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("{} is not a step", step))?;
+                (span, step)
+            }
+            None => (part, 1),
+        };
+
+        let (from, to) = match span.split_once(range) {
+            _ if span == "*" => (min, max),
+            Some((from, to)) => (value(from)?, value(to)?),
+            // a/n is a, a + n, ... up to max
+            None if step > 1 => (value(span)?, max),
+            None => (value(span)?, value(span)?),
+        };
+
+        if from > to {
+            return Err(format!("{} is an empty range", span));
+        }
+
+        values.extend((from..=to).step_by(step as usize));
+    }
+
+    values.sort_unstable();
+    values.dedup();
+    Ok(Some(values))
+}
+
+/// Every combination of the fields, as StartCalendarInterval entries
+fn calendar_intervals(
+    month: Option<Vec<i64>>,
+    day: Option<Vec<i64>>,
+    weekday: Option<Vec<i64>>,
+    hour: Option<Vec<i64>>,
+    minute: Option<Vec<i64>>,
+) -> Result<Vec<CalendarInterval>, String> {
+    let each = |field: Option<Vec<i64>>| match field {
+        Some(values) => values.into_iter().map(Some).collect(),
+        None => vec![None],
+    };
+
+    let (month, day, weekday, hour, minute) = (
+        each(month),
+        each(day),
+        each(weekday),
+        each(hour),
+        each(minute),
+    );
+
+    let count = month.len() * day.len() * weekday.len() * hour.len() * minute.len();
+    if count > MAX_INTERVALS {
+        return Err(format!(
+            "would need {} StartCalendarInterval entries",
+            count
+        ));
+    }
+
+    let mut intervals = vec![];
+
+    for month in &month {
+        for day in &day {
+            for weekday in &weekday {
+                for hour in &hour {
+                    for minute in &minute {
+                        intervals.push(CalendarInterval {
+                            month: *month,
+                            day: *day,
+                            weekday: *weekday,
+                            hour: *hour,
+                            minute: *minute,
+                            ..Default::default()
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(intervals)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::{calendar_intervals, expand_field};
+
+    #[test]
+    fn fields() {
+        let days = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+        let cases = vec![
+            ("*", Ok(None)),
+            ("5", Ok(Some(vec![5]))),
+            ("1,3,1", Ok(Some(vec![1, 3]))),
+            ("1-4", Ok(Some(vec![1, 2, 3, 4]))),
+            ("*/2", Ok(Some(vec![0, 2, 4, 6]))),
+            ("1-5/2", Ok(Some(vec![1, 3, 5]))),
+            ("4/2", Ok(Some(vec![4, 6]))),
+            ("Mon-Wed,sat", Ok(Some(vec![1, 2, 3, 6]))),
+            ("8", Err("8 is out of range 0-7".to_string())),
+            ("x", Err("x is not a number".to_string())),
+            ("*/0", Err("0 is not a step".to_string())),
+            ("5-1", Err("5-1 is an empty range".to_string())),
+        ];
+
+        for (field, expected) in cases {
+            assert_eq!(expand_field(field, 0, 7, &days, "-"), expected, "{}", field);
+        }
+
+        assert_eq!(
+            expand_field("1..3", 1, 12, &[], ".."),
+            Ok(Some(vec![1, 2, 3]))
+        );
+    }
+
+    #[test]
+    fn combinations() {
+        let intervals =
+            calendar_intervals(None, None, None, Some(vec![9, 17]), Some(vec![0, 30])).unwrap();
+
+        let times: Vec<(Option<i64>, Option<i64>)> =
+            intervals.iter().map(|i| (i.hour, i.minute)).collect();
+        assert_eq!(
+            times,
+            vec![
+                (Some(9), Some(0)),
+                (Some(9), Some(30)),
+                (Some(17), Some(0)),
+                (Some(17), Some(30))
+            ]
+        );
+
+        assert_eq!(
+            calendar_intervals(
+                None,
+                Some((1..=31).collect()),
+                None,
+                Some((0..=23).collect()),
+                Some(vec![0, 30])
+            ),
+            Err("would need 1488 StartCalendarInterval entries".to_string())
+        );
+    }
+}
@@ -0,0 +1,1024 @@
+use indexmap::IndexMap;
+
+use crate::convert::{calendar_intervals, expand_field, Conversion};
+use crate::error::PlistError;
+use crate::error::PlistError::ValueError;
+use crate::launchd_job::{CalendarInterval, KeepAlive, KeepAliveConditions, LaunchdJob, OneOrMany};
+use crate::lint::Diagnostic;
+
+static WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+/// Keys to_systemd converts, the rest are reported
+static CONVERTED_KEYS: [&str; 17] = [
+    "Label",
+    "Program",
+    "ProgramArguments",
+    "KeepAlive",
+    "ThrottleInterval",
+    "EnvironmentVariables",
+    "UserName",
+    "GroupName",
+    "WorkingDirectory",
+    "StandardOutPath",
+    "StandardErrorPath",
+    "Nice",
+    "Umask",
+    "ExitTimeOut",
+    "RunAtLoad",
+    "StartInterval",
+    "StartCalendarInterval",
+];
+
+/// Key=Value in a [Section]
+struct Directive {
+    section: String,
+    key: String,
+    value: String,
+}
+
+impl Directive {
+    fn warning<M: Into<String>>(&self, message: M) -> Diagnostic {
+        Diagnostic::warning(format!("{}.{}", self.section, self.key), message)
+    }
+
+    fn not_supported(&self) -> Diagnostic {
+        self.warning("not supported")
+    }
+}
+
+/// Convert a .service unit, and the .timer that starts it if there is one
+pub fn from_systemd(
+    label: &str,
+    service: &str,
+    timer: Option<&str>,
+) -> Result<Conversion, PlistError> {
+    let mut job = LaunchdJob {
+        label: Some(label.to_string()),
+        ..Default::default()
+    };
+    let mut environment = IndexMap::new();
+    let mut calendar = vec![];
+    let mut unsupported = vec![];
+
+    for d in parse_unit(service)? {
+        match (d.section.as_str(), d.key.as_str()) {
+            ("Service", "ExecStart") if job.program_arguments.is_some() => {
+                unsupported.push(d.warning("only the first ExecStart is converted"))
+            }
+            ("Service", "ExecStart") => {
+                let (arguments, warnings) = exec_start(&d.value)
+                    .map_err(|e| ValueError("Service.ExecStart".to_string(), e))?;
+
+                job.program_arguments = Some(arguments);
+                unsupported.extend(warnings.into_iter().map(|w| d.warning(w)));
+            }
+            ("Service", "Restart") => match d.value.as_str() {
+                "no" => (),
+                "always" => job.keep_alive = Some(KeepAlive::Always(true)),
+                "on-failure" | "on-success" => {
+                    job.keep_alive = Some(KeepAlive::Conditions(KeepAliveConditions {
+                        successful_exit: Some(d.value == "on-success"),
+                        ..Default::default()
+                    }))
+                }
+                other => unsupported.push(d.warning(format!("{} is not supported", other))),
+            },
+            ("Service", "RestartSec") => match parse_span(&d.value) {
+                Ok(secs) => job.throttle_interval = Some(secs),
+                Err(e) => unsupported.push(d.warning(e)),
+            },
+            ("Service", "TimeoutStopSec") => match parse_span(&d.value) {
+                Ok(secs) => job.exit_time_out = Some(secs),
+                Err(e) => unsupported.push(d.warning(e)),
+            },
+            ("Service", "Environment") => match split_words(&d.value) {
+                Ok(assignments) => {
+                    for assignment in assignments {
+                        match assignment.split_once('=') {
+                            Some((k, v)) => {
+                                environment.insert(k.to_string(), v.to_string());
+                            }
+                            None => unsupported
+                                .push(d.warning(format!("{} is not NAME=value", assignment))),
+                        }
+                    }
+                }
+                Err(e) => unsupported.push(d.warning(e)),
+            },
+            ("Service", "User") => job.user_name = Some(d.value.clone()),
+            ("Service", "Group") => job.group_name = Some(d.value.clone()),
+            ("Service", "WorkingDirectory") => {
+                // - means it's OK if it doesn't exist
+                let dir = d.value.trim_start_matches('-');
+
+                if dir.starts_with('/') {
+                    job.working_directory = Some(dir.to_string());
+                } else {
+                    unsupported.push(d.warning(format!("{} is not supported", dir)));
+                }
+            }
+            ("Service", "StandardOutput") | ("Service", "StandardError") => {
+                let path = d
+                    .value
+                    .strip_prefix("file:")
+                    .or_else(|| d.value.strip_prefix("append:"));
+
+                match (path, d.key.as_str()) {
+                    (Some(path), "StandardOutput") => {
+                        job.standard_out_path = Some(path.to_string())
+                    }
+                    (Some(path), _) => job.standard_error_path = Some(path.to_string()),
+                    // launchd's default
+                    (None, _) if d.value == "null" => (),
+                    (None, _) => unsupported.push(d.warning(format!(
+                        "{} is not supported, output goes to /dev/null",
+                        d.value
+                    ))),
+                }
+            }
+            ("Service", "Nice") => match d.value.parse() {
+                Ok(nice) => job.nice = Some(nice),
+                Err(_) => unsupported.push(d.warning(format!("{} is not a number", d.value))),
+            },
+            ("Service", "UMask") => match i64::from_str_radix(&d.value, 8) {
+                Ok(umask) => job.umask = Some(umask),
+                Err(_) => unsupported.push(d.warning(format!("{} is not octal", d.value))),
+            },
+            ("Service", "Type") => match d.value.as_str() {
+                "simple" | "exec" | "oneshot" => (),
+                other => unsupported.push(d.warning(format!(
+                    "{} is not supported, launchd expects the process not to fork or notify",
+                    other
+                ))),
+            },
+            ("Install", "WantedBy") => job.run_at_load = Some(true),
+            _ => unsupported.push(d.not_supported()),
+        }
+    }
+
+    for d in timer.map(parse_unit).transpose()?.unwrap_or_default() {
+        match (d.section.as_str(), d.key.as_str()) {
+            ("Timer", "OnCalendar") => match parse_calendar(&d.value) {
+                Ok(intervals) => calendar.extend(intervals),
+                Err(e) => unsupported.push(d.warning(e)),
+            },
+            ("Timer", "OnUnitActiveSec") | ("Timer", "OnUnitInactiveSec") => {
+                match (parse_span(&d.value), job.start_interval) {
+                    (Ok(secs), None) => job.start_interval = Some(secs),
+                    (Ok(_), Some(_)) => {
+                        unsupported.push(d.warning("only one interval is converted"))
+                    }
+                    (Err(e), _) => unsupported.push(d.warning(e)),
+                }
+            }
+            ("Timer", "OnBootSec") | ("Timer", "OnStartupSec") | ("Timer", "OnActiveSec") => {
+                job.run_at_load = Some(true);
+
+                if parse_span(&d.value) != Ok(0) {
+                    unsupported
+                        .push(d.warning(format!("runs at load, without the {} delay", d.value)));
+                }
+            }
+            ("Timer", "Persistent") if d.value == "true" => unsupported.push(
+                d.warning("launchd only catches up on runs missed while asleep, not while off"),
+            ),
+            ("Timer", "Persistent") => (),
+            ("Install", "WantedBy") => (),
+            _ => unsupported.push(d.not_supported()),
+        }
+    }
+
+    if job.program_arguments.is_none() {
+        return Err(ValueError(
+            "Service.ExecStart".to_string(),
+            "missing".to_string(),
+        ));
+    }
+
+    if !environment.is_empty() {
+        job.environment_variables = Some(environment);
+    }
+
+    job.start_calendar_interval = match calendar.len() {
+        0 => None,
+        1 => Some(OneOrMany::One(calendar.remove(0))),
+        _ => Some(OneOrMany::Many(calendar)),
+    };
+
+    Ok(Conversion { job, unsupported })
+}
+
+/// A job as systemd units
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemdUnits {
+    pub service: String,
+    /// If the job runs on StartInterval or StartCalendarInterval
+    pub timer: Option<String>,
+    /// A warning for each key that has no systemd equivalent
+    pub unsupported: Vec<Diagnostic>,
+}
+
+/// Convert a job to a .service unit, and a .timer if it is scheduled
+pub fn to_systemd(job: &LaunchdJob) -> Result<SystemdUnits, PlistError> {
+    let label = job
+        .label
+        .as_deref()
+        .ok_or_else(|| ValueError("Label".to_string(), "missing".to_string()))?;
+
+    let mut unsupported: Vec<Diagnostic> = job
+        .to_dictionary()
+        .keys()
+        .filter(|k| !CONVERTED_KEYS.contains(&k.as_str()))
+        .map(|k| Diagnostic::warning(k.as_str(), "not supported"))
+        .collect();
+
+    let arguments: Vec<String> = job
+        .program_arguments
+        .iter()
+        .flatten()
+        .map(|a| quote(a, true))
+        .collect();
+
+    let exec_start = match (&job.program, arguments.is_empty()) {
+        (Some(program), true) => quote(program, true),
+        // @ makes the first argument argv[0], like launchd does
+        (Some(program), false) => format!("@{} {}", quote(program, true), arguments.join(" ")),
+        (None, false) => arguments.join(" "),
+        (None, true) => return Err(ValueError("Program".to_string(), "missing".to_string())),
+    };
+
+    let mut service = vec![
+        "[Unit]".to_string(),
+        format!("Description={}", label),
+        "".to_string(),
+        "[Service]".to_string(),
+        format!("ExecStart={}", exec_start),
+    ];
+
+    match &job.keep_alive {
+        Some(KeepAlive::Always(true)) => service.push("Restart=always".to_string()),
+        Some(KeepAlive::Always(false)) | None => (),
+        Some(KeepAlive::Conditions(conditions)) => {
+            let only_successful_exit = KeepAliveConditions {
+                successful_exit: conditions.successful_exit,
+                ..Default::default()
+            };
+
+            match conditions.successful_exit {
+                Some(successful_exit) if *conditions == only_successful_exit => {
+                    service.push(format!(
+                        "Restart={}",
+                        if successful_exit {
+                            "on-success"
+                        } else {
+                            "on-failure"
+                        }
+                    ))
+                }
+                _ => unsupported.push(Diagnostic::warning(
+                    "KeepAlive",
+                    "only SuccessfulExit is supported",
+                )),
+            }
+        }
+    }
+
+    let mut push = |key: &str, value: Option<String>| {
+        if let Some(value) = value {
+            service.push(format!("{}={}", key, value));
+        }
+    };
+
+    push("RestartSec", job.throttle_interval.map(|i| i.to_string()));
+    push("TimeoutStopSec", job.exit_time_out.map(|i| i.to_string()));
+    push("User", job.user_name.clone());
+    push("Group", job.group_name.clone());
+    push("WorkingDirectory", job.working_directory.clone());
+    push(
+        "StandardOutput",
+        job.standard_out_path
+            .as_ref()
+            .map(|p| format!("append:{}", p)),
+    );
+    push(
+        "StandardError",
+        job.standard_error_path
+            .as_ref()
+            .map(|p| format!("append:{}", p)),
+    );
+    push("Nice", job.nice.map(|n| n.to_string()));
+    push("UMask", job.umask.map(|u| format!("{:04o}", u)));
+
+    for (k, v) in job.environment_variables.iter().flatten() {
+        push("Environment", Some(quote(&format!("{}={}", k, v), false)));
+    }
+
+    if job.run_at_load == Some(true) {
+        service.extend(vec![
+            "".to_string(),
+            "[Install]".to_string(),
+            "WantedBy=multi-user.target".to_string(),
+        ]);
+    }
+
+    let calendar = job
+        .start_calendar_interval
+        .as_ref()
+        .map(|c| c.as_slice())
+        .unwrap_or_default();
+
+    let timer = if job.start_interval.is_some() || !calendar.is_empty() {
+        let mut timer = vec![
+            "[Unit]".to_string(),
+            format!("Description={}", label),
+            "".to_string(),
+            "[Timer]".to_string(),
+        ];
+
+        if let Some(interval) = job.start_interval {
+            // The first run is an interval after load too
+            timer.push(format!("OnActiveSec={}", interval));
+            timer.push(format!("OnUnitActiveSec={}", interval));
+        }
+
+        for interval in calendar {
+            timer.extend(
+                on_calendar(interval)
+                    .into_iter()
+                    .map(|c| format!("OnCalendar={}", c)),
+            );
+        }
+
+        timer.extend(vec![
+            "".to_string(),
+            "[Install]".to_string(),
+            "WantedBy=timers.target".to_string(),
+        ]);
+
+        Some(timer.join("\n") + "\n")
+    } else {
+        None
+    };
+
+    Ok(SystemdUnits {
+        service: service.join("\n") + "\n",
+        timer,
+        unsupported,
+    })
+}
+
+fn parse_unit(text: &str) -> Result<Vec<Directive>, PlistError> {
+    let mut directives = vec![];
+    let mut section: Option<String> = None;
+    let mut lines = text.lines().enumerate();
+
+    while let Some((i, line)) = lines.next() {
+        let mut line = line.trim().to_string();
+
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        // A trailing backslash continues the line
+        while line.ends_with('\\') {
+            line.pop();
+            line.push(' ');
+
+            match lines.next() {
+                Some((_, next)) => line.push_str(next.trim()),
+                None => break,
+            }
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            section = Some(line[1..line.len() - 1].to_string());
+            continue;
+        }
+
+        let (key, value) = line.split_once('=').ok_or_else(|| {
+            ValueError(format!("line {}", i + 1), "expected Key=Value".to_string())
+        })?;
+        let section = section.clone().ok_or_else(|| {
+            ValueError(format!("line {}", i + 1), "not in a [Section]".to_string())
+        })?;
+
+        directives.push(Directive {
+            section,
+            key: key.trim().to_string(),
+            value: value.trim().to_string(),
+        });
+    }
+
+    Ok(directives)
+}
+
+/// ProgramArguments, and warnings for what they lose
+fn exec_start(value: &str) -> Result<(Vec<String>, Vec<String>), String> {
+    let mut warnings = vec![];
+    let mut command = value;
+    let mut expands_variables = true;
+    let mut replaces_argv0 = false;
+
+    // systemd.service(5) "special executable prefixes"
+    while let Some(prefix) = command.chars().next().filter(|c| "-@:+!".contains(*c)) {
+        match prefix {
+            // Ignore failure
+            '-' => (),
+            '@' => replaces_argv0 = true,
+            ':' => expands_variables = false,
+            _ => warnings.push(format!("the {} prefix is not supported", prefix)),
+        }
+
+        command = &command[1..];
+    }
+
+    let mut arguments = split_words(command)?;
+
+    if arguments.is_empty() {
+        return Err("empty".to_string());
+    }
+
+    // @/bin/program argv0 args...
+    if replaces_argv0 && arguments.len() > 1 {
+        warnings.push("@ is not supported, argv[0] is the program".to_string());
+        arguments.remove(1);
+    }
+
+    if arguments.iter().any(|a| a.contains('%')) {
+        warnings.push("% specifiers are not expanded".to_string());
+    }
+
+    if expands_variables && arguments.iter().any(|a| a.contains('$')) {
+        warnings.push("$ variables are not expanded".to_string());
+    }
+
+    Ok((arguments, warnings))
+}
+
+/// Split like systemd splits ExecStart= and Environment=: on whitespace,
+/// with "double" and 'single' quotes and backslash escapes
+fn split_words(value: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => {
+                let escaped = match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some(c) => c,
+                    None => return Err("trailing backslash".to_string()),
+                };
+
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (c, Some(_)) => word.get_or_insert_with(String::new).push(c),
+            ('"', None) | ('\'', None) => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (c, None) if c.is_whitespace() => words.extend(word.take()),
+            (c, None) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err("unterminated quote".to_string());
+    }
+
+    words.extend(word);
+    Ok(words)
+}
+
+/// The opposite of split_words. ExecStart expands $, Environment doesn't
+fn quote(word: &str, escape_dollar: bool) -> String {
+    let mut escaped = word.replace('%', "%%");
+    if escape_dollar {
+        escaped = escaped.replace('$', "$$");
+    }
+
+    let needs_quotes =
+        escaped.is_empty() || escaped.contains(|c: char| c.is_whitespace() || "\"'\\".contains(c));
+
+    if needs_quotes {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        escaped
+    }
+}
+
+/// systemd.time(7) time spans like "90", "5min" or "1h 30m", in seconds
+fn parse_span(value: &str) -> Result<i64, String> {
+    let not_a_span = || format!("{} is not a time span", value);
+    let mut rest = value.trim();
+    let mut total = 0;
+
+    if rest.is_empty() {
+        return Err(not_a_span());
+    }
+
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let n: i64 = rest[..digits].parse().map_err(|_| not_a_span())?;
+        rest = rest[digits..].trim_start();
+
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit() || c.is_whitespace())
+            .unwrap_or(rest.len());
+
+        let scale = match &rest[..unit_end] {
+            "" | "s" | "sec" | "second" | "seconds" => 1,
+            "m" | "min" | "minute" | "minutes" => 60,
+            "h" | "hr" | "hour" | "hours" => 3600,
+            "d" | "day" | "days" => 86400,
+            "w" | "week" | "weeks" => 604800,
+            _ => return Err(not_a_span()),
+        };
+
+        total += n * scale;
+        rest = rest[unit_end..].trim_start();
+    }
+
+    Ok(total)
+}
+
+/// systemd.time(7) calendar events, as far as StartCalendarInterval
+/// goes: no years, seconds, time zones or last days of the month
+fn parse_calendar(value: &str) -> Result<Vec<CalendarInterval>, String> {
+    let expanded = match value.to_ascii_lowercase().as_str() {
+        "minutely" => "*-*-* *:*:00",
+        "hourly" => "*-*-* *:00:00",
+        "daily" => "*-*-* 00:00:00",
+        "weekly" => "Mon *-*-* 00:00:00",
+        "monthly" => "*-*-01 00:00:00",
+        "quarterly" => "*-01,04,07,10-01 00:00:00",
+        "semiannually" => "*-01,07-01 00:00:00",
+        "yearly" | "annually" => "*-01-01 00:00:00",
+        _ => value,
+    };
+
+    let (mut weekday, mut date, mut time) = (None, "*-*-*", "00:00:00");
+
+    for token in expanded.split_whitespace() {
+        if token.contains(':') {
+            time = token;
+        } else if token.starts_with(|c: char| c.is_ascii_digit() || c == '*') {
+            date = token;
+        } else if weekday.is_none() {
+            weekday = Some(token);
+        } else {
+            return Err(format!("{} is not supported", token));
+        }
+    }
+
+    let weekday = match weekday {
+        Some(w) => expand_field(w, 0, 6, &WEEKDAYS, "..")?,
+        None => None,
+    };
+
+    let (month, day) = match date.split('-').collect::<Vec<&str>>().as_slice() {
+        ["*", month, day] | [month, day] => (*month, *day),
+        [_, _, _] => return Err("years are not supported".to_string()),
+        _ => return Err(format!("{} is not a date", date)),
+    };
+
+    if day.contains('~') {
+        return Err("last days of the month are not supported".to_string());
+    }
+
+    let (hour, minute) = match time.split(':').collect::<Vec<&str>>().as_slice() {
+        [hour, minute] => (*hour, *minute),
+        [hour, minute, second] if second.chars().all(|c| c == '0') => (*hour, *minute),
+        [_, _, _] => return Err("seconds are not supported".to_string()),
+        _ => return Err(format!("{} is not a time", time)),
+    };
+
+    let month = expand_field(month, 1, 12, &[], "..")?;
+    let day = expand_field(day, 1, 31, &[], "..")?;
+
+    // systemd ANDs the weekday with the day, launchd ORs them
+    if weekday.is_some() && day.is_some() {
+        return Err("a weekday together with a day can't be converted".to_string());
+    }
+
+    calendar_intervals(
+        month,
+        day,
+        weekday,
+        expand_field(hour, 0, 23, &[], "..")?,
+        expand_field(minute, 0, 59, &[], "..")?,
+    )
+}
+
+/// OnCalendar values for a StartCalendarInterval entry. launchd runs a
+/// Weekday with a Day on either, which takes two in systemd
+fn on_calendar(interval: &CalendarInterval) -> Vec<String> {
+    let field = |v: Option<i64>| v.map(|v| format!("{:02}", v)).unwrap_or("*".to_string());
+    let time = format!("{}:{}:00", field(interval.hour), field(interval.minute));
+    let date = format!("*-{}-{}", field(interval.month), field(interval.day));
+
+    match interval.weekday {
+        None => vec![format!("{} {}", date, time)],
+        Some(weekday) => {
+            let weekday = format!(
+                "{} *-{}-* {}",
+                WEEKDAYS[(weekday % 7) as usize],
+                field(interval.month),
+                time
+            );
+
+            if interval.day.is_none() {
+                vec![weekday]
+            } else {
+                vec![format!("{} {}", date, time), weekday]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use crate::convert::systemd::{
+        from_systemd, on_calendar, parse_calendar, parse_span, split_words, to_systemd,
+        SystemdUnits,
+    };
+    use crate::launchd_job::{
+        CalendarInterval, KeepAlive, KeepAliveConditions, LaunchdJob, OneOrMany,
+    };
+    use crate::lint::Diagnostic;
+
+    static SERVICE: &str = r#"
+[Unit]
+Description=Nightly backup
+After=network-online.target
+
+[Service]
+Type=oneshot
+ExecStart=/usr/local/bin/backup --dest "/Volumes/Backup Disk" \
+    --quiet
+Restart=on-failure
+RestartSec=30s
+Environment="LANG=en_US.UTF-8" TZ=UTC
+User=backup
+WorkingDirectory=-/var/backup
+StandardOutput=append:/var/log/backup.log
+StandardError=journal
+"#;
+
+    static TIMER: &str = r#"
+[Unit]
+Description=Run backup nightly
+
+[Timer]
+OnCalendar=*-*-* 03:30:00
+OnCalendar=Sat,Sun 12:00
+Persistent=true
+
+[Install]
+WantedBy=timers.target
+"#;
+
+    fn backup_job() -> LaunchdJob {
+        LaunchdJob {
+            label: Some("com.example.backup".to_string()),
+            program_arguments: Some(vec![
+                "/usr/local/bin/backup".to_string(),
+                "--dest".to_string(),
+                "/Volumes/Backup Disk".to_string(),
+                "--quiet".to_string(),
+            ]),
+            keep_alive: Some(KeepAlive::Conditions(KeepAliveConditions {
+                successful_exit: Some(false),
+                ..Default::default()
+            })),
+            throttle_interval: Some(30),
+            environment_variables: Some(
+                vec![
+                    ("LANG".to_string(), "en_US.UTF-8".to_string()),
+                    ("TZ".to_string(), "UTC".to_string()),
+                ]
+                .into_iter()
+                .collect::<IndexMap<String, String>>(),
+            ),
+            user_name: Some("backup".to_string()),
+            working_directory: Some("/var/backup".to_string()),
+            standard_out_path: Some("/var/log/backup.log".to_string()),
+            start_calendar_interval: Some(OneOrMany::Many(vec![
+                CalendarInterval {
+                    hour: Some(3),
+                    minute: Some(30),
+                    ..Default::default()
+                },
+                CalendarInterval {
+                    weekday: Some(0),
+                    hour: Some(12),
+                    minute: Some(0),
+                    ..Default::default()
+                },
+                CalendarInterval {
+                    weekday: Some(6),
+                    hour: Some(12),
+                    minute: Some(0),
+                    ..Default::default()
+                },
+            ])),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn service_and_timer() {
+        let conversion = from_systemd("com.example.backup", SERVICE, Some(TIMER)).unwrap();
+
+        assert_eq!(conversion.job, backup_job());
+        assert_eq!(
+            conversion.unsupported,
+            vec![
+                Diagnostic::warning("Unit.Description", "not supported"),
+                Diagnostic::warning("Unit.After", "not supported"),
+                Diagnostic::warning(
+                    "Service.StandardError",
+                    "journal is not supported, output goes to /dev/null"
+                ),
+                Diagnostic::warning("Unit.Description", "not supported"),
+                Diagnostic::warning(
+                    "Timer.Persistent",
+                    "launchd only catches up on runs missed while asleep, not while off"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn service_only() {
+        let service = "[Service]\nExecStart=-@/bin/sleep sleepy 60\nRestart=always\n\
+                       [Install]\nWantedBy=multi-user.target\n";
+        let conversion = from_systemd("sleep", service, None).unwrap();
+
+        assert_eq!(
+            conversion.job,
+            LaunchdJob {
+                label: Some("sleep".to_string()),
+                program_arguments: Some(vec!["/bin/sleep".to_string(), "60".to_string()]),
+                keep_alive: Some(KeepAlive::Always(true)),
+                run_at_load: Some(true),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            conversion.unsupported,
+            vec![Diagnostic::warning(
+                "Service.ExecStart",
+                "@ is not supported, argv[0] is the program"
+            )]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let cases = vec![
+            ("ExecStart=/bin/true", "line 1: not in a [Section]"),
+            ("[Service]\nExecStart", "line 2: expected Key=Value"),
+            ("[Service]\nUser=nobody", "Service.ExecStart: missing"),
+            (
+                "[Service]\nExecStart=/bin/echo \"hi",
+                "Service.ExecStart: unterminated quote",
+            ),
+        ];
+
+        for (service, expected) in cases {
+            assert_eq!(
+                from_systemd("x", service, None).unwrap_err().to_string(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn calendar() {
+        let time = |hour, minute| CalendarInterval {
+            hour: Some(hour),
+            minute: Some(minute),
+            ..Default::default()
+        };
+
+        assert_eq!(parse_calendar("daily"), Ok(vec![time(0, 0)]));
+        assert_eq!(
+            parse_calendar("Mon..Wed 08:15"),
+            Ok((1..=3)
+                .map(|weekday| CalendarInterval {
+                    weekday: Some(weekday),
+                    ..time(8, 15)
+                })
+                .collect())
+        );
+        assert_eq!(
+            parse_calendar("*-*-01,15 06,18:00"),
+            Ok(vec![
+                CalendarInterval {
+                    day: Some(1),
+                    ..time(6, 0)
+                },
+                CalendarInterval {
+                    day: Some(1),
+                    ..time(18, 0)
+                },
+                CalendarInterval {
+                    day: Some(15),
+                    ..time(6, 0)
+                },
+                CalendarInterval {
+                    day: Some(15),
+                    ..time(18, 0)
+                },
+            ])
+        );
+        assert_eq!(
+            parse_calendar("Mon *-07-* 09:00"),
+            Ok(vec![CalendarInterval {
+                month: Some(7),
+                weekday: Some(1),
+                ..time(9, 0)
+            }])
+        );
+        assert_eq!(
+            parse_calendar("*:0/15").map(|i| i.len()),
+            Ok(4),
+            "every 15 minutes"
+        );
+
+        let errors = vec![
+            ("2024-01-01", "years are not supported"),
+            ("*-*-* 00:00:30", "seconds are not supported"),
+            ("*-02~01", "last days of the month are not supported"),
+            (
+                "Fri *-*-13",
+                "a weekday together with a day can't be converted",
+            ),
+            ("daily UTC", "UTC is not supported"),
+        ];
+
+        for (value, expected) in errors {
+            assert_eq!(
+                parse_calendar(value),
+                Err(expected.to_string()),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn spans_and_words() {
+        assert_eq!(parse_span("90"), Ok(90));
+        assert_eq!(parse_span("1h 30min"), Ok(5400));
+        assert_eq!(parse_span("2d5s"), Ok(172805));
+        assert_eq!(
+            parse_span("soon"),
+            Err("soon is not a time span".to_string())
+        );
+
+        assert_eq!(
+            split_words(r#"a "b c" 'd "e"' f\ g"#),
+            Ok(vec![
+                "a".to_string(),
+                "b c".to_string(),
+                "d \"e\"".to_string(),
+                "f g".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn to_units() {
+        let mut job = backup_job();
+        job.environment_variables
+            .as_mut()
+            .unwrap()
+            .insert("COST".to_string(), "$5 or 10%".to_string());
+        job.start_calendar_interval = Some(OneOrMany::One(CalendarInterval {
+            day: Some(1),
+            weekday: Some(1),
+            hour: Some(3),
+            minute: Some(30),
+            ..Default::default()
+        }));
+        job.start_interval = Some(600);
+        job.low_priority_io = Some(true);
+
+        let SystemdUnits {
+            service,
+            timer,
+            unsupported,
+        } = to_systemd(&job).unwrap();
+
+        assert_eq!(
+            service,
+            r#"[Unit]
+Description=com.example.backup
+
+[Service]
+ExecStart=/usr/local/bin/backup --dest "/Volumes/Backup Disk" --quiet
+Restart=on-failure
+RestartSec=30
+User=backup
+WorkingDirectory=/var/backup
+StandardOutput=append:/var/log/backup.log
+Environment=LANG=en_US.UTF-8
+Environment=TZ=UTC
+Environment="COST=$5 or 10%%"
+"#
+        );
+
+        assert_eq!(
+            timer.unwrap(),
+            r#"[Unit]
+Description=com.example.backup
+
+[Timer]
+OnActiveSec=600
+OnUnitActiveSec=600
+OnCalendar=*-*-01 03:30:00
+OnCalendar=Mon *-*-* 03:30:00
+
+[Install]
+WantedBy=timers.target
+"#
+        );
+
+        assert_eq!(
+            unsupported,
+            vec![Diagnostic::warning("LowPriorityIO", "not supported")]
+        );
+
+        // And back
+        let conversion = from_systemd("com.example.backup", &service, None).unwrap();
+        assert_eq!(
+            conversion.job.program_arguments,
+            backup_job().program_arguments
+        );
+    }
+
+    #[test]
+    fn month_and_weekday() {
+        let interval = CalendarInterval {
+            month: Some(7),
+            weekday: Some(1),
+            hour: Some(3),
+            minute: Some(30),
+            ..Default::default()
+        };
+        assert_eq!(on_calendar(&interval), vec!["Mon *-07-* 03:30:00"]);
+
+        // Month ANDed with both, Day or Weekday
+        let either = CalendarInterval {
+            day: Some(1),
+            ..interval.clone()
+        };
+        assert_eq!(
+            on_calendar(&either),
+            vec!["*-07-01 03:30:00", "Mon *-07-* 03:30:00"]
+        );
+
+        for interval in [interval, either] {
+            let mut job = backup_job();
+            job.start_calendar_interval = Some(OneOrMany::One(interval.clone()));
+
+            let SystemdUnits { service, timer, .. } = to_systemd(&job).unwrap();
+            let conversion =
+                from_systemd("com.example.backup", &service, timer.as_deref()).unwrap();
+
+            let mut intervals = conversion
+                .job
+                .start_calendar_interval
+                .unwrap()
+                .as_slice()
+                .to_vec();
+            intervals.sort_by_key(|i| i.weekday);
+
+            // The Day or Weekday one comes back split in two
+            let expected = match interval.day {
+                None => vec![interval],
+                Some(_) => vec![
+                    CalendarInterval {
+                        weekday: None,
+                        ..interval.clone()
+                    },
+                    CalendarInterval {
+                        day: None,
+                        ..interval
+                    },
+                ],
+            };
+            assert_eq!(intervals, expected);
+        }
+    }
+}
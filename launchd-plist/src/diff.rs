@@ -0,0 +1,180 @@
+use std::fmt;
+
+use plist::Value;
+
+use crate::plist_value::join;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added(String, Value),
+    Removed(String, Value),
+    /// (key path, old, new)
+    Changed(String, Value, Value),
+}
+
+impl Change {
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added(p, _) | Change::Removed(p, _) | Change::Changed(p, _, _) => p,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added(p, v) => write!(f, "+ {}: {}", p, display_value(v)),
+            Change::Removed(p, v) => write!(f, "- {}: {}", p, display_value(v)),
+            Change::Changed(p, old, new) => write!(
+                f,
+                "~ {}: {} -> {}",
+                p,
+                display_value(old),
+                display_value(new)
+            ),
+        }
+    }
+}
+
+/// Key level differences between two plists. Dictionaries are compared key
+/// by key (old order first, then keys only in new), anything else as a whole
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = vec![];
+    diff_at("", old, new, &mut changes);
+    changes
+}
+
+fn diff_at(path: &str, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    let (old_dict, new_dict) = match (old, new) {
+        (Value::Dictionary(o), Value::Dictionary(n)) => (o, n),
+        _ if old == new => return,
+        _ => {
+            changes.push(Change::Changed(path.to_string(), old.clone(), new.clone()));
+            return;
+        }
+    };
+
+    for (k, v) in old_dict.iter() {
+        match new_dict.get(k) {
+            Some(nv) => diff_at(&join(path, k), v, nv, changes),
+            None => changes.push(Change::Removed(join(path, k), v.clone())),
+        }
+    }
+
+    for (k, v) in new_dict.iter() {
+        if !old_dict.contains_key(k) {
+            changes.push(Change::Added(join(path, k), v.clone()));
+        }
+    }
+}
+
+/// One line, plist-ish rendering of a value
+pub fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(r) => r.to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Date(d) => d.to_xml_format(),
+        Value::Data(d) => format!("<{} bytes>", d.len()),
+        Value::Uid(u) => format!("uid({})", u.get()),
+        Value::Array(a) => format!(
+            "[{}]",
+            a.iter()
+                .map(display_value)
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        Value::Dictionary(d) => format!(
+            "{{{}}}",
+            d.iter()
+                .map(|(k, v)| format!("{} = {}", k, display_value(v)))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        _ => "?".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plist::{Dictionary, Value};
+
+    use crate::diff::{diff, Change};
+
+    fn dict(pairs: Vec<(&str, Value)>) -> Value {
+        let mut dict = Dictionary::new();
+
+        for (k, v) in pairs {
+            dict.insert(k.to_string(), v);
+        }
+
+        Value::Dictionary(dict)
+    }
+
+    #[test]
+    fn key_level_changes() {
+        let old = dict(vec![
+            ("Label", Value::from("com.example.job")),
+            ("RunAtLoad", Value::from(true)),
+            (
+                "KeepAlive",
+                dict(vec![("SuccessfulExit", Value::from(false))]),
+            ),
+            ("StartInterval", Value::from(300_i64)),
+        ]);
+
+        let new = dict(vec![
+            ("Label", Value::from("com.example.job")),
+            (
+                "KeepAlive",
+                dict(vec![("SuccessfulExit", Value::from(true))]),
+            ),
+            ("StartInterval", Value::from(600_i64)),
+            (
+                "ProgramArguments",
+                Value::Array(vec![Value::from("/bin/echo"), Value::from("hi")]),
+            ),
+        ]);
+
+        let changes = diff(&old, &new);
+
+        assert_eq!(
+            changes,
+            vec![
+                Change::Removed("RunAtLoad".to_string(), Value::from(true)),
+                Change::Changed(
+                    "KeepAlive.SuccessfulExit".to_string(),
+                    Value::from(false),
+                    Value::from(true)
+                ),
+                Change::Changed(
+                    "StartInterval".to_string(),
+                    Value::from(300_i64),
+                    Value::from(600_i64)
+                ),
+                Change::Added(
+                    "ProgramArguments".to_string(),
+                    Value::Array(vec![Value::from("/bin/echo"), Value::from("hi")])
+                ),
+            ]
+        );
+
+        let lines: Vec<String> = changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "- RunAtLoad: true",
+                "~ KeepAlive.SuccessfulExit: false -> true",
+                "~ StartInterval: 300 -> 600",
+                "+ ProgramArguments: [\"/bin/echo\", \"hi\"]",
+            ]
+        );
+    }
+
+    #[test]
+    fn same_is_empty() {
+        let value = dict(vec![("Label", Value::from("com.example.job"))]);
+        assert!(diff(&value, &value.clone()).is_empty());
+    }
+}
@@ -0,0 +1,474 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Cursor};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchdEntryType {
+    /// Runs on behalf of currently logged in user
+    Agent,
+    /// Global system daemon
+    Daemon,
+}
+
+impl fmt::Display for LaunchdEntryType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchdEntryLocation {
+    /// macOS system provided agent or daemon
+    System,
+    /// Admin provided agent or daemon in /Library,
+    /// would name it admin...but the [sguadlri] filter
+    /// needs uniques
+    Global,
+    /// User provided agent
+    User,
+}
+
+impl fmt::Display for LaunchdEntryLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// What discovery reads, so it can run against any directory tree
+pub trait FileSystem {
+    /// Everything in a directory, as paths in this filesystem
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    fn is_dir(&self, path: &Path) -> bool;
+    /// Following symlinks, which for a RootedFs stay inside root
+    fn stat(&self, path: &Path) -> io::Result<FileStat>;
+}
+
+/// The parts of a file's metadata the audit looks at
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct FileStat {
+    /// Permission bits, with setuid, setgid and sticky
+    pub mode: u32,
+    pub uid: u32,
+}
+
+/// The filesystem under root: / for this Mac, or somewhere another
+/// one is mounted (or copied to). Paths in it are absolute, e.g.
+/// /Library/LaunchDaemons is <root>/Library/LaunchDaemons
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RootedFs {
+    root: PathBuf,
+}
+
+impl RootedFs {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Where a path in this filesystem really is
+    pub fn host_path(&self, path: &Path) -> PathBuf {
+        self.root.join(path.strip_prefix("/").unwrap_or(path))
+    }
+
+    /// The reverse of host_path, None if it's not under root
+    pub fn fs_path(&self, host_path: &Path) -> Option<PathBuf> {
+        host_path
+            .strip_prefix(&self.root)
+            .ok()
+            .map(|p| Path::new("/").join(p))
+    }
+
+    /// host_path with symlinks resolved inside root: an absolute
+    /// target is relative to root, and .. stops at it, so a link
+    /// on another volume can't point at this Mac's files
+    fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        if self.root == Path::new("/") {
+            return Ok(path.to_path_buf());
+        }
+
+        let mut pending: Vec<PathBuf> = path
+            .components()
+            .rev()
+            .map(|c| PathBuf::from(c.as_os_str()))
+            .collect();
+        let mut resolved = PathBuf::from("/");
+        let mut links = 0;
+
+        while let Some(part) = pending.pop() {
+            match part.components().next() {
+                Some(Component::RootDir) => resolved = PathBuf::from("/"),
+                Some(Component::ParentDir) => {
+                    resolved.pop();
+                }
+                Some(Component::Normal(name)) => {
+                    let next = resolved.join(name);
+                    let host = self.host_path(&next);
+
+                    let is_link = fs::symlink_metadata(&host)
+                        .map(|m| m.file_type().is_symlink())
+                        .unwrap_or(false);
+
+                    if !is_link {
+                        resolved = next;
+                        continue;
+                    }
+
+                    links += 1;
+                    if links > 40 {
+                        return Err(io::Error::other(format!(
+                            "Too many symlinks in {}",
+                            path.display()
+                        )));
+                    }
+
+                    let target = fs::read_link(&host)?;
+                    if target.is_absolute() {
+                        resolved = PathBuf::from("/");
+                    }
+                    pending.extend(
+                        target
+                            .components()
+                            .rev()
+                            .map(|c| PathBuf::from(c.as_os_str())),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        Ok(self.host_path(&resolved))
+    }
+}
+
+impl Default for RootedFs {
+    fn default() -> Self {
+        Self::new("/")
+    }
+}
+
+impl FileSystem for RootedFs {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(self.resolve(path)?)?
+            .map(|e| e.map(|e| path.join(e.file_name())))
+            .collect()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(self.resolve(path)?)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.resolve(path).map(|p| p.is_dir()).unwrap_or(false)
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        let meta = fs::metadata(self.resolve(path)?)?;
+
+        Ok(FileStat {
+            mode: meta.mode() & 0o7777,
+            uid: meta.uid(),
+        })
+    }
+}
+
+/// A directory to look for plists in, and what the jobs in it are
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlistRoot {
+    /// Absolute, or ~/ for home directories
+    pub path: String,
+    pub location: LaunchdEntryLocation,
+    pub entry_type: LaunchdEntryType,
+}
+
+impl PlistRoot {
+    pub fn new<S: Into<String>>(
+        path: S,
+        location: LaunchdEntryLocation,
+        entry_type: LaunchdEntryType,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            location,
+            entry_type,
+        }
+    }
+}
+
+/// The LaunchAgents and LaunchDaemons directories launchd loads from
+pub fn default_roots() -> Vec<PlistRoot> {
+    use LaunchdEntryLocation::*;
+    use LaunchdEntryType::*;
+
+    vec![
+        PlistRoot::new("~/Library/LaunchAgents", User, Agent),
+        PlistRoot::new("/Library/LaunchAgents", Global, Agent),
+        PlistRoot::new("/Library/LaunchDaemons", Global, Daemon),
+        PlistRoot::new("/System/Library/LaunchAgents", System, Agent),
+        PlistRoot::new("/System/Library/LaunchDaemons", System, Daemon),
+        PlistRoot::new("/Library/Apple/System/Library/LaunchAgents", System, Agent),
+        PlistRoot::new(
+            "/Library/Apple/System/Library/LaunchDaemons",
+            System,
+            Daemon,
+        ),
+    ]
+}
+
+/// A PlistRoot with ~/ expanded
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PlistDir {
+    pub path: PathBuf,
+    pub location: LaunchdEntryLocation,
+    pub entry_type: LaunchdEntryType,
+}
+
+/// The directories roots are, ~/ being home, and with all_users
+/// (e.g. when running as root) every home in /Users as well
+pub fn plist_dirs<F: FileSystem + ?Sized>(
+    fs: &F,
+    roots: &[PlistRoot],
+    home: &Path,
+    all_users: bool,
+) -> Vec<PlistDir> {
+    let mut homes = vec![home.to_path_buf()];
+
+    if all_users {
+        let mut users: Vec<PathBuf> = fs
+            .read_dir(Path::new("/Users"))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| fs.is_dir(p))
+            .collect();
+
+        users.sort();
+        homes.extend(users);
+    }
+
+    let mut dirs: Vec<PlistDir> = vec![];
+
+    for root in roots {
+        let paths: Vec<PathBuf> = match root.path.strip_prefix("~/") {
+            Some(rest) => homes.iter().map(|h| h.join(rest)).collect(),
+            None => vec![PathBuf::from(&root.path)],
+        };
+
+        for path in paths {
+            if dirs.iter().all(|d| d.path != path) {
+                dirs.push(PlistDir {
+                    path,
+                    location: root.location.clone(),
+                    entry_type: root.entry_type.clone(),
+                });
+            }
+        }
+    }
+
+    dirs
+}
+
+/// The directory a plist is under, the deepest one if they nest
+pub fn classify<'a>(dirs: &'a [PlistDir], path: &Path) -> Option<&'a PlistDir> {
+    dirs.iter()
+        .filter(|d| path.starts_with(&d.path))
+        .max_by_key(|d| d.path.components().count())
+}
+
+pub fn is_plist_path(path: &Path) -> bool {
+    path.extension().map(|e| e == "plist").unwrap_or(false)
+}
+
+/// Plists directly in dirs, ones that can't be read are skipped
+pub fn find_plists<F: FileSystem + ?Sized>(fs: &F, dirs: &[PlistDir]) -> Vec<PathBuf> {
+    dirs.iter()
+        .filter_map(|d| fs.read_dir(&d.path).ok())
+        .flatten()
+        .filter(|p| is_plist_path(p) && !fs.is_dir(p))
+        .collect()
+}
+
+pub fn read_plist<F: FileSystem + ?Sized>(fs: &F, path: &Path) -> Option<plist::Value> {
+    let contents = fs.read(path).ok()?;
+    plist::Value::from_reader(Cursor::new(contents)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use tempfile::TempDir;
+
+    use crate::discovery::{
+        classify, default_roots, find_plists, plist_dirs, read_plist, FileSystem,
+        LaunchdEntryLocation, LaunchdEntryType, PlistRoot, RootedFs,
+    };
+
+    fn tree() -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        let mut dict = plist::Dictionary::new();
+        dict.insert("Label".to_string(), "com.example.job".into());
+        let plist = plist::Value::Dictionary(dict);
+
+        for path in &[
+            "Users/alice/Library/LaunchAgents/com.example.alice.plist",
+            "Users/bob/Library/LaunchAgents/com.example.bob.plist",
+            "Library/LaunchDaemons/com.example.daemon.plist",
+            "Library/Apple/System/Library/LaunchDaemons/com.apple.daemon.plist",
+        ] {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            plist.to_file_xml(path).unwrap();
+        }
+
+        fs::write(root.join("Library/LaunchDaemons/README"), "").unwrap();
+        fs::create_dir_all(root.join("Library/LaunchDaemons/dir.plist")).unwrap();
+
+        dir
+    }
+
+    fn paths(dirs: &[PathBuf]) -> Vec<String> {
+        let mut paths: Vec<String> = dirs
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn discovery() {
+        let root = tree();
+        let fs = RootedFs::new(root.path());
+        let roots = default_roots();
+
+        let dirs = plist_dirs(&fs, &roots, Path::new("/Users/alice"), false);
+        assert_eq!(dirs.len(), 7);
+        assert_eq!(
+            paths(&find_plists(&fs, &dirs)),
+            vec![
+                "/Library/Apple/System/Library/LaunchDaemons/com.apple.daemon.plist",
+                "/Library/LaunchDaemons/com.example.daemon.plist",
+                "/Users/alice/Library/LaunchAgents/com.example.alice.plist",
+            ]
+        );
+
+        // Everyone's LaunchAgents, alice only once
+        let dirs = plist_dirs(&fs, &roots, Path::new("/Users/alice"), true);
+        assert_eq!(dirs.len(), 8);
+        let plists = find_plists(&fs, &dirs);
+        assert_eq!(plists.len(), 4);
+
+        let bob = Path::new("/Users/bob/Library/LaunchAgents/com.example.bob.plist");
+        let dir = classify(&dirs, bob).unwrap();
+        assert_eq!(
+            (&dir.location, &dir.entry_type),
+            (&LaunchdEntryLocation::User, &LaunchdEntryType::Agent)
+        );
+
+        let apple = Path::new("/Library/Apple/System/Library/LaunchDaemons/com.apple.daemon.plist");
+        let dir = classify(&dirs, apple).unwrap();
+        assert_eq!(
+            (&dir.location, &dir.entry_type),
+            (&LaunchdEntryLocation::System, &LaunchdEntryType::Daemon)
+        );
+
+        assert_eq!(
+            classify(&dirs, Path::new("/Library/com.example.plist")),
+            None
+        );
+
+        let plist = read_plist(&fs, apple).unwrap();
+        assert_eq!(
+            plist
+                .as_dictionary()
+                .and_then(|d| d.get("Label"))
+                .and_then(|l| l.as_string()),
+            Some("com.example.job")
+        );
+    }
+
+    #[test]
+    fn nested_roots() {
+        let roots = vec![
+            PlistRoot::new(
+                "/opt/jobs",
+                LaunchdEntryLocation::Global,
+                LaunchdEntryType::Agent,
+            ),
+            PlistRoot::new(
+                "/opt/jobs/daemons",
+                LaunchdEntryLocation::Global,
+                LaunchdEntryType::Daemon,
+            ),
+        ];
+
+        let fs = RootedFs::default();
+        let dirs = plist_dirs(&fs, &roots, Path::new("/nonexistent"), false);
+
+        let dir = classify(&dirs, Path::new("/opt/jobs/daemons/a.plist")).unwrap();
+        assert_eq!(dir.entry_type, LaunchdEntryType::Daemon);
+
+        let dir = classify(&dirs, Path::new("/opt/jobs/a.plist")).unwrap();
+        assert_eq!(dir.entry_type, LaunchdEntryType::Agent);
+
+        // Not just a prefix of the string
+        assert_eq!(classify(&dirs, Path::new("/opt/jobsx/a.plist")), None);
+    }
+
+    #[test]
+    fn rooted_paths() {
+        let fs = RootedFs::new("/Volumes/Evidence");
+        let path = Path::new("/Library/LaunchDaemons/a.plist");
+
+        assert_eq!(
+            fs.host_path(path),
+            Path::new("/Volumes/Evidence/Library/LaunchDaemons/a.plist")
+        );
+        assert_eq!(fs.fs_path(&fs.host_path(path)).as_deref(), Some(path));
+        assert_eq!(fs.fs_path(Path::new("/Library")), None);
+        assert!(!fs.is_dir(Path::new("/")));
+
+        assert_eq!(RootedFs::default().host_path(path), path);
+    }
+
+    #[test]
+    fn symlinks_stay_in_root() {
+        let (root, outside) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (root, outside) = (root.path(), outside.path());
+
+        fs::create_dir_all(root.join("etc")).unwrap();
+        fs::create_dir_all(root.join("Library/LaunchDaemons")).unwrap();
+        fs::write(root.join("etc/job.plist"), "inside").unwrap();
+        fs::write(outside.join("job.plist"), "outside").unwrap();
+
+        let daemons = root.join("Library/LaunchDaemons");
+        // Absolute: /etc on the volume, not the host's
+        std::os::unix::fs::symlink("/etc/job.plist", daemons.join("a.plist")).unwrap();
+        // Absolute into the host's temp dir, which isn't in root
+        std::os::unix::fs::symlink(outside.join("job.plist"), daemons.join("b.plist")).unwrap();
+        // .. past root stops at it
+        std::os::unix::fs::symlink("../../../../../etc/job.plist", daemons.join("c.plist"))
+            .unwrap();
+
+        let fs = RootedFs::new(root);
+        let dir = Path::new("/Library/LaunchDaemons");
+
+        assert_eq!(fs.read(&dir.join("a.plist")).unwrap(), b"inside");
+        assert!(fs.read(&dir.join("b.plist")).is_err());
+        assert!(fs.stat(&dir.join("b.plist")).is_err());
+        assert_eq!(fs.read(&dir.join("c.plist")).unwrap(), b"inside");
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+
+use plist::{Dictionary, Value};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer};
+
+use crate::diff::{diff, Change};
+use crate::launchd_job::{KeepAlive, LaunchdJob, OneOrMany};
+
+/// The service in launchd's reply for a loaded job, the keys it has in
+/// common with the job's plist
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LoadedService {
+    pub program: Option<String>,
+    pub program_arguments: Option<Vec<String>>,
+    pub limit_load_to_session_type: Option<String>,
+    pub standard_out_path: Option<String>,
+    pub standard_error_path: Option<String>,
+    /// The inverse of KeepAlive
+    pub on_demand: Option<bool>,
+    /// Names, launchd has their ports
+    #[serde(default, deserialize_with = "service_names")]
+    pub mach_services: Option<Vec<String>>,
+}
+
+fn service_names<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<String>>, D::Error> {
+    let services: Option<BTreeMap<String, IgnoredAny>> = Option::deserialize(d)?;
+    Ok(services.map(|s| s.keys().cloned().collect()))
+}
+
+/// How the plist on disk differs from what launchd loaded, changes are
+/// from loaded to on disk. Only compares what the plist sets, launchd
+/// fills in the rest with defaults that depend on the domain, so a key
+/// taken out of the plist isn't a change
+pub fn drift(loaded: &LoadedService, job: &LaunchdJob) -> Vec<Change> {
+    let mut was = Dictionary::new();
+    let mut now = Dictionary::new();
+
+    let mut compare = |key: &str, loaded: Option<Value>, on_disk: Option<Value>| {
+        if let Some(v) = loaded {
+            was.insert(key.to_string(), v);
+        }
+
+        if let Some(v) = on_disk {
+            now.insert(key.to_string(), v);
+        }
+    };
+
+    // Without it, launchd's is ProgramArguments[0], compared below
+    if let Some(program) = &job.program {
+        compare(
+            "Program",
+            loaded.program.clone().map(Value::from),
+            Some(Value::from(program.as_str())),
+        );
+    }
+
+    if let Some(args) = &job.program_arguments {
+        compare(
+            "ProgramArguments",
+            loaded.program_arguments.as_deref().map(strings),
+            Some(strings(args)),
+        );
+    }
+
+    // Loaded as one of them
+    if let Some(types) = &job.limit_load_to_session_type {
+        let loaded_type = loaded.limit_load_to_session_type.as_ref();
+        let on_disk = match (loaded_type, types) {
+            (Some(t), _) if types.as_slice().contains(t) => Value::from(t.as_str()),
+            (_, OneOrMany::One(t)) => Value::from(t.as_str()),
+            (_, OneOrMany::Many(ts)) => strings(ts),
+        };
+
+        compare(
+            "LimitLoadToSessionType",
+            loaded_type.cloned().map(Value::from),
+            Some(on_disk),
+        );
+    }
+
+    if let Some(path) = &job.standard_out_path {
+        compare(
+            "StandardOutPath",
+            loaded.standard_out_path.clone().map(Value::from),
+            Some(Value::from(path.as_str())),
+        );
+    }
+
+    if let Some(path) = &job.standard_error_path {
+        compare(
+            "StandardErrorPath",
+            loaded.standard_error_path.clone().map(Value::from),
+            Some(Value::from(path.as_str())),
+        );
+    }
+
+    // Conditions aren't reported, only whether there are any
+    let keep_alive = match &job.keep_alive {
+        Some(KeepAlive::Always(keep_alive)) => Some(*keep_alive),
+        Some(KeepAlive::Conditions(_)) => None,
+        None => job.on_demand.map(|on_demand| !on_demand),
+    };
+
+    if let (Some(on_demand), Some(keep_alive)) = (loaded.on_demand, keep_alive) {
+        compare(
+            "KeepAlive",
+            Some(Value::from(!on_demand)),
+            Some(Value::from(keep_alive)),
+        );
+    }
+
+    if let Some(services) = &job.mach_services {
+        let names: Vec<String> = services.keys().cloned().collect();
+
+        compare(
+            "MachServices",
+            loaded.mach_services.as_deref().map(strings),
+            Some(strings(&names)),
+        );
+    }
+
+    diff(&Value::Dictionary(was), &Value::Dictionary(now))
+}
+
+fn strings(values: &[String]) -> Value {
+    Value::Array(values.iter().map(|v| Value::from(v.as_str())).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+
+    use plist::{Dictionary, Value};
+
+    use crate::diff::Change;
+    use crate::drift::{drift, LoadedService};
+    use crate::launchd_job::{KeepAlive, LaunchdJob, MachService, OneOrMany};
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn service_reply() {
+        let mut services = Dictionary::new();
+        services.insert("com.example.job".to_string(), Value::from(4355));
+
+        let mut service = Dictionary::new();
+        service.insert("PID".to_string(), Value::from(123));
+        service.insert("Program".to_string(), Value::from("/usr/local/bin/job"));
+        service.insert(
+            "ProgramArguments".to_string(),
+            Value::Array(vec![Value::from("/usr/local/bin/job"), Value::from("-v")]),
+        );
+        service.insert("OnDemand".to_string(), Value::from(false));
+        service.insert("MachServices".to_string(), Value::Dictionary(services));
+
+        assert_eq!(
+            plist::from_value::<LoadedService>(&Value::Dictionary(service)).unwrap(),
+            LoadedService {
+                program: Some("/usr/local/bin/job".to_string()),
+                program_arguments: Some(strings(&["/usr/local/bin/job", "-v"])),
+                on_demand: Some(false),
+                mach_services: Some(strings(&["com.example.job"])),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn stale() {
+        let loaded = LoadedService {
+            program: Some("/usr/local/bin/job".to_string()),
+            program_arguments: Some(strings(&["/usr/local/bin/job", "-v"])),
+            limit_load_to_session_type: Some("Aqua".to_string()),
+            on_demand: Some(true),
+            ..Default::default()
+        };
+
+        let mut job = LaunchdJob {
+            program_arguments: Some(strings(&["/usr/local/bin/job", "-v"])),
+            limit_load_to_session_type: Some(OneOrMany::Many(strings(&["Aqua", "Background"]))),
+            ..Default::default()
+        };
+
+        assert!(drift(&loaded, &job).is_empty());
+
+        job.program_arguments = Some(strings(&["/usr/local/bin/job", "-q"]));
+        job.keep_alive = Some(KeepAlive::Always(true));
+        job.standard_out_path = Some("/tmp/job.log".to_string());
+        let mut services = IndexMap::new();
+        services.insert("com.example.job".to_string(), MachService::Enabled(true));
+        job.mach_services = Some(services);
+
+        let lines: Vec<String> = drift(&loaded, &job).iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "~ ProgramArguments: [\"/usr/local/bin/job\", \"-v\"] -> [\"/usr/local/bin/job\", \"-q\"]",
+                "~ KeepAlive: false -> true",
+                "+ StandardOutPath: \"/tmp/job.log\"",
+                "+ MachServices: [\"com.example.job\"]",
+            ]
+        );
+
+        // Not in the plist, launchd's defaults
+        let defaults = LoadedService {
+            standard_error_path: Some("/dev/null".to_string()),
+            mach_services: Some(strings(&["com.example.job"])),
+            on_demand: Some(false),
+            ..loaded.clone()
+        };
+
+        job = LaunchdJob {
+            program_arguments: Some(strings(&["/usr/local/bin/job", "-v"])),
+            ..Default::default()
+        };
+
+        assert!(drift(&defaults, &job).is_empty());
+
+        // Loaded as something the plist doesn't allow anymore
+        job = LaunchdJob {
+            program_arguments: Some(strings(&["/usr/local/bin/job", "-v"])),
+            limit_load_to_session_type: Some(OneOrMany::One("Background".to_string())),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            drift(&loaded, &job),
+            vec![Change::Changed(
+                "LimitLoadToSessionType".to_string(),
+                Value::from("Aqua"),
+                Value::from("Background")
+            )]
+        );
+    }
+}
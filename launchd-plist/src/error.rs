@@ -0,0 +1,21 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PlistError {
+    /// A key with the wrong type or value: (its path, what's wrong).
+    /// The path is empty for the whole plist
+    ValueError(String, String),
+    IOError(String),
+}
+
+impl Display for PlistError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlistError::ValueError(path, e) if !path.is_empty() => write!(f, "{}: {}", path, e),
+            PlistError::ValueError(_, e) | PlistError::IOError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for PlistError {}
@@ -0,0 +1,216 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use serde::Serialize;
+
+/// What the checks need to know about a plist
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PlistLabel {
+    pub path: String,
+    pub label: String,
+    pub run_at_load: bool,
+}
+
+/// Something inconsistent between the plists on disk and what launchd has
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Issue {
+    /// More than one plist has the label, only one of them is used
+    DuplicateLabel { label: String, plists: Vec<String> },
+    /// Loaded, but there's no plist for it
+    Orphan { label: String },
+    /// Has RunAtLoad, but isn't loaded
+    NeverLoaded { label: String, plist: String },
+    /// e.g. com.example.a.plist with the Label com.example.b
+    NameMismatch { label: String, plist: String },
+}
+
+impl Issue {
+    /// e.g. duplicate-label, as in the serialized kind
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Issue::DuplicateLabel { .. } => "duplicate-label",
+            Issue::Orphan { .. } => "orphan",
+            Issue::NeverLoaded { .. } => "never-loaded",
+            Issue::NameMismatch { .. } => "name-mismatch",
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            Issue::DuplicateLabel { label, .. }
+            | Issue::Orphan { label }
+            | Issue::NeverLoaded { label, .. }
+            | Issue::NameMismatch { label, .. } => label,
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::DuplicateLabel { label, plists } => {
+                write!(f, "{} is in {}", label, plists.join(", "))
+            }
+            Issue::Orphan { label } => write!(f, "{} is loaded, but has no plist", label),
+            Issue::NeverLoaded { label, plist } => {
+                write!(f, "{} has RunAtLoad but isn't loaded ({})", label, plist)
+            }
+            Issue::NameMismatch { label, plist } => {
+                write!(f, "{} doesn't match its file name ({})", label, plist)
+            }
+        }
+    }
+}
+
+/// Check plists against each other, and against the loaded labels if
+/// launchd can be asked (not offline). Sorted by label
+pub fn check(plists: &[PlistLabel], loaded: Option<&HashSet<String>>) -> Vec<Issue> {
+    let mut issues = vec![];
+    let mut by_label: BTreeMap<&str, Vec<&PlistLabel>> = BTreeMap::new();
+
+    let mut sorted: Vec<&PlistLabel> = plists.iter().collect();
+    sorted.sort_by(|a, b| a.path.cmp(&b.path));
+
+    for plist in sorted {
+        by_label.entry(&plist.label).or_default().push(plist);
+    }
+
+    for (&label, same) in by_label.iter() {
+        if same.len() > 1 {
+            issues.push(Issue::DuplicateLabel {
+                label: label.to_string(),
+                plists: same.iter().map(|p| p.path.clone()).collect(),
+            });
+        }
+
+        for plist in same.iter() {
+            let stem = Path::new(&plist.path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string());
+
+            if stem.as_deref() != Some(label) {
+                issues.push(Issue::NameMismatch {
+                    label: label.to_string(),
+                    plist: plist.path.clone(),
+                });
+            }
+        }
+
+        let never_loaded = loaded
+            .filter(|l| !l.contains(label))
+            .and_then(|_| same.iter().find(|p| p.run_at_load));
+
+        if let Some(plist) = never_loaded {
+            issues.push(Issue::NeverLoaded {
+                label: label.to_string(),
+                plist: plist.path.clone(),
+            });
+        }
+    }
+
+    let mut orphans: Vec<&String> = loaded
+        .map(|l| {
+            l.iter()
+                .filter(|l| !by_label.contains_key(l.as_str()))
+                .collect()
+        })
+        .unwrap_or_default();
+    orphans.sort();
+
+    issues.extend(orphans.into_iter().map(|label| Issue::Orphan {
+        label: label.clone(),
+    }));
+
+    issues.sort_by(|a, b| a.label().cmp(b.label()));
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::integrity::{check, Issue, PlistLabel};
+
+    fn plist(path: &str, label: &str, run_at_load: bool) -> PlistLabel {
+        PlistLabel {
+            path: path.to_string(),
+            label: label.to_string(),
+            run_at_load,
+        }
+    }
+
+    #[test]
+    fn issues() {
+        let plists = vec![
+            plist(
+                "/Library/LaunchDaemons/com.example.ok.plist",
+                "com.example.ok",
+                true,
+            ),
+            plist(
+                "/Library/LaunchDaemons/com.example.dup.plist",
+                "com.example.dup",
+                false,
+            ),
+            plist(
+                "/Library/LaunchAgents/com.example.dup.plist",
+                "com.example.dup",
+                false,
+            ),
+            plist(
+                "/Library/LaunchAgents/renamed.plist",
+                "com.example.named",
+                false,
+            ),
+            plist(
+                "/Library/LaunchAgents/com.example.idle.plist",
+                "com.example.idle",
+                true,
+            ),
+        ];
+        let loaded: HashSet<String> = vec!["com.example.ok", "com.example.orphan"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        let issues = check(&plists, Some(&loaded));
+        let expected = vec![
+            Issue::DuplicateLabel {
+                label: "com.example.dup".to_string(),
+                plists: vec![
+                    "/Library/LaunchAgents/com.example.dup.plist".to_string(),
+                    "/Library/LaunchDaemons/com.example.dup.plist".to_string(),
+                ],
+            },
+            Issue::NeverLoaded {
+                label: "com.example.idle".to_string(),
+                plist: "/Library/LaunchAgents/com.example.idle.plist".to_string(),
+            },
+            Issue::NameMismatch {
+                label: "com.example.named".to_string(),
+                plist: "/Library/LaunchAgents/renamed.plist".to_string(),
+            },
+            Issue::Orphan {
+                label: "com.example.orphan".to_string(),
+            },
+        ];
+        assert_eq!(issues, expected);
+        assert_eq!(
+            serde_json::to_value(&issues[3]).unwrap(),
+            serde_json::json!({ "kind": issues[3].kind(), "label": "com.example.orphan" })
+        );
+
+        assert_eq!(
+            issues[0].to_string(),
+            "com.example.dup is in /Library/LaunchAgents/com.example.dup.plist, \
+             /Library/LaunchDaemons/com.example.dup.plist"
+        );
+
+        // Nothing is known to be loaded offline
+        let offline = check(&plists, None);
+        let labels: Vec<&str> = offline.iter().map(|i| i.label()).collect();
+        assert_eq!(labels, vec!["com.example.dup", "com.example.named"]);
+    }
+}
@@ -0,0 +1,241 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use plist::{Date, Dictionary, Integer, Uid, Value};
+use serde_json::{Map, Number, Value as Json};
+
+use crate::error::PlistError;
+use crate::error::PlistError::ValueError;
+use crate::plist_value::join;
+
+/// Plist types without a JSON equivalent become a single key
+/// object, e.g. {"$date": "2021-06-01T12:00:00Z"}. Dictionary keys
+/// starting with $ are escaped with another $. See doc/edit_formats.md
+static DATE: &str = "$date";
+static DATA: &str = "$data";
+static UID: &str = "$uid";
+static REAL: &str = "$real";
+
+fn tagged(tag: &str, value: Json) -> Json {
+    let mut map = Map::new();
+    map.insert(tag.to_string(), value);
+    Json::Object(map)
+}
+
+/// JSON for a plist value, for editing as JSON (or anything serde can
+/// write a serde_json::Value as, like YAML and TOML)
+pub fn to_json(value: &Value) -> Json {
+    match value {
+        Value::String(s) => Json::String(s.clone()),
+        Value::Boolean(b) => Json::Bool(*b),
+        Value::Integer(i) => match (i.as_signed(), i.as_unsigned()) {
+            (Some(i), _) => Json::from(i),
+            (_, Some(u)) => Json::from(u),
+            _ => unreachable!("plist integers are i64 or u64"),
+        },
+        Value::Real(r) => match Number::from_f64(*r) {
+            Some(n) => Json::Number(n),
+            // NaN and infinities
+            None => tagged(REAL, Json::String(r.to_string())),
+        },
+        Value::Date(d) => tagged(DATE, Json::String(d.to_xml_format())),
+        Value::Data(d) => tagged(DATA, Json::String(STANDARD.encode(d))),
+        Value::Uid(u) => tagged(UID, Json::from(u.get())),
+        Value::Array(a) => Json::Array(a.iter().map(to_json).collect()),
+        Value::Dictionary(d) => Json::Object(
+            d.iter()
+                .map(|(k, v)| {
+                    let key = if k.starts_with('$') {
+                        format!("${}", k)
+                    } else {
+                        k.clone()
+                    };
+
+                    (key, to_json(v))
+                })
+                .collect(),
+        ),
+        _ => Json::Null,
+    }
+}
+
+/// Plist value back from to_json's output (after it was edited)
+pub fn from_json(json: &Json) -> Result<Value, PlistError> {
+    from_json_at("", json)
+}
+
+fn from_json_at(path: &str, json: &Json) -> Result<Value, PlistError> {
+    let error = |msg: String| ValueError(path.to_string(), msg);
+
+    match json {
+        Json::Null => Err(error("null has no plist type".to_string())),
+        Json::Bool(b) => Ok(Value::Boolean(*b)),
+        Json::String(s) => Ok(Value::String(s.clone())),
+        Json::Number(n) => match (n.as_i64(), n.as_u64(), n.as_f64()) {
+            (Some(i), _, _) if !n.is_f64() => Ok(Value::Integer(Integer::from(i))),
+            (_, Some(u), _) if !n.is_f64() => Ok(Value::Integer(Integer::from(u))),
+            (_, _, Some(f)) => Ok(Value::Real(f)),
+            _ => Err(error(format!("{} is not a number", n))),
+        },
+        Json::Array(a) => a
+            .iter()
+            .enumerate()
+            .map(|(i, v)| from_json_at(&format!("{}[{}]", path, i), v))
+            .collect::<Result<Vec<Value>, PlistError>>()
+            .map(Value::Array),
+        Json::Object(map) => {
+            let tag = map
+                .keys()
+                .find(|k| k.starts_with('$') && !k.starts_with("$$"));
+
+            match tag {
+                Some(tag) if map.len() == 1 => from_tagged(tag, &map[tag]).map_err(error),
+                Some(tag) => Err(error(format!(
+                    "{} must be the only key, escape it as ${} if it is a plist key",
+                    tag, tag
+                ))),
+                None => {
+                    let mut dict = Dictionary::new();
+
+                    for (k, v) in map.iter() {
+                        let key = k.strip_prefix('$').unwrap_or(k);
+                        dict.insert(key.to_string(), from_json_at(&join(path, key), v)?);
+                    }
+
+                    Ok(Value::Dictionary(dict))
+                }
+            }
+        }
+    }
+}
+
+/// Same names as kind() for plists
+fn json_kind(json: &Json) -> &'static str {
+    match json {
+        Json::Null => "null",
+        Json::Bool(_) => "bool",
+        Json::Number(n) if n.is_f64() => "real",
+        Json::Number(_) => "integer",
+        Json::String(_) => "string",
+        Json::Array(_) => "array",
+        Json::Object(_) => "dict",
+    }
+}
+
+fn from_tagged(tag: &str, value: &Json) -> Result<Value, String> {
+    let as_str = || {
+        value
+            .as_str()
+            .ok_or_else(|| format!("{} expects a string, got {}", tag, json_kind(value)))
+    };
+
+    match tag {
+        t if t == DATE => Date::from_xml_format(as_str()?)
+            .map(Value::Date)
+            .map_err(|e| format!("{}: {}", DATE, e)),
+        t if t == DATA => STANDARD
+            .decode(as_str()?)
+            .map(Value::Data)
+            .map_err(|e| format!("{}: {}", DATA, e)),
+        t if t == UID => value
+            .as_u64()
+            .map(|u| Value::Uid(Uid::new(u)))
+            .ok_or_else(|| format!("{} expects an unsigned integer", UID)),
+        t if t == REAL => as_str()?
+            .parse::<f64>()
+            .map(Value::Real)
+            .map_err(|e| format!("{}: {}", REAL, e)),
+        _ => Err(format!(
+            "unknown {}, escape it as ${} if it is a plist key",
+            tag, tag
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use plist::{Date, Dictionary, Uid, Value};
+    use serde_json::json;
+
+    use crate::interchange::{from_json, to_json};
+
+    #[test]
+    fn round_trip() {
+        let mut dict = Dictionary::new();
+        dict.insert("Label".to_string(), Value::from("com.example.job"));
+        dict.insert("RunAtLoad".to_string(), Value::from(true));
+        dict.insert("Nice".to_string(), Value::from(-5_i64));
+        dict.insert("Big".to_string(), Value::from(u64::MAX));
+        dict.insert("Real".to_string(), Value::Real(1.0));
+        dict.insert("Inf".to_string(), Value::Real(f64::INFINITY));
+        dict.insert(
+            "Date".to_string(),
+            Value::Date(Date::from(UNIX_EPOCH + Duration::from_secs(1622548800))),
+        );
+        dict.insert("Data".to_string(), Value::Data(vec![0, 1, 2, 255]));
+        dict.insert("Uid".to_string(), Value::Uid(Uid::new(7)));
+        dict.insert("$weird".to_string(), Value::Array(vec![Value::from("a")]));
+
+        let value = Value::Dictionary(dict);
+        let json = to_json(&value);
+
+        assert_eq!(
+            json,
+            json!({
+                "Label": "com.example.job",
+                "RunAtLoad": true,
+                "Nice": -5,
+                "Big": u64::MAX,
+                "Real": 1.0,
+                "Inf": {"$real": "inf"},
+                "Date": {"$date": "2021-06-01T12:00:00Z"},
+                "Data": {"$data": "AAEC/w=="},
+                "Uid": {"$uid": 7},
+                "$$weird": ["a"],
+            })
+        );
+
+        // Through text, like it would be edited
+        let text = serde_json::to_string_pretty(&json).unwrap();
+        let edited: serde_json::Value = serde_json::from_str(&text).unwrap();
+        let back = from_json(&edited).unwrap();
+
+        assert_eq!(back, value);
+        assert_eq!(
+            back.as_dictionary().unwrap().keys().collect::<Vec<_>>(),
+            value.as_dictionary().unwrap().keys().collect::<Vec<_>>()
+        );
+
+        // Fractions of a second are kept
+        let subsec = Value::Date(Date::from(
+            UNIX_EPOCH + Duration::from_millis(1_622_548_800_250),
+        ));
+        let json = to_json(&subsec);
+        assert_eq!(json, json!({"$date": "2021-06-01T12:00:00.25Z"}));
+        assert_eq!(from_json(&json).unwrap(), subsec);
+    }
+
+    #[test]
+    fn errors() {
+        let cases = vec![
+            (json!({"Label": null}), "Label: null has no plist type"),
+            (
+                json!({"Sockets": {"Listeners": [{"$date": 5}]}}),
+                "Sockets.Listeners[0]: $date expects a string, got integer",
+            ),
+            (
+                json!({"$date": "2021-06-01T12:00:00Z", "Label": "x"}),
+                "$date must be the only key, escape it as $$date if it is a plist key",
+            ),
+            (
+                json!({"$when": "now"}),
+                "unknown $when, escape it as $$when if it is a plist key",
+            ),
+        ];
+
+        for (json, expected) in cases {
+            assert_eq!(from_json(&json).unwrap_err().to_string(), expected);
+        }
+    }
+}
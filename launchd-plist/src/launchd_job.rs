@@ -0,0 +1,860 @@
+use std::convert::TryFrom;
+use std::path::Path;
+
+use indexmap::IndexMap;
+use plist::{Dictionary, Value};
+
+use crate::error::PlistError;
+use crate::plist_value::{type_error, DictBuilder, Fields, FromPlist, ToPlist};
+
+/// A job's launchd.plist, see launchd.plist(5). Keys launchd reads are
+/// typed, everything else is kept in `other` so a job written back
+/// has the same keys and values it was read with.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LaunchdJob {
+    pub label: Option<String>,
+    pub disabled: Option<bool>,
+    pub user_name: Option<String>,
+    pub group_name: Option<String>,
+    pub inetd_compatibility: Option<InetdCompatibility>,
+    pub limit_load_to_hosts: Option<Vec<String>>,
+    pub limit_load_from_hosts: Option<Vec<String>>,
+    /// Aqua, Background, LoginWindow, StandardIO or System
+    pub limit_load_to_session_type: Option<OneOrMany<String>>,
+    pub program: Option<String>,
+    pub program_arguments: Option<Vec<String>>,
+    pub enable_globbing: Option<bool>,
+    pub enable_transactions: Option<bool>,
+    pub enable_pressured_exit: Option<bool>,
+    /// Deprecated, the inverse of KeepAlive
+    pub on_demand: Option<bool>,
+    pub service_ipc: Option<bool>,
+    pub keep_alive: Option<KeepAlive>,
+    pub run_at_load: Option<bool>,
+    pub root_directory: Option<String>,
+    pub working_directory: Option<String>,
+    pub environment_variables: Option<IndexMap<String, String>>,
+    pub umask: Option<i64>,
+    pub time_out: Option<i64>,
+    pub exit_time_out: Option<i64>,
+    pub throttle_interval: Option<i64>,
+    pub init_groups: Option<bool>,
+    pub watch_paths: Option<Vec<String>>,
+    pub queue_directories: Option<Vec<String>>,
+    pub start_on_mount: Option<bool>,
+    /// Seconds
+    pub start_interval: Option<i64>,
+    pub start_calendar_interval: Option<OneOrMany<CalendarInterval>>,
+    pub standard_in_path: Option<String>,
+    pub standard_out_path: Option<String>,
+    pub standard_error_path: Option<String>,
+    pub debug: Option<bool>,
+    pub wait_for_debugger: Option<bool>,
+    pub soft_resource_limits: Option<ResourceLimits>,
+    pub hard_resource_limits: Option<ResourceLimits>,
+    pub nice: Option<i64>,
+    /// Background, Standard, Adaptive or Interactive
+    pub process_type: Option<String>,
+    pub abandon_process_group: Option<bool>,
+    pub low_priority_io: Option<bool>,
+    pub low_priority_background_io: Option<bool>,
+    pub launch_only_once: Option<bool>,
+    pub session_create: Option<bool>,
+    pub mach_services: Option<IndexMap<String, MachService>>,
+    /// Socket name -> one or more sockets
+    pub sockets: Option<IndexMap<String, OneOrMany<Socket>>>,
+    pub associated_bundle_identifiers: Option<OneOrMany<String>>,
+    /// Keys not listed above, in file order. Read tolerantly, also
+    /// the ones with a value of the wrong type
+    pub other: Dictionary,
+    pub key_order: KeyOrder,
+}
+
+/// The order a dictionary's keys were in, so it's written back the same
+/// way. Not part of ==, the same keys and values in another order are equal
+#[derive(Debug, Clone, Default)]
+pub struct KeyOrder(pub(crate) Vec<String>);
+
+impl PartialEq for KeyOrder {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// Keys that take either a value or an array of them, kept as written
+#[derive(Debug, Clone, PartialEq)]
+pub enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T> OneOrMany<T> {
+    pub fn as_slice(&self) -> &[T] {
+        match self {
+            OneOrMany::One(v) => std::slice::from_ref(v),
+            OneOrMany::Many(vs) => vs,
+        }
+    }
+}
+
+// Conditions is most of a job, and as common as a bool
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeepAlive {
+    Always(bool),
+    Conditions(KeepAliveConditions),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeepAliveConditions {
+    pub successful_exit: Option<bool>,
+    pub network_state: Option<bool>,
+    /// Path -> keep alive while it exists (true) or doesn't (false)
+    pub path_state: Option<IndexMap<String, bool>>,
+    /// Label -> keep alive while it is loaded (true) or not (false)
+    pub other_job_enabled: Option<IndexMap<String, bool>>,
+    pub crashed: Option<bool>,
+    pub other: Dictionary,
+    pub key_order: KeyOrder,
+}
+
+/// One StartCalendarInterval entry, missing fields are wildcards
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CalendarInterval {
+    pub minute: Option<i64>,
+    pub hour: Option<i64>,
+    pub day: Option<i64>,
+    /// 0 and 7 are Sunday
+    pub weekday: Option<i64>,
+    pub month: Option<i64>,
+    pub other: Dictionary,
+    pub key_order: KeyOrder,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    pub core: Option<i64>,
+    pub cpu: Option<i64>,
+    pub data: Option<i64>,
+    pub file_size: Option<i64>,
+    pub memory_lock: Option<i64>,
+    pub number_of_files: Option<i64>,
+    pub number_of_processes: Option<i64>,
+    pub resident_set_size: Option<i64>,
+    pub stack: Option<i64>,
+    pub other: Dictionary,
+    pub key_order: KeyOrder,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InetdCompatibility {
+    pub wait: Option<bool>,
+    pub other: Dictionary,
+    pub key_order: KeyOrder,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MachService {
+    Enabled(bool),
+    Options(MachServiceOptions),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MachServiceOptions {
+    pub reset_at_close: Option<bool>,
+    pub hide_until_check_in: Option<bool>,
+    pub other: Dictionary,
+    pub key_order: KeyOrder,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Socket {
+    /// stream, dgram or seqpacket
+    pub sock_type: Option<String>,
+    pub sock_passive: Option<bool>,
+    pub sock_node_name: Option<String>,
+    pub sock_service_name: Option<SockServiceName>,
+    /// IPv4, IPv6 or IPv4v6
+    pub sock_family: Option<String>,
+    pub sock_protocol: Option<String>,
+    pub sock_path_name: Option<String>,
+    pub secure_socket_with_key: Option<String>,
+    pub sock_path_mode: Option<i64>,
+    /// Bonjour, MulticastGroup etc.
+    pub other: Dictionary,
+    pub key_order: KeyOrder,
+}
+
+/// SockServiceName is a service name from /etc/services or a port
+#[derive(Debug, Clone, PartialEq)]
+pub enum SockServiceName {
+    Name(String),
+    Port(i64),
+}
+
+impl LaunchdJob {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PlistError> {
+        let value = Value::from_file(path.as_ref()).map_err(|e| {
+            PlistError::IOError(format!("{}: {}", path.as_ref().to_string_lossy(), e))
+        })?;
+
+        Self::try_from(&value)
+    }
+
+    /// ProgramArguments[0] if Program is not set, like launchd
+    pub fn executable(&self) -> Option<&str> {
+        self.program
+            .as_deref()
+            .or_else(|| self.program_arguments.as_ref()?.first().map(|s| s.as_str()))
+    }
+
+    pub fn to_dictionary(&self) -> Dictionary {
+        DictBuilder::default()
+            .put("Label", &self.label)
+            .put("Disabled", &self.disabled)
+            .put("UserName", &self.user_name)
+            .put("GroupName", &self.group_name)
+            .put("inetdCompatibility", &self.inetd_compatibility)
+            .put("LimitLoadToHosts", &self.limit_load_to_hosts)
+            .put("LimitLoadFromHosts", &self.limit_load_from_hosts)
+            .put("LimitLoadToSessionType", &self.limit_load_to_session_type)
+            .put("Program", &self.program)
+            .put("ProgramArguments", &self.program_arguments)
+            .put("EnableGlobbing", &self.enable_globbing)
+            .put("EnableTransactions", &self.enable_transactions)
+            .put("EnablePressuredExit", &self.enable_pressured_exit)
+            .put("OnDemand", &self.on_demand)
+            .put("ServiceIPC", &self.service_ipc)
+            .put("KeepAlive", &self.keep_alive)
+            .put("RunAtLoad", &self.run_at_load)
+            .put("RootDirectory", &self.root_directory)
+            .put("WorkingDirectory", &self.working_directory)
+            .put("EnvironmentVariables", &self.environment_variables)
+            .put("Umask", &self.umask)
+            .put("TimeOut", &self.time_out)
+            .put("ExitTimeOut", &self.exit_time_out)
+            .put("ThrottleInterval", &self.throttle_interval)
+            .put("InitGroups", &self.init_groups)
+            .put("WatchPaths", &self.watch_paths)
+            .put("QueueDirectories", &self.queue_directories)
+            .put("StartOnMount", &self.start_on_mount)
+            .put("StartInterval", &self.start_interval)
+            .put("StartCalendarInterval", &self.start_calendar_interval)
+            .put("StandardInPath", &self.standard_in_path)
+            .put("StandardOutPath", &self.standard_out_path)
+            .put("StandardErrorPath", &self.standard_error_path)
+            .put("Debug", &self.debug)
+            .put("WaitForDebugger", &self.wait_for_debugger)
+            .put("SoftResourceLimits", &self.soft_resource_limits)
+            .put("HardResourceLimits", &self.hard_resource_limits)
+            .put("Nice", &self.nice)
+            .put("ProcessType", &self.process_type)
+            .put("AbandonProcessGroup", &self.abandon_process_group)
+            .put("LowPriorityIO", &self.low_priority_io)
+            .put("LowPriorityBackgroundIO", &self.low_priority_background_io)
+            .put("LaunchOnlyOnce", &self.launch_only_once)
+            .put("SessionCreate", &self.session_create)
+            .put("MachServices", &self.mach_services)
+            .put("Sockets", &self.sockets)
+            .put(
+                "AssociatedBundleIdentifiers",
+                &self.associated_bundle_identifiers,
+            )
+            .extend(&self.other, &self.key_order)
+    }
+}
+
+impl FromPlist for LaunchdJob {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        Self::read(&mut Fields::new(value, path)?)
+    }
+}
+
+impl LaunchdJob {
+    /// Like TryFrom, but a key with a value of the wrong type is kept in
+    /// other as it is, instead of failing the whole job. The errors for
+    /// those keys come with it
+    pub fn tolerant(value: &Value) -> Result<(Self, Vec<PlistError>), PlistError> {
+        let mut f = Fields::tolerant(value, "")?;
+        let job = Self::read(&mut f)?;
+
+        Ok((job, f.errors()))
+    }
+
+    fn read(f: &mut Fields) -> Result<Self, PlistError> {
+        Ok(Self {
+            label: f.get("Label")?,
+            disabled: f.get("Disabled")?,
+            user_name: f.get("UserName")?,
+            group_name: f.get("GroupName")?,
+            inetd_compatibility: f.get("inetdCompatibility")?,
+            limit_load_to_hosts: f.get("LimitLoadToHosts")?,
+            limit_load_from_hosts: f.get("LimitLoadFromHosts")?,
+            limit_load_to_session_type: f.get("LimitLoadToSessionType")?,
+            program: f.get("Program")?,
+            program_arguments: f.get("ProgramArguments")?,
+            enable_globbing: f.get("EnableGlobbing")?,
+            enable_transactions: f.get("EnableTransactions")?,
+            enable_pressured_exit: f.get("EnablePressuredExit")?,
+            on_demand: f.get("OnDemand")?,
+            service_ipc: f.get("ServiceIPC")?,
+            keep_alive: f.get("KeepAlive")?,
+            run_at_load: f.get("RunAtLoad")?,
+            root_directory: f.get("RootDirectory")?,
+            working_directory: f.get("WorkingDirectory")?,
+            environment_variables: f.get("EnvironmentVariables")?,
+            umask: f.get("Umask")?,
+            time_out: f.get("TimeOut")?,
+            exit_time_out: f.get("ExitTimeOut")?,
+            throttle_interval: f.get("ThrottleInterval")?,
+            init_groups: f.get("InitGroups")?,
+            watch_paths: f.get("WatchPaths")?,
+            queue_directories: f.get("QueueDirectories")?,
+            start_on_mount: f.get("StartOnMount")?,
+            start_interval: f.get("StartInterval")?,
+            start_calendar_interval: f.get("StartCalendarInterval")?,
+            standard_in_path: f.get("StandardInPath")?,
+            standard_out_path: f.get("StandardOutPath")?,
+            standard_error_path: f.get("StandardErrorPath")?,
+            debug: f.get("Debug")?,
+            wait_for_debugger: f.get("WaitForDebugger")?,
+            soft_resource_limits: f.get("SoftResourceLimits")?,
+            hard_resource_limits: f.get("HardResourceLimits")?,
+            nice: f.get("Nice")?,
+            process_type: f.get("ProcessType")?,
+            abandon_process_group: f.get("AbandonProcessGroup")?,
+            low_priority_io: f.get("LowPriorityIO")?,
+            low_priority_background_io: f.get("LowPriorityBackgroundIO")?,
+            launch_only_once: f.get("LaunchOnlyOnce")?,
+            session_create: f.get("SessionCreate")?,
+            mach_services: f.get("MachServices")?,
+            sockets: f.get("Sockets")?,
+            associated_bundle_identifiers: f.get("AssociatedBundleIdentifiers")?,
+            key_order: f.key_order(),
+            other: f.rest(),
+        })
+    }
+}
+
+impl ToPlist for LaunchdJob {
+    fn to_plist(&self) -> Value {
+        Value::Dictionary(self.to_dictionary())
+    }
+}
+
+impl TryFrom<&Value> for LaunchdJob {
+    type Error = PlistError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        Self::from_plist(value, "")
+    }
+}
+
+impl From<&LaunchdJob> for Value {
+    fn from(job: &LaunchdJob) -> Self {
+        job.to_plist()
+    }
+}
+
+impl<T: FromPlist> FromPlist for OneOrMany<T> {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        match value {
+            Value::Array(_) => Vec::from_plist(value, path).map(OneOrMany::Many),
+            _ => T::from_plist(value, path).map(OneOrMany::One),
+        }
+    }
+}
+
+impl<T: ToPlist> ToPlist for OneOrMany<T> {
+    fn to_plist(&self) -> Value {
+        match self {
+            OneOrMany::One(v) => v.to_plist(),
+            OneOrMany::Many(vs) => vs.to_plist(),
+        }
+    }
+}
+
+impl FromPlist for KeepAlive {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        match value {
+            Value::Boolean(b) => Ok(KeepAlive::Always(*b)),
+            Value::Dictionary(_) => {
+                KeepAliveConditions::from_plist(value, path).map(KeepAlive::Conditions)
+            }
+            _ => Err(type_error(value, path, "bool or dict")),
+        }
+    }
+}
+
+impl ToPlist for KeepAlive {
+    fn to_plist(&self) -> Value {
+        match self {
+            KeepAlive::Always(b) => b.to_plist(),
+            KeepAlive::Conditions(c) => c.to_plist(),
+        }
+    }
+}
+
+impl FromPlist for KeepAliveConditions {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        let mut f = Fields::new(value, path)?;
+
+        Ok(Self {
+            successful_exit: f.get("SuccessfulExit")?,
+            network_state: f.get("NetworkState")?,
+            path_state: f.get("PathState")?,
+            other_job_enabled: f.get("OtherJobEnabled")?,
+            crashed: f.get("Crashed")?,
+            key_order: f.key_order(),
+            other: f.rest(),
+        })
+    }
+}
+
+impl ToPlist for KeepAliveConditions {
+    fn to_plist(&self) -> Value {
+        DictBuilder::default()
+            .put("SuccessfulExit", &self.successful_exit)
+            .put("NetworkState", &self.network_state)
+            .put("PathState", &self.path_state)
+            .put("OtherJobEnabled", &self.other_job_enabled)
+            .put("Crashed", &self.crashed)
+            .extend(&self.other, &self.key_order)
+            .into()
+    }
+}
+
+impl FromPlist for CalendarInterval {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        let mut f = Fields::new(value, path)?;
+
+        Ok(Self {
+            minute: f.get("Minute")?,
+            hour: f.get("Hour")?,
+            day: f.get("Day")?,
+            weekday: f.get("Weekday")?,
+            month: f.get("Month")?,
+            key_order: f.key_order(),
+            other: f.rest(),
+        })
+    }
+}
+
+impl ToPlist for CalendarInterval {
+    fn to_plist(&self) -> Value {
+        DictBuilder::default()
+            .put("Minute", &self.minute)
+            .put("Hour", &self.hour)
+            .put("Day", &self.day)
+            .put("Weekday", &self.weekday)
+            .put("Month", &self.month)
+            .extend(&self.other, &self.key_order)
+            .into()
+    }
+}
+
+impl FromPlist for ResourceLimits {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        let mut f = Fields::new(value, path)?;
+
+        Ok(Self {
+            core: f.get("Core")?,
+            cpu: f.get("CPU")?,
+            data: f.get("Data")?,
+            file_size: f.get("FileSize")?,
+            memory_lock: f.get("MemoryLock")?,
+            number_of_files: f.get("NumberOfFiles")?,
+            number_of_processes: f.get("NumberOfProcesses")?,
+            resident_set_size: f.get("ResidentSetSize")?,
+            stack: f.get("Stack")?,
+            key_order: f.key_order(),
+            other: f.rest(),
+        })
+    }
+}
+
+impl ToPlist for ResourceLimits {
+    fn to_plist(&self) -> Value {
+        DictBuilder::default()
+            .put("Core", &self.core)
+            .put("CPU", &self.cpu)
+            .put("Data", &self.data)
+            .put("FileSize", &self.file_size)
+            .put("MemoryLock", &self.memory_lock)
+            .put("NumberOfFiles", &self.number_of_files)
+            .put("NumberOfProcesses", &self.number_of_processes)
+            .put("ResidentSetSize", &self.resident_set_size)
+            .put("Stack", &self.stack)
+            .extend(&self.other, &self.key_order)
+            .into()
+    }
+}
+
+impl FromPlist for InetdCompatibility {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        let mut f = Fields::new(value, path)?;
+
+        Ok(Self {
+            wait: f.get("Wait")?,
+            key_order: f.key_order(),
+            other: f.rest(),
+        })
+    }
+}
+
+impl ToPlist for InetdCompatibility {
+    fn to_plist(&self) -> Value {
+        DictBuilder::default()
+            .put("Wait", &self.wait)
+            .extend(&self.other, &self.key_order)
+            .into()
+    }
+}
+
+impl FromPlist for MachService {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        match value {
+            Value::Boolean(b) => Ok(MachService::Enabled(*b)),
+            Value::Dictionary(_) => {
+                let mut f = Fields::new(value, path)?;
+
+                Ok(MachService::Options(MachServiceOptions {
+                    reset_at_close: f.get("ResetAtClose")?,
+                    hide_until_check_in: f.get("HideUntilCheckIn")?,
+                    key_order: f.key_order(),
+                    other: f.rest(),
+                }))
+            }
+            _ => Err(type_error(value, path, "bool or dict")),
+        }
+    }
+}
+
+impl ToPlist for MachService {
+    fn to_plist(&self) -> Value {
+        match self {
+            MachService::Enabled(b) => b.to_plist(),
+            MachService::Options(o) => DictBuilder::default()
+                .put("ResetAtClose", &o.reset_at_close)
+                .put("HideUntilCheckIn", &o.hide_until_check_in)
+                .extend(&o.other, &o.key_order)
+                .into(),
+        }
+    }
+}
+
+impl FromPlist for Socket {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        let mut f = Fields::new(value, path)?;
+
+        Ok(Self {
+            sock_type: f.get("SockType")?,
+            sock_passive: f.get("SockPassive")?,
+            sock_node_name: f.get("SockNodeName")?,
+            sock_service_name: f.get("SockServiceName")?,
+            sock_family: f.get("SockFamily")?,
+            sock_protocol: f.get("SockProtocol")?,
+            sock_path_name: f.get("SockPathName")?,
+            secure_socket_with_key: f.get("SecureSocketWithKey")?,
+            sock_path_mode: f.get("SockPathMode")?,
+            key_order: f.key_order(),
+            other: f.rest(),
+        })
+    }
+}
+
+impl ToPlist for Socket {
+    fn to_plist(&self) -> Value {
+        DictBuilder::default()
+            .put("SockType", &self.sock_type)
+            .put("SockPassive", &self.sock_passive)
+            .put("SockNodeName", &self.sock_node_name)
+            .put("SockServiceName", &self.sock_service_name)
+            .put("SockFamily", &self.sock_family)
+            .put("SockProtocol", &self.sock_protocol)
+            .put("SockPathName", &self.sock_path_name)
+            .put("SecureSocketWithKey", &self.secure_socket_with_key)
+            .put("SockPathMode", &self.sock_path_mode)
+            .extend(&self.other, &self.key_order)
+            .into()
+    }
+}
+
+impl FromPlist for SockServiceName {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        match value {
+            Value::String(s) => Ok(SockServiceName::Name(s.clone())),
+            Value::Integer(_) => i64::from_plist(value, path).map(SockServiceName::Port),
+            _ => Err(type_error(value, path, "string or integer")),
+        }
+    }
+}
+
+impl ToPlist for SockServiceName {
+    fn to_plist(&self) -> Value {
+        match self {
+            SockServiceName::Name(s) => s.to_plist(),
+            SockServiceName::Port(p) => p.to_plist(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use plist::Value;
+
+    use crate::error::PlistError;
+    use crate::launchd_job::{
+        CalendarInterval, KeepAlive, LaunchdJob, MachService, OneOrMany, SockServiceName,
+    };
+
+    const REDIS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>Label</key>
+  <string>homebrew.mxcl.redis</string>
+  <key>ProgramArguments</key>
+  <array>
+    <string>/opt/homebrew/opt/redis/bin/redis-server</string>
+    <string>/opt/homebrew/etc/redis.conf</string>
+  </array>
+  <key>KeepAlive</key>
+  <dict>
+    <key>SuccessfulExit</key>
+    <false/>
+    <key>AfterInitialDemand</key>
+    <true/>
+  </dict>
+  <key>RunAtLoad</key>
+  <true/>
+  <key>LimitLoadToSessionType</key>
+  <array>
+    <string>Aqua</string>
+    <string>Background</string>
+  </array>
+  <key>StartCalendarInterval</key>
+  <dict>
+    <key>Hour</key>
+    <integer>3</integer>
+    <key>Minute</key>
+    <integer>15</integer>
+  </dict>
+  <key>MachServices</key>
+  <dict>
+    <key>homebrew.mxcl.redis.xpc</key>
+    <true/>
+  </dict>
+  <key>Sockets</key>
+  <dict>
+    <key>Listeners</key>
+    <dict>
+      <key>SockServiceName</key>
+      <integer>6379</integer>
+      <key>Bonjour</key>
+      <true/>
+    </dict>
+  </dict>
+  <key>X-Homebrew-Installed</key>
+  <date>2021-05-18T23:04:57Z</date>
+  <key>X-Checksum</key>
+  <data>3q2+7w==</data>
+</dict>
+</plist>
+"#;
+
+    fn redis() -> Value {
+        Value::from_reader_xml(REDIS.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn typed_keys() {
+        let job = LaunchdJob::try_from(&redis()).unwrap();
+
+        assert_eq!(job.label.as_deref(), Some("homebrew.mxcl.redis"));
+        assert_eq!(
+            job.executable(),
+            Some("/opt/homebrew/opt/redis/bin/redis-server")
+        );
+        assert_eq!(job.run_at_load, Some(true));
+        assert_eq!(
+            job.limit_load_to_session_type.unwrap().as_slice(),
+            &["Aqua".to_string(), "Background".to_string()]
+        );
+
+        match job.keep_alive {
+            Some(KeepAlive::Conditions(c)) => {
+                assert_eq!(c.successful_exit, Some(false));
+                assert_eq!(
+                    c.other.get("AfterInitialDemand"),
+                    Some(&Value::Boolean(true))
+                );
+            }
+            other => panic!("KeepAlive should be a dict, got {:?}", other),
+        }
+
+        assert_eq!(
+            job.start_calendar_interval,
+            Some(OneOrMany::One(CalendarInterval {
+                hour: Some(3),
+                minute: Some(15),
+                ..Default::default()
+            }))
+        );
+        assert_eq!(
+            job.mach_services.unwrap().get("homebrew.mxcl.redis.xpc"),
+            Some(&MachService::Enabled(true))
+        );
+
+        let listeners = &job.sockets.unwrap()["Listeners"];
+        assert_eq!(
+            listeners.as_slice()[0].sock_service_name,
+            Some(SockServiceName::Port(6379))
+        );
+    }
+
+    #[test]
+    fn round_trip_keeps_unknown_keys() {
+        let value = redis();
+        let job = LaunchdJob::try_from(&value).unwrap();
+
+        assert_eq!(
+            job.other.keys().collect::<Vec<_>>(),
+            vec!["X-Homebrew-Installed", "X-Checksum"]
+        );
+
+        let written = Value::from(&job);
+        let mut expected = value.into_dictionary().unwrap();
+        let mut written = written.into_dictionary().unwrap();
+        expected.sort_keys();
+        written.sort_keys();
+
+        assert_eq!(written, expected);
+    }
+
+    #[test]
+    fn round_trip_keeps_key_order() {
+        let value = Value::from_reader_xml(
+            r#"<plist version="1.0"><dict>
+                <key>RunAtLoad</key><true/>
+                <key>X-Note</key><string>kept</string>
+                <key>EnvironmentVariables</key>
+                <dict>
+                  <key>PATH</key><string>/bin</string>
+                  <key>HOME</key><string>/var/root</string>
+                </dict>
+                <key>KeepAlive</key>
+                <dict>
+                  <key>Crashed</key><true/>
+                  <key>SuccessfulExit</key><false/>
+                </dict>
+                <key>Label</key><string>a</string>
+            </dict></plist>"#
+                .as_bytes(),
+        )
+        .unwrap();
+
+        let mut job = LaunchdJob::try_from(&value).unwrap();
+        assert_eq!(Value::from(&job), value);
+
+        // New keys go last
+        job.nice = Some(5);
+        let written = job.to_dictionary();
+        assert_eq!(
+            written.keys().collect::<Vec<_>>(),
+            vec![
+                "RunAtLoad",
+                "X-Note",
+                "EnvironmentVariables",
+                "KeepAlive",
+                "Label",
+                "Nice"
+            ]
+        );
+
+        let keys = |key: &str| -> Vec<String> {
+            written[key]
+                .as_dictionary()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect()
+        };
+        assert_eq!(keys("EnvironmentVariables"), vec!["PATH", "HOME"]);
+        assert_eq!(keys("KeepAlive"), vec!["Crashed", "SuccessfulExit"]);
+
+        // Order doesn't make jobs different
+        let mut reordered = LaunchdJob::try_from(&Value::from(&job)).unwrap();
+        reordered.key_order = Default::default();
+        assert_eq!(reordered, job);
+    }
+
+    #[test]
+    fn tolerant() {
+        let value = Value::from_reader_xml(
+            r#"<plist version="1.0"><dict>
+                <key>Label</key><string>a</string>
+                <key>RunAtLoad</key><string>yes</string>
+                <key>KeepAlive</key><dict><key>Crashed</key><integer>1</integer></dict>
+                <key>Nice</key><integer>5</integer>
+            </dict></plist>"#
+                .as_bytes(),
+        )
+        .unwrap();
+
+        assert!(LaunchdJob::try_from(&value).is_err());
+
+        let (job, errors) = LaunchdJob::tolerant(&value).unwrap();
+        assert_eq!(job.label.as_deref(), Some("a"));
+        assert_eq!(job.nice, Some(5));
+        assert_eq!(job.run_at_load, None);
+        assert_eq!(job.keep_alive, None);
+        assert_eq!(
+            errors,
+            vec![
+                PlistError::ValueError(
+                    "KeepAlive.Crashed".to_string(),
+                    "expected bool, got integer".to_string()
+                ),
+                PlistError::ValueError(
+                    "RunAtLoad".to_string(),
+                    "expected bool, got string".to_string()
+                ),
+            ]
+        );
+
+        // Written back as they were
+        assert_eq!(Value::from(&job), value);
+    }
+
+    #[test]
+    fn errors_have_key_paths() {
+        let job = Value::from_reader_xml(
+            r#"<plist version="1.0"><dict>
+                <key>Label</key><string>a</string>
+                <key>StartCalendarInterval</key>
+                <array>
+                  <dict><key>Hour</key><integer>1</integer></dict>
+                  <dict><key>Hour</key><string>two</string></dict>
+                </array>
+            </dict></plist>"#
+                .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            LaunchdJob::try_from(&job).unwrap_err(),
+            PlistError::ValueError(
+                "StartCalendarInterval[1].Hour".to_string(),
+                "expected integer, got string".to_string()
+            )
+        );
+
+        assert_eq!(
+            LaunchdJob::try_from(&Value::Boolean(true)).unwrap_err(),
+            PlistError::ValueError("".to_string(), "expected dict, got bool".to_string())
+        );
+    }
+}
@@ -0,0 +1,41 @@
+/// Looking for risky launch persistence
+pub mod audit;
+
+/// Jobs from systemd units and crontabs, and back
+pub mod convert;
+
+/// Finding plists in LaunchAgents and LaunchDaemons directories
+pub mod discovery;
+
+/// Key level differences between plists
+pub mod diff;
+
+/// Loaded jobs that differ from their plist
+pub mod drift;
+
+/// Reading a plist that doesn't fit launchd.plist(5)
+pub mod error;
+
+/// Duplicate labels, orphaned and never loaded jobs
+pub mod integrity;
+
+/// Plists as JSON, for editing in other formats
+pub mod interchange;
+
+/// launchd.plist(5) keys as Rust types
+pub mod launchd_job;
+
+/// Diagnostics for launchd.plist mistakes
+pub mod lint;
+
+/// When launchd next starts a job on a timer
+pub mod next_run;
+
+/// launchd's databases of enabled and disabled jobs
+pub mod overrides;
+
+/// Reading and writing plist values at a key path
+mod plist_value;
+
+/// Generating plists for new jobs
+pub mod template;
@@ -0,0 +1,333 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::{Component, Path};
+
+use plist::Value;
+
+use crate::discovery::FileSystem;
+use crate::error::PlistError;
+use crate::launchd_job::{CalendarInterval, KeepAlive, LaunchdJob, OneOrMany};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Severity {
+    /// launchd won't load the job, or it can't run
+    Error,
+    /// Loads, but probably doesn't do what was meant
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_ascii_lowercase())
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Key path, e.g. StartCalendarInterval[1].Hour, empty for the whole plist
+    pub path: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error<P: Into<String>, M: Into<String>>(path: P, message: M) -> Self {
+        Self {
+            severity: Severity::Error,
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn warning<P: Into<String>, M: Into<String>>(path: P, message: M) -> Self {
+        Self {
+            severity: Severity::Warning,
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}: {}", self.severity, self.message)
+        } else {
+            write!(f, "{}: {}: {}", self.severity, self.path, self.message)
+        }
+    }
+}
+
+/// Lint a plist on disk. Its path is used to check the Label against
+/// the file name, and whether it is an agent (in a LaunchAgents directory).
+/// Programs are looked for in fs, e.g. the volume the plist is on
+pub fn lint_file<F: FileSystem + ?Sized, P: AsRef<Path>>(fs: &F, path: P) -> Vec<Diagnostic> {
+    let path = path.as_ref();
+
+    match Value::from_file(path) {
+        Ok(value) => lint_value(fs, &value, Some(path)),
+        Err(e) => vec![Diagnostic::error("", format!("Cannot read plist: {}", e))],
+    }
+}
+
+/// Lint a plist value that isn't necessarily a valid job yet,
+/// e.g. one that was just edited
+pub fn lint_value<F: FileSystem + ?Sized>(
+    fs: &F,
+    value: &Value,
+    path: Option<&Path>,
+) -> Vec<Diagnostic> {
+    match LaunchdJob::try_from(value) {
+        Ok(job) => lint(fs, &job, path),
+        Err(PlistError::ValueError(path, message)) => vec![Diagnostic::error(path, message)],
+        Err(e) => vec![Diagnostic::error("", e.to_string())],
+    }
+}
+
+/// Lint a job, path is where its plist is (if anywhere)
+pub fn lint<F: FileSystem + ?Sized>(
+    fs: &F,
+    job: &LaunchdJob,
+    path: Option<&Path>,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    match (&job.label, path.and_then(|p| p.file_stem())) {
+        (None, _) => diagnostics.push(Diagnostic::error("Label", "missing")),
+        (Some(label), _) if label.is_empty() => {
+            diagnostics.push(Diagnostic::error("Label", "empty"))
+        }
+        (Some(label), Some(stem)) if stem.to_string_lossy() != *label => {
+            diagnostics.push(Diagnostic::warning(
+                "Label",
+                format!(
+                    "{} doesn't match the file name {}",
+                    label,
+                    path.and_then(|p| p.file_name())
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                ),
+            ))
+        }
+        _ => (),
+    }
+
+    lint_program(fs, job, &mut diagnostics);
+
+    if let Some(intervals) = &job.start_calendar_interval {
+        for (i, interval) in intervals.as_slice().iter().enumerate() {
+            let path = match intervals {
+                OneOrMany::One(_) => "StartCalendarInterval".to_string(),
+                OneOrMany::Many(_) => format!("StartCalendarInterval[{}]", i),
+            };
+
+            lint_calendar_interval(interval, &path, &mut diagnostics);
+        }
+    }
+
+    let keeps_alive = match &job.keep_alive {
+        Some(KeepAlive::Always(b)) => *b,
+        Some(KeepAlive::Conditions(_)) => true,
+        None => false,
+    };
+
+    if keeps_alive && job.start_interval.is_some() {
+        diagnostics.push(Diagnostic::warning(
+            "StartInterval",
+            "has no effect on a job that is kept alive",
+        ));
+    }
+
+    let is_agent = path
+        .map(|p| {
+            p.components()
+                .any(|c| c == Component::Normal("LaunchAgents".as_ref()))
+        })
+        .unwrap_or(false);
+
+    if is_agent && job.user_name.is_some() {
+        diagnostics.push(Diagnostic::warning(
+            "UserName",
+            "ignored, agents run as the logged in user",
+        ));
+    }
+
+    diagnostics
+}
+
+fn lint_program<F: FileSystem + ?Sized>(
+    fs: &F,
+    job: &LaunchdJob,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let (key, program) = match (&job.program, &job.program_arguments) {
+        (Some(p), _) => ("Program", p.as_str()),
+        (None, Some(args)) if !args.is_empty() => ("ProgramArguments[0]", args[0].as_str()),
+        _ => {
+            diagnostics.push(Diagnostic::error(
+                "Program",
+                "missing, and no ProgramArguments",
+            ));
+            return;
+        }
+    };
+
+    // Otherwise launchd looks it up in PATH
+    if !program.starts_with('/') {
+        return;
+    }
+
+    let path = Path::new(program);
+
+    match fs.stat(path) {
+        Err(_) => diagnostics.push(Diagnostic::error(
+            key,
+            format!("{} does not exist", program),
+        )),
+        Ok(s) if fs.is_dir(path) || s.mode & 0o111 == 0 => diagnostics.push(Diagnostic::error(
+            key,
+            format!("{} is not executable", program),
+        )),
+        Ok(_) => (),
+    }
+}
+
+fn lint_calendar_interval(
+    interval: &CalendarInterval,
+    path: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let fields = [
+        ("Minute", interval.minute, 0, 59),
+        ("Hour", interval.hour, 0, 23),
+        ("Day", interval.day, 1, 31),
+        ("Weekday", interval.weekday, 0, 7),
+        ("Month", interval.month, 1, 12),
+    ];
+
+    for (key, value, min, max) in fields.iter() {
+        match value {
+            Some(v) if v < min || v > max => diagnostics.push(Diagnostic::error(
+                format!("{}.{}", path, key),
+                format!("{} is out of range {}-{}", v, min, max),
+            )),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::discovery::RootedFs;
+    use crate::lint::{lint_file, lint_value, Diagnostic};
+
+    fn fixture(name: &str) -> PathBuf {
+        [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", name]
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn fixtures() {
+        let cases = vec![
+            ("LaunchDaemons/com.example.good.plist", vec![]),
+            (
+                "LaunchDaemons/com.example.missing.plist",
+                vec![
+                    Diagnostic::error("Label", "missing"),
+                    Diagnostic::error("Program", "missing, and no ProgramArguments"),
+                ],
+            ),
+            (
+                "LaunchDaemons/com.example.mismatch.plist",
+                vec![Diagnostic::warning(
+                    "Label",
+                    "com.example.other doesn't match the file name com.example.mismatch.plist",
+                )],
+            ),
+            (
+                "LaunchDaemons/com.example.calendar.plist",
+                vec![
+                    Diagnostic::error("StartCalendarInterval[0].Hour", "24 is out of range 0-23"),
+                    Diagnostic::error("StartCalendarInterval[1].Weekday", "8 is out of range 0-7"),
+                ],
+            ),
+            (
+                "LaunchDaemons/com.example.program.plist",
+                vec![Diagnostic::error(
+                    "Program",
+                    "/nonexistent/bin/example does not exist",
+                )],
+            ),
+            (
+                "LaunchDaemons/com.example.notexec.plist",
+                vec![Diagnostic::error(
+                    "ProgramArguments[0]",
+                    "/dev/null is not executable",
+                )],
+            ),
+            (
+                "LaunchDaemons/com.example.keepalive.plist",
+                vec![Diagnostic::warning(
+                    "StartInterval",
+                    "has no effect on a job that is kept alive",
+                )],
+            ),
+            (
+                "LaunchAgents/com.example.username.plist",
+                vec![Diagnostic::warning(
+                    "UserName",
+                    "ignored, agents run as the logged in user",
+                )],
+            ),
+            (
+                "LaunchDaemons/com.example.types.plist",
+                vec![Diagnostic::error("RunAtLoad", "expected bool, got string")],
+            ),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(
+                lint_file(&RootedFs::default(), fixture(name)),
+                expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn value_errors() {
+        let value = plist::Value::from_reader_xml(
+            r#"<plist version="1.0"><dict>
+                <key>EnvironmentVariables</key>
+                <dict><key>A: B</key><integer>1</integer></dict>
+            </dict></plist>"#
+                .as_bytes(),
+        )
+        .unwrap();
+
+        // The key path has ": " in it
+        assert_eq!(
+            lint_value(&RootedFs::default(), &value, None),
+            vec![Diagnostic::error(
+                "EnvironmentVariables.A: B",
+                "expected string, got integer"
+            )]
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            Diagnostic::error("StartCalendarInterval.Hour", "24 is out of range 0-23").to_string(),
+            "error: StartCalendarInterval.Hour: 24 is out of range 0-23"
+        );
+        assert_eq!(
+            Diagnostic::error("", "Cannot read plist").to_string(),
+            "error: Cannot read plist"
+        );
+    }
+}
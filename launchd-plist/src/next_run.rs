@@ -0,0 +1,679 @@
+use std::fmt;
+use std::mem;
+
+use crate::launchd_job::{CalendarInterval, LaunchdJob};
+
+/// How far ahead to look for a day matching StartCalendarInterval,
+/// enough to find the next Feb 29 (even across 2100)
+const HORIZON_DAYS: i64 = 8 * 366;
+
+/// A date and time on the local clock, to the minute
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct WallTime {
+    pub year: i64,
+    /// 1-12
+    pub month: i64,
+    pub day: i64,
+    pub hour: i64,
+    pub minute: i64,
+}
+
+impl WallTime {
+    /// 0 is Sunday
+    pub fn weekday(&self) -> i64 {
+        weekday(days_from_civil(self.year, self.month, self.day))
+    }
+}
+
+impl fmt::Display for WallTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute
+        )
+    }
+}
+
+/// Where "now" and local time come from, so schedules can be
+/// evaluated against a fixed clock
+pub trait Clock {
+    /// Seconds since the epoch
+    fn now(&self) -> i64;
+    fn to_wall(&self, secs: i64) -> WallTime;
+    /// A time skipped by a DST change is moved past it, like launchd
+    /// (which uses mktime) does
+    fn timestamp(&self, wall: WallTime) -> i64;
+}
+
+/// The system clock and time zone
+pub struct LocalClock;
+
+impl Clock for LocalClock {
+    fn now(&self) -> i64 {
+        unsafe { libc::time(std::ptr::null_mut()) as i64 }
+    }
+
+    fn to_wall(&self, secs: i64) -> WallTime {
+        let secs = secs as libc::time_t;
+        let mut tm: libc::tm = unsafe { mem::zeroed() };
+        unsafe { libc::localtime_r(&secs, &mut tm) };
+
+        WallTime {
+            year: tm.tm_year as i64 + 1900,
+            month: tm.tm_mon as i64 + 1,
+            day: tm.tm_mday as i64,
+            hour: tm.tm_hour as i64,
+            minute: tm.tm_min as i64,
+        }
+    }
+
+    fn timestamp(&self, wall: WallTime) -> i64 {
+        let mut tm: libc::tm = unsafe { mem::zeroed() };
+        tm.tm_year = (wall.year - 1900) as libc::c_int;
+        tm.tm_mon = (wall.month - 1) as libc::c_int;
+        tm.tm_mday = wall.day as libc::c_int;
+        tm.tm_hour = wall.hour as libc::c_int;
+        tm.tm_min = wall.minute as libc::c_int;
+        // Let mktime work out DST
+        tm.tm_isdst = -1;
+
+        unsafe { libc::mktime(&mut tm) as i64 }
+    }
+}
+
+/// A StartCalendarInterval entry, None is a wildcard
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct CalendarSpec {
+    pub minute: Option<i64>,
+    pub hour: Option<i64>,
+    pub day: Option<i64>,
+    /// 0 and 7 are Sunday
+    pub weekday: Option<i64>,
+    pub month: Option<i64>,
+}
+
+impl From<&CalendarInterval> for CalendarSpec {
+    fn from(interval: &CalendarInterval) -> Self {
+        CalendarSpec {
+            minute: interval.minute,
+            hour: interval.hour,
+            day: interval.day,
+            weekday: interval.weekday,
+            month: interval.month,
+        }
+    }
+}
+
+fn field_matches(field: Option<i64>, value: i64) -> bool {
+    field.map(|f| f == value).unwrap_or(true)
+}
+
+impl CalendarSpec {
+    /// Month always has to match. A Weekday together with a Day
+    /// matches either, e.g. the 1st and every Monday
+    fn matches_date(&self, month: i64, day: i64, weekday: i64) -> bool {
+        if !field_matches(self.month, month) {
+            return false;
+        }
+
+        let weekday_matches = self.weekday.map(|w| w % 7 == weekday);
+
+        match (self.day, weekday_matches) {
+            (Some(d), Some(w)) => d == day || w,
+            (_, Some(w)) => w,
+            (d, None) => field_matches(d, day),
+        }
+    }
+
+    /// False if no date and time ever matches, e.g. Hour 24 or February 30th
+    /// (without a Weekday to match instead). Saves searching the whole horizon
+    fn can_match(&self) -> bool {
+        let in_range = |field: Option<i64>, min: i64, max: i64| {
+            field.map(|f| (min..=max).contains(&f)).unwrap_or(true)
+        };
+
+        let fields_in_range = in_range(self.minute, 0, 59)
+            && in_range(self.hour, 0, 23)
+            && in_range(self.day, 1, 31)
+            && in_range(self.weekday, 0, 7)
+            && in_range(self.month, 1, 12);
+
+        if !fields_in_range {
+            return false;
+        }
+
+        match (self.month, self.day, self.weekday) {
+            (Some(month), Some(day), None) => {
+                day <= [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31][month as usize - 1]
+            }
+            _ => true,
+        }
+    }
+
+    /// (hour, minute) pairs in a matching day, earliest first
+    fn times(&self) -> impl Iterator<Item = (i64, i64)> + '_ {
+        (0..24)
+            .filter(move |h| field_matches(self.hour, *h))
+            .flat_map(move |h| {
+                (0..60)
+                    .filter(move |m| field_matches(self.minute, *m))
+                    .map(move |m| (h, m))
+            })
+    }
+}
+
+/// The timers launchd starts a job on by itself
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct RunSchedule {
+    /// Seconds
+    pub start_interval: Option<i64>,
+    pub start_calendar_interval: Vec<CalendarSpec>,
+}
+
+impl RunSchedule {
+    /// None if the job has neither StartInterval nor StartCalendarInterval
+    pub fn from_job(job: &LaunchdJob) -> Option<Self> {
+        let start_calendar_interval: Vec<CalendarSpec> = job
+            .start_calendar_interval
+            .as_ref()
+            .map(|i| i.as_slice().iter().map(CalendarSpec::from).collect())
+            .unwrap_or_default();
+
+        if job.start_interval.is_none() && start_calendar_interval.is_empty() {
+            return None;
+        }
+
+        Some(RunSchedule {
+            start_interval: job.start_interval,
+            start_calendar_interval,
+        })
+    }
+
+    /// The next n times (seconds since the epoch) launchd starts the job,
+    /// earliest first. StartInterval counts from when the job was loaded,
+    /// so it is left out unless loaded_at is known
+    pub fn next_runs<C: Clock + ?Sized>(
+        &self,
+        clock: &C,
+        loaded_at: Option<i64>,
+        n: usize,
+    ) -> Vec<i64> {
+        let now = clock.now();
+        let mut runs = self.next_calendar_runs(clock, now, n);
+
+        if let (Some(interval), Some(loaded_at)) = (self.start_interval, loaded_at) {
+            if interval > 0 {
+                let first = if now < loaded_at {
+                    1
+                } else {
+                    (now - loaded_at) / interval + 1
+                };
+
+                runs.extend((first..first + n as i64).map(|k| loaded_at + k * interval));
+            }
+        }
+
+        runs.sort_unstable();
+        runs.dedup();
+        runs.truncate(n);
+        runs
+    }
+
+    fn next_calendar_runs<C: Clock + ?Sized>(&self, clock: &C, now: i64, n: usize) -> Vec<i64> {
+        let mut runs = vec![];
+        let specs: Vec<&CalendarSpec> = self
+            .start_calendar_interval
+            .iter()
+            .filter(|s| s.can_match())
+            .collect();
+
+        if specs.is_empty() || n == 0 {
+            return runs;
+        }
+
+        let today = clock.to_wall(now);
+        let today = days_from_civil(today.year, today.month, today.day);
+
+        for days in today..today + HORIZON_DAYS {
+            let (year, month, day) = civil_from_days(days);
+
+            for spec in &specs {
+                if !spec.matches_date(month, day, weekday(days)) {
+                    continue;
+                }
+
+                let after_now = spec
+                    .times()
+                    .map(|(hour, minute)| {
+                        clock.timestamp(WallTime {
+                            year,
+                            month,
+                            day,
+                            hour,
+                            minute,
+                        })
+                    })
+                    .filter(|secs| *secs > now)
+                    .take(n);
+
+                runs.extend(after_now);
+            }
+
+            // Every later day is later than these
+            if runs.len() >= n {
+                break;
+            }
+        }
+
+        runs
+    }
+}
+
+fn weekday(days: i64) -> i64 {
+    // 1970-01-01 was a Thursday
+    (days + 4).rem_euclid(7)
+}
+
+/// Days since 1970-01-01, from Howard Hinnant's date algorithms
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe - 719468
+}
+
+/// (year, month, day) from days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::launchd_job::{CalendarInterval, LaunchdJob, OneOrMany};
+    use crate::next_run::{
+        civil_from_days, days_from_civil, CalendarSpec, Clock, LocalClock, RunSchedule, WallTime,
+    };
+
+    /// now, and a fixed UTC offset in seconds
+    struct FixedClock(i64, i64);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> i64 {
+            self.0
+        }
+
+        fn to_wall(&self, secs: i64) -> WallTime {
+            let secs = secs + self.1;
+            let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+            let minutes = secs.rem_euclid(86400) / 60;
+
+            WallTime {
+                year,
+                month,
+                day,
+                hour: minutes / 60,
+                minute: minutes % 60,
+            }
+        }
+
+        fn timestamp(&self, wall: WallTime) -> i64 {
+            days_from_civil(wall.year, wall.month, wall.day) * 86400
+                + wall.hour * 3600
+                + wall.minute * 60
+                - self.1
+        }
+    }
+
+    /// Tuesday 2021-06-01 12:00:30 UTC
+    const NOW: i64 = 1622548830;
+
+    fn wall(year: i64, month: i64, day: i64, hour: i64, minute: i64) -> WallTime {
+        WallTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+        }
+    }
+
+    fn calendar(specs: Vec<CalendarSpec>, n: usize) -> Vec<String> {
+        let clock = FixedClock(NOW, 0);
+        let schedule = RunSchedule {
+            start_interval: None,
+            start_calendar_interval: specs,
+        };
+
+        schedule
+            .next_runs(&clock, None, n)
+            .into_iter()
+            .map(|secs| clock.to_wall(secs).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn civil_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2021, 6, 1), 18779);
+        assert_eq!(civil_from_days(18779), (2021, 6, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(days_from_civil(2024, 2, 29)), (2024, 2, 29));
+
+        assert_eq!(wall(2021, 6, 1, 0, 0).weekday(), 2);
+        assert_eq!(wall(2021, 6, 6, 0, 0).weekday(), 0);
+        assert_eq!(wall(2021, 6, 1, 3, 5).to_string(), "2021-06-01 03:05");
+    }
+
+    #[test]
+    fn calendar_intervals() {
+        let cases = vec![
+            (
+                "daily at 03:00",
+                vec![CalendarSpec {
+                    hour: Some(3),
+                    minute: Some(0),
+                    ..Default::default()
+                }],
+                vec!["2021-06-02 03:00", "2021-06-03 03:00"],
+            ),
+            (
+                "later today",
+                vec![CalendarSpec {
+                    hour: Some(12),
+                    minute: Some(30),
+                    ..Default::default()
+                }],
+                vec!["2021-06-01 12:30", "2021-06-02 12:30"],
+            ),
+            (
+                "every minute of an hour",
+                vec![CalendarSpec {
+                    hour: Some(13),
+                    ..Default::default()
+                }],
+                vec!["2021-06-01 13:00", "2021-06-01 13:01", "2021-06-01 13:02"],
+            ),
+            (
+                "every hour, not this minute",
+                vec![CalendarSpec {
+                    minute: Some(0),
+                    ..Default::default()
+                }],
+                vec!["2021-06-01 13:00", "2021-06-01 14:00"],
+            ),
+            (
+                "everything wildcard is every minute",
+                vec![CalendarSpec::default()],
+                vec!["2021-06-01 12:01", "2021-06-01 12:02"],
+            ),
+            (
+                "Mondays",
+                vec![CalendarSpec {
+                    weekday: Some(1),
+                    hour: Some(9),
+                    minute: Some(0),
+                    ..Default::default()
+                }],
+                vec!["2021-06-07 09:00", "2021-06-14 09:00"],
+            ),
+            (
+                "7 is Sunday",
+                vec![CalendarSpec {
+                    weekday: Some(7),
+                    hour: Some(0),
+                    minute: Some(0),
+                    ..Default::default()
+                }],
+                vec!["2021-06-06 00:00", "2021-06-13 00:00"],
+            ),
+            (
+                "Day or Weekday",
+                vec![CalendarSpec {
+                    day: Some(1),
+                    weekday: Some(1),
+                    hour: Some(0),
+                    minute: Some(0),
+                    ..Default::default()
+                }],
+                vec![
+                    "2021-06-07 00:00",
+                    "2021-06-14 00:00",
+                    "2021-06-21 00:00",
+                    "2021-06-28 00:00",
+                    "2021-07-01 00:00",
+                    "2021-07-05 00:00",
+                ],
+            ),
+            (
+                "Month and Weekday",
+                vec![CalendarSpec {
+                    month: Some(7),
+                    weekday: Some(1),
+                    hour: Some(0),
+                    minute: Some(0),
+                    ..Default::default()
+                }],
+                vec![
+                    "2021-07-05 00:00",
+                    "2021-07-12 00:00",
+                    "2021-07-19 00:00",
+                    "2021-07-26 00:00",
+                    "2022-07-04 00:00",
+                ],
+            ),
+            (
+                "Month, Day or Weekday",
+                vec![CalendarSpec {
+                    month: Some(7),
+                    day: Some(2),
+                    weekday: Some(1),
+                    hour: Some(0),
+                    minute: Some(0),
+                }],
+                vec!["2021-07-02 00:00", "2021-07-05 00:00", "2021-07-12 00:00"],
+            ),
+            (
+                "leap day",
+                vec![CalendarSpec {
+                    month: Some(2),
+                    day: Some(29),
+                    hour: Some(0),
+                    minute: Some(0),
+                    ..Default::default()
+                }],
+                vec!["2024-02-29 00:00", "2028-02-29 00:00"],
+            ),
+            (
+                "never",
+                vec![CalendarSpec {
+                    month: Some(2),
+                    day: Some(31),
+                    ..Default::default()
+                }],
+                vec![],
+            ),
+            (
+                "out of range hour",
+                vec![CalendarSpec {
+                    hour: Some(24),
+                    ..Default::default()
+                }],
+                vec![],
+            ),
+            (
+                "impossible day, but a weekday",
+                vec![CalendarSpec {
+                    month: Some(6),
+                    day: Some(31),
+                    weekday: Some(3),
+                    hour: Some(0),
+                    minute: Some(0),
+                }],
+                vec!["2021-06-02 00:00", "2021-06-09 00:00"],
+            ),
+            (
+                "out of range month",
+                vec![CalendarSpec {
+                    month: Some(13),
+                    day: Some(1),
+                    ..Default::default()
+                }],
+                vec![],
+            ),
+            (
+                "out of range alongside one that runs",
+                vec![
+                    CalendarSpec {
+                        minute: Some(60),
+                        ..Default::default()
+                    },
+                    CalendarSpec {
+                        hour: Some(3),
+                        minute: Some(0),
+                        ..Default::default()
+                    },
+                ],
+                vec!["2021-06-02 03:00"],
+            ),
+            (
+                "several, merged",
+                vec![
+                    CalendarSpec {
+                        hour: Some(18),
+                        minute: Some(0),
+                        ..Default::default()
+                    },
+                    CalendarSpec {
+                        hour: Some(6),
+                        minute: Some(0),
+                        ..Default::default()
+                    },
+                ],
+                vec!["2021-06-01 18:00", "2021-06-02 06:00", "2021-06-02 18:00"],
+            ),
+        ];
+
+        for (name, specs, expected) in cases {
+            assert_eq!(calendar(specs, expected.len()), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn time_zone() {
+        // UTC+2, so it's 14:00:30 on the wall
+        let clock = FixedClock(NOW, 2 * 3600);
+        let schedule = RunSchedule {
+            start_interval: None,
+            start_calendar_interval: vec![CalendarSpec {
+                hour: Some(14),
+                minute: Some(30),
+                ..Default::default()
+            }],
+        };
+
+        assert_eq!(
+            schedule.next_runs(&clock, None, 1),
+            vec![NOW + 29 * 60 + 30]
+        );
+    }
+
+    #[test]
+    fn start_interval() {
+        let clock = FixedClock(NOW, 0);
+        let schedule = RunSchedule {
+            start_interval: Some(3600),
+            start_calendar_interval: vec![CalendarSpec {
+                hour: Some(13),
+                minute: Some(15),
+                ..Default::default()
+            }],
+        };
+
+        // Loaded at 11:40:30, runs at 12:40:30, 13:40:30...
+        let loaded_at = NOW - 20 * 60;
+        let runs: Vec<String> = schedule
+            .next_runs(&clock, Some(loaded_at), 4)
+            .into_iter()
+            .map(|secs| clock.to_wall(secs).to_string())
+            .collect();
+
+        assert_eq!(
+            runs,
+            vec![
+                "2021-06-01 12:40",
+                "2021-06-01 13:15",
+                "2021-06-01 13:40",
+                "2021-06-01 14:40"
+            ]
+        );
+
+        // Without a load time, only the calendar
+        let runs: Vec<String> = schedule
+            .next_runs(&clock, None, 2)
+            .into_iter()
+            .map(|secs| clock.to_wall(secs).to_string())
+            .collect();
+
+        assert_eq!(runs, vec!["2021-06-01 13:15", "2021-06-02 13:15"]);
+    }
+
+    #[test]
+    fn from_job() {
+        assert_eq!(RunSchedule::from_job(&LaunchdJob::default()), None);
+
+        let job = LaunchdJob {
+            start_calendar_interval: Some(OneOrMany::Many(vec![
+                CalendarInterval {
+                    hour: Some(3),
+                    ..Default::default()
+                },
+                CalendarInterval {
+                    weekday: Some(0),
+                    ..Default::default()
+                },
+            ])),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            RunSchedule::from_job(&job),
+            Some(RunSchedule {
+                start_interval: None,
+                start_calendar_interval: vec![
+                    CalendarSpec {
+                        hour: Some(3),
+                        ..Default::default()
+                    },
+                    CalendarSpec {
+                        weekday: Some(0),
+                        ..Default::default()
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn local_clock() {
+        let clock = LocalClock;
+        let now = clock.now();
+        let minute = now - now % 60;
+
+        assert_eq!(clock.timestamp(clock.to_wall(now)), minute);
+    }
+}
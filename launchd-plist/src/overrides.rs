@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::discovery::{read_plist, FileSystem, LaunchdEntryType};
+
+/// disabled.plist and disabled.<uid>.plist
+pub const DISABLED_DB_DIR: &str = "/private/var/db/com.apple.xpc.launchd";
+
+/// Before 10.10: com.apple.launchd/overrides.plist and
+/// com.apple.launchd.peruser.<uid>/overrides.plist
+pub const OVERRIDES_DB_DIR: &str = "/private/var/db/launchd.db";
+
+/// One of launchd's databases of `launchctl enable` / `disable`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Overrides {
+    /// system, or user/<uid>
+    pub domain: String,
+    pub path: PathBuf,
+    /// Label to whether it's disabled
+    pub disabled: BTreeMap<String, bool>,
+}
+
+/// Every database on fs, ones that can't be read are skipped
+pub fn read_overrides<F: FileSystem + ?Sized>(fs: &F) -> Vec<Overrides> {
+    let mut dbs: Vec<(String, PathBuf)> = vec![];
+
+    for path in fs.read_dir(Path::new(DISABLED_DB_DIR)).unwrap_or_default() {
+        let name = file_name(&path);
+
+        let domain = match name
+            .strip_prefix("disabled.")
+            .and_then(|n| n.strip_suffix(".plist"))
+        {
+            _ if name == "disabled.plist" => "system".to_string(),
+            Some(uid) if is_uid(uid) => format!("user/{}", uid),
+            _ => continue,
+        };
+
+        dbs.push((domain, path));
+    }
+
+    for dir in fs.read_dir(Path::new(OVERRIDES_DB_DIR)).unwrap_or_default() {
+        let name = file_name(&dir);
+
+        let domain = match name.strip_prefix("com.apple.launchd.peruser.") {
+            _ if name == "com.apple.launchd" => "system".to_string(),
+            Some(uid) if is_uid(uid) => format!("user/{}", uid),
+            _ => continue,
+        };
+
+        dbs.push((domain, dir.join("overrides.plist")));
+    }
+
+    dbs.sort();
+
+    dbs.into_iter()
+        .filter_map(|(domain, path)| {
+            let value = read_plist(fs, &path);
+            if value.is_none() {
+                log::warn!("Couldn't read {:?}", path);
+            }
+
+            let disabled = value?
+                .as_dictionary()?
+                .iter()
+                .filter_map(|(label, v)| {
+                    // overrides.plist has { Disabled = true; } for each label
+                    let disabled = v.as_boolean().or_else(|| {
+                        v.as_dictionary()
+                            .and_then(|d| d.get("Disabled"))
+                            .and_then(|d| d.as_boolean())
+                    })?;
+
+                    Some((label.clone(), disabled))
+                })
+                .collect();
+
+            Some(Overrides {
+                domain,
+                path,
+                disabled,
+            })
+        })
+        .collect()
+}
+
+/// Daemons go by the system database, agents by the users'. Disabled
+/// for anyone counts. None if none of them mention label
+pub fn is_disabled(
+    overrides: &[Overrides],
+    label: &str,
+    entry_type: &LaunchdEntryType,
+) -> Option<bool> {
+    let states: Vec<bool> = overrides
+        .iter()
+        .filter(|o| (o.domain == "system") == (*entry_type == LaunchdEntryType::Daemon))
+        .filter_map(|o| o.disabled.get(label).copied())
+        .collect();
+
+    if states.is_empty() {
+        None
+    } else {
+        Some(states.contains(&true))
+    }
+}
+
+/// (domain, disabled) for every database that mentions label
+pub fn overrides_for<'a>(overrides: &'a [Overrides], label: &str) -> Vec<(&'a str, bool)> {
+    overrides
+        .iter()
+        .filter_map(|o| o.disabled.get(label).map(|d| (o.domain.as_str(), *d)))
+        .collect()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn is_uid(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use plist::{Dictionary, Value};
+
+    use crate::discovery::{LaunchdEntryType, RootedFs};
+    use crate::overrides::{is_disabled, overrides_for, read_overrides};
+
+    fn write(root: &Path, path: &str, entries: Vec<(&str, Value)>) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        let dict: Dictionary = entries
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        Value::Dictionary(dict).to_file_xml(path).unwrap();
+    }
+
+    #[test]
+    fn databases() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        let legacy = |disabled: bool| {
+            let mut dict = Dictionary::new();
+            dict.insert("Disabled".to_string(), disabled.into());
+            Value::Dictionary(dict)
+        };
+
+        write(
+            root,
+            "private/var/db/com.apple.xpc.launchd/disabled.plist",
+            vec![
+                ("com.example.daemon", true.into()),
+                ("com.example.enabled", false.into()),
+            ],
+        );
+        write(
+            root,
+            "private/var/db/com.apple.xpc.launchd/disabled.501.plist",
+            vec![("com.example.agent", true.into())],
+        );
+        write(
+            root,
+            "private/var/db/launchd.db/com.apple.launchd.peruser.502/overrides.plist",
+            vec![
+                ("com.example.agent", legacy(false)),
+                ("com.example.daemon", legacy(false)),
+            ],
+        );
+        fs::write(
+            root.join("private/var/db/com.apple.xpc.launchd/disabled.501.plist.bak"),
+            "",
+        )
+        .unwrap();
+
+        let overrides = read_overrides(&RootedFs::new(root));
+
+        let domains: Vec<&str> = overrides.iter().map(|o| o.domain.as_str()).collect();
+        assert_eq!(domains, vec!["system", "user/501", "user/502"]);
+
+        let daemon = LaunchdEntryType::Daemon;
+        let agent = LaunchdEntryType::Agent;
+
+        assert_eq!(
+            is_disabled(&overrides, "com.example.daemon", &daemon),
+            Some(true)
+        );
+        assert_eq!(
+            is_disabled(&overrides, "com.example.enabled", &daemon),
+            Some(false)
+        );
+        assert_eq!(
+            is_disabled(&overrides, "com.example.agent", &agent),
+            Some(true)
+        );
+        // The user databases don't count for daemons
+        assert_eq!(is_disabled(&overrides, "com.example.agent", &daemon), None);
+        assert_eq!(is_disabled(&overrides, "com.example.other", &agent), None);
+
+        assert_eq!(
+            overrides_for(&overrides, "com.example.daemon"),
+            vec![("system", true), ("user/502", false)]
+        );
+    }
+}
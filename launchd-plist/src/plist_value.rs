@@ -0,0 +1,254 @@
+use std::collections::HashSet;
+
+use indexmap::IndexMap;
+use plist::{Dictionary, Value};
+
+use crate::error::PlistError;
+use crate::error::PlistError::ValueError;
+use crate::launchd_job::KeyOrder;
+
+/// Get Self out of a plist value, path is where the value is
+/// (e.g. Sockets.Listeners[0].SockType) for error messages
+pub trait FromPlist: Sized {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError>;
+}
+
+/// Plist value for Self
+pub trait ToPlist {
+    fn to_plist(&self) -> Value;
+}
+
+/// Same names as the XML plist tags
+pub fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Array(_) => "array",
+        Value::Dictionary(_) => "dict",
+        Value::Boolean(_) => "bool",
+        Value::Data(_) => "data",
+        Value::Date(_) => "date",
+        Value::Real(_) => "real",
+        Value::Integer(_) => "integer",
+        Value::String(_) => "string",
+        Value::Uid(_) => "uid",
+        _ => "unknown",
+    }
+}
+
+pub fn type_error(value: &Value, path: &str, expected: &str) -> PlistError {
+    ValueError(
+        path.to_string(),
+        format!("expected {}, got {}", expected, kind(value)),
+    )
+}
+
+pub fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Reads keys out of a dictionary, remembering which were read so the
+/// rest can be kept as-is
+pub struct Fields<'a> {
+    dict: &'a Dictionary,
+    path: &'a str,
+    read: HashSet<&'static str>,
+    /// Some when tolerant
+    errors: Option<Vec<PlistError>>,
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(value: &'a Value, path: &'a str) -> Result<Self, PlistError> {
+        let dict = value
+            .as_dictionary()
+            .ok_or_else(|| type_error(value, path, "dict"))?;
+
+        Ok(Self {
+            dict,
+            path,
+            read: HashSet::new(),
+            errors: None,
+        })
+    }
+
+    /// Like new, but get skips a key it can't read (leaving it for
+    /// rest) rather than failing, see errors
+    pub fn tolerant(value: &'a Value, path: &'a str) -> Result<Self, PlistError> {
+        Ok(Self {
+            errors: Some(vec![]),
+            ..Self::new(value, path)?
+        })
+    }
+
+    pub fn get<T: FromPlist>(&mut self, key: &'static str) -> Result<Option<T>, PlistError> {
+        let value = match self.dict.get(key) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        match (
+            T::from_plist(value, &join(self.path, key)),
+            &mut self.errors,
+        ) {
+            (Ok(v), _) => {
+                self.read.insert(key);
+                Ok(Some(v))
+            }
+            (Err(e), Some(errors)) => {
+                errors.push(e);
+                Ok(None)
+            }
+            (Err(e), None) => Err(e),
+        }
+    }
+
+    /// What tolerant get skipped
+    pub fn errors(&self) -> Vec<PlistError> {
+        self.errors.clone().unwrap_or_default()
+    }
+
+    /// Every key, in the dictionary's order, for DictBuilder::extend
+    pub fn key_order(&self) -> KeyOrder {
+        KeyOrder(self.dict.keys().cloned().collect())
+    }
+
+    /// Keys that were not read, in their original order
+    pub fn rest(&self) -> Dictionary {
+        let mut rest = Dictionary::new();
+
+        for (k, v) in self.dict.iter() {
+            if !self.read.contains(k.as_str()) {
+                rest.insert(k.clone(), v.clone());
+            }
+        }
+
+        rest
+    }
+}
+
+/// Builds a dictionary, skipping None
+#[derive(Default)]
+pub struct DictBuilder(pub Dictionary);
+
+impl DictBuilder {
+    pub fn put<T: ToPlist>(mut self, key: &str, value: &Option<T>) -> Self {
+        if let Some(v) = value {
+            self.0.insert(key.to_string(), v.to_plist());
+        }
+
+        self
+    }
+
+    /// Append keys kept by Fields::rest, then put every key back where
+    /// it was in the dictionary that was read. New keys go last
+    pub fn extend(mut self, other: &Dictionary, order: &KeyOrder) -> Dictionary {
+        for (k, v) in other.iter() {
+            self.0.insert(k.clone(), v.clone());
+        }
+
+        let mut entries: Vec<(String, Value)> = self.0.into_iter().collect();
+        let position = |key: &str| order.0.iter().position(|k| k == key);
+        // Stable, so new keys keep the order they were put in
+        entries.sort_by_key(|(k, _)| position(k).unwrap_or(usize::MAX));
+
+        entries.into_iter().collect()
+    }
+}
+
+impl FromPlist for String {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        value
+            .as_string()
+            .map(|s| s.to_string())
+            .ok_or_else(|| type_error(value, path, "string"))
+    }
+}
+
+impl ToPlist for String {
+    fn to_plist(&self) -> Value {
+        Value::String(self.clone())
+    }
+}
+
+impl FromPlist for bool {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        value
+            .as_boolean()
+            .ok_or_else(|| type_error(value, path, "bool"))
+    }
+}
+
+impl ToPlist for bool {
+    fn to_plist(&self) -> Value {
+        Value::Boolean(*self)
+    }
+}
+
+impl FromPlist for i64 {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        value
+            .as_signed_integer()
+            .ok_or_else(|| type_error(value, path, "integer"))
+    }
+}
+
+impl ToPlist for i64 {
+    fn to_plist(&self) -> Value {
+        Value::from(*self)
+    }
+}
+
+impl FromPlist for Value {
+    fn from_plist(value: &Value, _: &str) -> Result<Self, PlistError> {
+        Ok(value.clone())
+    }
+}
+
+impl ToPlist for Value {
+    fn to_plist(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl<T: FromPlist> FromPlist for Vec<T> {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        value
+            .as_array()
+            .ok_or_else(|| type_error(value, path, "array"))?
+            .iter()
+            .enumerate()
+            .map(|(i, v)| T::from_plist(v, &format!("{}[{}]", path, i)))
+            .collect()
+    }
+}
+
+impl<T: ToPlist> ToPlist for Vec<T> {
+    fn to_plist(&self) -> Value {
+        Value::Array(self.iter().map(|v| v.to_plist()).collect())
+    }
+}
+
+impl<T: FromPlist> FromPlist for IndexMap<String, T> {
+    fn from_plist(value: &Value, path: &str) -> Result<Self, PlistError> {
+        value
+            .as_dictionary()
+            .ok_or_else(|| type_error(value, path, "dict"))?
+            .iter()
+            .map(|(k, v)| T::from_plist(v, &join(path, k)).map(|v| (k.clone(), v)))
+            .collect()
+    }
+}
+
+impl<T: ToPlist> ToPlist for IndexMap<String, T> {
+    fn to_plist(&self) -> Value {
+        let mut dict = Dictionary::new();
+
+        for (k, v) in self.iter() {
+            dict.insert(k.clone(), v.to_plist());
+        }
+
+        Value::Dictionary(dict)
+    }
+}
@@ -0,0 +1,381 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::discovery::RootedFs;
+use crate::error::PlistError;
+use crate::error::PlistError::ValueError;
+use crate::launchd_job::{CalendarInterval, KeepAlive, LaunchdJob, OneOrMany};
+use crate::lint::{lint, Diagnostic, Severity};
+
+/// Where a new job's plist goes
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Scope {
+    /// ~/Library/LaunchAgents
+    UserAgent,
+    /// /Library/LaunchAgents
+    GlobalAgent,
+    /// /Library/LaunchDaemons
+    GlobalDaemon,
+}
+
+pub static SCOPES: [Scope; 3] = [Scope::UserAgent, Scope::GlobalAgent, Scope::GlobalDaemon];
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Scope::UserAgent => "User agent",
+            Scope::GlobalAgent => "Global agent",
+            Scope::GlobalDaemon => "Global daemon",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl Scope {
+    pub fn directory(&self, home: &Path) -> PathBuf {
+        match self {
+            Scope::UserAgent => home.join("Library/LaunchAgents"),
+            Scope::GlobalAgent => PathBuf::from("/Library/LaunchAgents"),
+            Scope::GlobalDaemon => PathBuf::from("/Library/LaunchDaemons"),
+        }
+    }
+}
+
+/// When launchd starts the job
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schedule {
+    pub run_at_load: bool,
+    pub keep_alive: bool,
+    /// Seconds
+    pub start_interval: Option<i64>,
+    pub start_calendar_interval: Vec<CalendarInterval>,
+    pub watch_paths: Vec<String>,
+}
+
+/// Starting points for common kinds of jobs
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Template {
+    /// Started at load and restarted whenever it exits
+    Service,
+    /// Runs every hour
+    Periodic,
+    /// Runs when a file or directory changes
+    WatchPath,
+    /// Nothing scheduled
+    Blank,
+}
+
+pub static TEMPLATES: [Template; 4] = [
+    Template::Service,
+    Template::Periodic,
+    Template::WatchPath,
+    Template::Blank,
+];
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Template::Service => "Long-running service (RunAtLoad, KeepAlive)",
+            Template::Periodic => "Periodic task (StartInterval)",
+            Template::WatchPath => "Watch path trigger (WatchPaths)",
+            Template::Blank => "Blank",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl Template {
+    pub fn schedule(&self) -> Schedule {
+        match self {
+            Template::Service => Schedule {
+                run_at_load: true,
+                keep_alive: true,
+                ..Default::default()
+            },
+            Template::Periodic => Schedule {
+                start_interval: Some(3600),
+                ..Default::default()
+            },
+            Template::WatchPath => Schedule {
+                watch_paths: vec!["/tmp/watched".to_string()],
+                ..Default::default()
+            },
+            Template::Blank => Schedule::default(),
+        }
+    }
+}
+
+/// What the :new wizard asks for
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewJob {
+    pub label: String,
+    pub scope: Scope,
+    pub program_arguments: Vec<String>,
+    pub schedule: Schedule,
+}
+
+impl NewJob {
+    /// <scope directory>/<label>.plist
+    pub fn path(&self, home: &Path) -> PathBuf {
+        self.scope
+            .directory(home)
+            .join(format!("{}.plist", self.label))
+    }
+
+    pub fn to_job(&self) -> LaunchdJob {
+        let Schedule {
+            run_at_load,
+            keep_alive,
+            start_interval,
+            start_calendar_interval,
+            watch_paths,
+        } = &self.schedule;
+
+        LaunchdJob {
+            label: Some(self.label.clone()),
+            program_arguments: Some(self.program_arguments.clone()),
+            run_at_load: Some(*run_at_load).filter(|b| *b),
+            keep_alive: Some(*keep_alive).filter(|b| *b).map(KeepAlive::Always),
+            start_interval: *start_interval,
+            start_calendar_interval: match start_calendar_interval.as_slice() {
+                [] => None,
+                [one] => Some(OneOrMany::One(one.clone())),
+                many => Some(OneOrMany::Many(many.to_vec())),
+            },
+            watch_paths: Some(watch_paths.clone()).filter(|w| !w.is_empty()),
+            ..Default::default()
+        }
+    }
+
+    /// The job and where to write it, if it lints without errors and
+    /// wouldn't replace an existing plist. home is for user agents
+    pub fn generate(&self, home: &Path) -> Result<(PathBuf, LaunchdJob), Vec<Diagnostic>> {
+        // Becomes the file name
+        if self.label.contains('/') || self.label.starts_with('.') {
+            return Err(vec![Diagnostic::error(
+                "Label",
+                format!("{} can't be used as a file name", self.label),
+            )]);
+        }
+
+        let path = self.path(home);
+        let job = self.to_job();
+
+        if path.exists() {
+            return Err(vec![Diagnostic::error(
+                "",
+                format!("{} already exists", path.display()),
+            )]);
+        }
+
+        let errors: Vec<Diagnostic> = lint(&RootedFs::default(), &job, Some(&path))
+            .into_iter()
+            .filter(|d| d.severity == Severity::Error)
+            .collect();
+
+        if errors.is_empty() {
+            Ok((path, job))
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Crontab style "minute hour day month weekday", each a number or *
+pub fn parse_calendar(fields: &str) -> Result<CalendarInterval, PlistError> {
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+
+    if fields.len() != 5 {
+        return Err(ValueError(
+            "".to_string(),
+            format!(
+                "expected 5 fields (minute hour day month weekday), got {}",
+                fields.len()
+            ),
+        ));
+    }
+
+    let parse = |field: &str| -> Result<Option<i64>, PlistError> {
+        match field {
+            "*" => Ok(None),
+            n => n
+                .parse()
+                .map(Some)
+                .map_err(|_| ValueError("".to_string(), format!("{} is not a number or *", n))),
+        }
+    };
+
+    Ok(CalendarInterval {
+        minute: parse(fields[0])?,
+        hour: parse(fields[1])?,
+        day: parse(fields[2])?,
+        month: parse(fields[3])?,
+        weekday: parse(fields[4])?,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use plist::Value;
+
+    use crate::launchd_job::CalendarInterval;
+    use crate::lint::Diagnostic;
+    use crate::template::{parse_calendar, NewJob, Schedule, Scope, Template};
+
+    fn new_job(template: Template) -> NewJob {
+        NewJob {
+            label: "com.example.new".to_string(),
+            scope: Scope::UserAgent,
+            program_arguments: vec!["/bin/sh".to_string(), "-c".to_string(), "true".to_string()],
+            schedule: template.schedule(),
+        }
+    }
+
+    #[test]
+    fn templates() {
+        let cases = vec![
+            (
+                Template::Service,
+                vec![
+                    ("RunAtLoad", Value::from(true)),
+                    ("KeepAlive", Value::from(true)),
+                ],
+            ),
+            (
+                Template::Periodic,
+                vec![("StartInterval", Value::from(3600_i64))],
+            ),
+            (
+                Template::WatchPath,
+                vec![(
+                    "WatchPaths",
+                    Value::Array(vec![Value::from("/tmp/watched")]),
+                )],
+            ),
+            (Template::Blank, vec![]),
+        ];
+
+        let home = tempfile::tempdir().unwrap();
+
+        for (template, schedule_keys) in cases {
+            let (path, job) = new_job(template).generate(home.path()).unwrap();
+
+            assert_eq!(
+                path,
+                home.path()
+                    .join("Library/LaunchAgents/com.example.new.plist")
+            );
+
+            let dict = job.to_dictionary();
+            let mut expected = vec![
+                ("Label", Value::from("com.example.new")),
+                (
+                    "ProgramArguments",
+                    Value::Array(vec![
+                        Value::from("/bin/sh"),
+                        Value::from("-c"),
+                        Value::from("true"),
+                    ]),
+                ),
+            ];
+            expected.extend(schedule_keys);
+
+            assert_eq!(dict.len(), expected.len(), "{:?}", template);
+            for (k, v) in expected {
+                assert_eq!(dict.get(k), Some(&v), "{:?} {}", template, k);
+            }
+        }
+    }
+
+    #[test]
+    fn calendar() {
+        let mut job = new_job(Template::Blank);
+        job.scope = Scope::GlobalDaemon;
+        job.schedule = Schedule {
+            start_calendar_interval: vec![
+                parse_calendar("30 3 * * 1").unwrap(),
+                parse_calendar("0 * 1 * *").unwrap(),
+            ],
+            ..Default::default()
+        };
+
+        let (path, job) = job.generate(Path::new("/nonexistent")).unwrap();
+        assert_eq!(
+            path,
+            Path::new("/Library/LaunchDaemons/com.example.new.plist")
+        );
+        assert_eq!(
+            job.start_calendar_interval.unwrap().as_slice(),
+            &[
+                CalendarInterval {
+                    minute: Some(30),
+                    hour: Some(3),
+                    weekday: Some(1),
+                    ..Default::default()
+                },
+                CalendarInterval {
+                    minute: Some(0),
+                    day: Some(1),
+                    ..Default::default()
+                },
+            ]
+        );
+
+        assert_eq!(
+            parse_calendar("0 3 * *").unwrap_err().to_string(),
+            "expected 5 fields (minute hour day month weekday), got 4"
+        );
+        assert_eq!(
+            parse_calendar("0 3 * * mon").unwrap_err().to_string(),
+            "mon is not a number or *"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let home = tempfile::tempdir().unwrap();
+
+        let mut job = new_job(Template::Periodic);
+        job.label = "../evil".to_string();
+        assert_eq!(
+            job.generate(home.path()).unwrap_err(),
+            vec![Diagnostic::error(
+                "Label",
+                "../evil can't be used as a file name"
+            )]
+        );
+
+        let mut job = new_job(Template::Periodic);
+        job.program_arguments = vec![];
+        job.schedule.start_calendar_interval = vec![parse_calendar("0 24 * * *").unwrap()];
+        assert_eq!(
+            job.generate(home.path()).unwrap_err(),
+            vec![
+                Diagnostic::error("Program", "missing, and no ProgramArguments"),
+                Diagnostic::error("StartCalendarInterval.Hour", "24 is out of range 0-23"),
+            ]
+        );
+
+        let job = new_job(Template::Service);
+        let dir = home.path().join("Library/LaunchAgents");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("com.example.new.plist"), "").unwrap();
+
+        assert_eq!(
+            job.generate(home.path()).unwrap_err(),
+            vec![Diagnostic::error(
+                "",
+                format!(
+                    "{} already exists",
+                    dir.join("com.example.new.plist").display()
+                )
+            )]
+        );
+    }
+}
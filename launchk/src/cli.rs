@@ -0,0 +1,236 @@
+use std::cmp::Reverse;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use launchd_plist::audit::{max_severity, Finding, Severity as AuditSeverity};
+use launchd_plist::convert::crontab::from_crontab;
+use launchd_plist::convert::systemd::{from_systemd, to_systemd, SystemdUnits};
+use launchd_plist::convert::Conversion;
+use launchd_plist::discovery::{LaunchdEntryLocation, LaunchdEntryType};
+use launchd_plist::launchd_job::LaunchdJob;
+use launchd_plist::lint::{lint_file, Diagnostic, Severity};
+use serde::Serialize;
+
+use crate::launchd::plist::{plist_paths, read_plists, LaunchdPlist, PLIST_FS};
+use crate::launchd::privileged::install;
+
+static USAGE: &str = "usage: launchk [--root <volume>]
+              [lint [plist ...] | audit | install-plist <source> <target>
+              | import-systemd <dir> <unit.service> [unit.timer]
+              | import-crontab <dir> <crontab> | export-systemd <dir> <plist>]";
+
+/// Headless subcommands, None to start the TUI
+pub fn run(args: &[String]) -> Option<i32> {
+    match args.first().map(|s| s.as_str()) {
+        None => None,
+        Some("lint") => Some(lint(&args[1..])),
+        Some("audit") if args.len() == 1 => Some(audit()),
+        Some("install-plist") if args.len() == 3 => Some(install_plist(&args[1], &args[2])),
+        Some("import-systemd") if args.len() == 3 || args.len() == 4 => {
+            Some(exit_code(import_systemd(&args[1], &args[2], args.get(3))))
+        }
+        Some("import-crontab") if args.len() == 3 => {
+            Some(exit_code(import_crontab(&args[1], &args[2])))
+        }
+        Some("export-systemd") if args.len() == 3 => {
+            Some(exit_code(export_systemd(&args[1], &args[2])))
+        }
+        Some(_) => {
+            eprintln!("{}", USAGE);
+            Some(2)
+        }
+    }
+}
+
+/// Lint the given plists, or every plist launchk knows about.
+/// Exits 1 if anything is an error, warnings alone are OK
+fn lint(paths: &[String]) -> i32 {
+    let paths: Vec<PathBuf> = if paths.is_empty() {
+        plist_paths().collect()
+    } else {
+        paths.iter().map(PathBuf::from).collect()
+    };
+
+    let mut has_errors = false;
+
+    for path in paths {
+        for diagnostic in lint_file(&*PLIST_FS, &path) {
+            has_errors |= diagnostic.severity == Severity::Error;
+            println!("{}: {}", path.display(), diagnostic);
+        }
+    }
+
+    if has_errors {
+        1
+    } else {
+        0
+    }
+}
+
+/// A plist the persistence audit found something in
+#[derive(Serialize)]
+struct AuditEntry {
+    label: String,
+    plist: String,
+    location: LaunchdEntryLocation,
+    entry_type: LaunchdEntryType,
+    severity: AuditSeverity,
+    findings: Vec<Finding>,
+}
+
+/// Print what the persistence audit found as JSON, worst first.
+/// Exits 1 if anything is high severity
+fn audit() -> i32 {
+    let mut entries: Vec<AuditEntry> = read_plists()
+        .into_iter()
+        .filter_map(|(label, plist)| {
+            let LaunchdPlist {
+                entry_type,
+                entry_location,
+                plist_path,
+                findings,
+                ..
+            } = plist;
+
+            Some(AuditEntry {
+                label,
+                plist: plist_path,
+                location: entry_location,
+                entry_type,
+                severity: max_severity(&findings)?,
+                findings,
+            })
+        })
+        .collect();
+
+    entries.sort_by(|a, b| {
+        (Reverse(a.severity), &a.label, &a.plist).cmp(&(Reverse(b.severity), &b.label, &b.plist))
+    });
+
+    match serde_json::to_string_pretty(&entries) {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    }
+
+    if entries.iter().any(|e| e.severity == AuditSeverity::High) {
+        1
+    } else {
+        0
+    }
+}
+
+/// Run through sudo by the TUI to write plists it can't
+fn install_plist(source: &str, target: &str) -> i32 {
+    match install(Path::new(source), Path::new(target)) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+fn exit_code(result: Result<(), String>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Never replaces anything, prints where it wrote
+fn write_new(dir: &str, name: &str, contents: &[u8]) -> Result<(), String> {
+    let path = Path::new(dir).join(name);
+
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut f| f.write_all(contents))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    println!("{}", path.display());
+    Ok(())
+}
+
+fn report(source: &str, unsupported: &[Diagnostic]) {
+    for diagnostic in unsupported {
+        eprintln!("{}: {}", source, diagnostic);
+    }
+}
+
+fn write_job(dir: &str, job: &LaunchdJob) -> Result<(), String> {
+    let label = job.label.as_deref().unwrap_or_default();
+    let mut contents = vec![];
+    plist::Value::from(job)
+        .to_writer_xml(&mut contents)
+        .map_err(|e| e.to_string())?;
+
+    write_new(dir, &format!("{}.plist", label), &contents)
+}
+
+/// Labelled after the .service, e.g. backup.service is backup
+fn import_systemd(dir: &str, service: &str, timer: Option<&String>) -> Result<(), String> {
+    let label = Path::new(service)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let timer_text = timer.map(|t| read(t)).transpose()?;
+    let Conversion { job, unsupported } =
+        from_systemd(&label, &read(service)?, timer_text.as_deref())
+            .map_err(|e| format!("{}: {}", service, e))?;
+
+    report(service, &unsupported);
+    write_job(dir, &job)
+}
+
+/// Labelled after the file and entry, e.g. crontab.1. Crontabs in /etc
+/// have a user name field
+fn import_crontab(dir: &str, crontab: &str) -> Result<(), String> {
+    let prefix = Path::new(crontab)
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let conversions = from_crontab(&read(crontab)?, &prefix, crontab.starts_with("/etc/"))
+        .map_err(|e| format!("{}: {}", crontab, e))?;
+
+    for Conversion { job, unsupported } in conversions {
+        report(crontab, &unsupported);
+        write_job(dir, &job)?;
+    }
+
+    Ok(())
+}
+
+/// <label>.service, and <label>.timer if the job is scheduled
+fn export_systemd(dir: &str, plist: &str) -> Result<(), String> {
+    let job = LaunchdJob::from_file(plist).map_err(|e| e.to_string())?;
+    let SystemdUnits {
+        service,
+        timer,
+        unsupported,
+    } = to_systemd(&job).map_err(|e| format!("{}: {}", plist, e))?;
+
+    let label = job.label.unwrap_or_default();
+
+    report(plist, &unsupported);
+    write_new(dir, &format!("{}.service", label), service.as_bytes())?;
+
+    if let Some(timer) = timer {
+        write_new(dir, &format!("{}.timer", label), timer.as_bytes())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::process::exit;
+
+use launchd_plist::discovery::{default_roots, LaunchdEntryLocation, PlistRoot};
+use serde::Deserialize;
+
+use crate::launchd::edit_format::EditFormat;
+
+lazy_static! {
+    pub static ref CONFIG_PATH: String = env::var("HOME").expect("Must read $HOME")
+        + "/Library/Application Support/launchk/config.toml";
+    pub static ref CONFIG: Config = Config::load().unwrap_or_else(|e| {
+        eprintln!("{}: {}", &*CONFIG_PATH, e);
+        exit(1);
+    });
+}
+
+/// ~/Library/Application Support/launchk/config.toml, every key is optional:
+///
+/// ```toml
+/// # xml, json, yaml or toml
+/// edit_format = "yaml"
+/// # Show when scheduled jobs next run
+/// next_run_column = true
+/// # Reload loaded jobs when their plist changes, in these locations
+/// auto_reload = ["user"]
+///
+/// # Where to look for plists, replaces the defaults (launchd_plist::discovery::default_roots)
+/// [[plist_roots]]
+/// path = "~/Library/LaunchAgents"
+/// location = "user"       # system, global or user
+/// entry_type = "agent"    # agent or daemon
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// What :edit opens plists as
+    pub edit_format: EditFormat,
+    /// Add a Next Run column to the service list
+    pub next_run_column: bool,
+    /// Directories with plists in them, ~/ is every user's home when root
+    pub plist_roots: Vec<PlistRoot>,
+    /// Where stale jobs are reloaded without asking
+    pub auto_reload: Vec<LaunchdEntryLocation>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            edit_format: EditFormat::default(),
+            next_run_column: false,
+            plist_roots: default_roots(),
+            auto_reload: vec![],
+        }
+    }
+}
+
+impl Config {
+    fn load() -> Result<Self, String> {
+        match fs::read_to_string(&*CONFIG_PATH) {
+            Ok(text) => toml::from_str(&text).map_err(|e| e.to_string()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
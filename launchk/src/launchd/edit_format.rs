@@ -0,0 +1,178 @@
+use std::io::Cursor;
+
+use launchd_plist::interchange::{from_json, to_json};
+use serde::Deserialize;
+
+/// What a plist is converted to for $EDITOR. Whatever it is edited
+/// as, it is written back in its original XML or binary encoding.
+/// See doc/edit_formats.md for how plist types map to the others
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EditFormat {
+    #[default]
+    Xml,
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl EditFormat {
+    /// For the temp file, so $EDITOR picks the right syntax
+    pub fn extension(&self) -> &'static str {
+        match self {
+            EditFormat::Xml => "plist",
+            EditFormat::Json => "json",
+            EditFormat::Yaml => "yaml",
+            EditFormat::Toml => "toml",
+        }
+    }
+
+    pub fn render(&self, plist: &plist::Value) -> Result<String, String> {
+        match self {
+            EditFormat::Xml => {
+                let mut buf = vec![];
+                plist.to_writer_xml(&mut buf).map_err(|e| e.to_string())?;
+                String::from_utf8(buf).map_err(|e| e.to_string())
+            }
+            EditFormat::Json => {
+                serde_json::to_string_pretty(&to_json(plist)).map_err(|e| e.to_string())
+            }
+            EditFormat::Yaml => serde_yaml::to_string(&to_json(plist)).map_err(|e| e.to_string()),
+            EditFormat::Toml => {
+                let json = to_json(plist);
+
+                if let Some(path) = beyond_i64(&json, "") {
+                    return Err(format!(
+                        "{} is too big for a TOML integer, use xml or json for this plist",
+                        path
+                    ));
+                }
+
+                toml::to_string_pretty(&json).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    pub fn parse(&self, text: &str) -> Result<plist::Value, String> {
+        let json: serde_json::Value = match self {
+            EditFormat::Xml => {
+                return plist::Value::from_reader_xml(Cursor::new(text)).map_err(|e| e.to_string())
+            }
+            EditFormat::Json => serde_json::from_str(text).map_err(|e| e.to_string())?,
+            EditFormat::Yaml => serde_yaml::from_str(text).map_err(|e| e.to_string())?,
+            EditFormat::Toml => toml::from_str(text).map_err(|e| e.to_string())?,
+        };
+
+        from_json(&json).map_err(|e| e.to_string())
+    }
+}
+
+/// Path to the first integer only u64 holds, TOML stops at i64::MAX
+fn beyond_i64(json: &serde_json::Value, path: &str) -> Option<String> {
+    match json {
+        serde_json::Value::Number(n) if n.is_u64() && n.as_i64().is_none() => {
+            Some(path.to_string())
+        }
+        serde_json::Value::Array(a) => a
+            .iter()
+            .enumerate()
+            .find_map(|(i, v)| beyond_i64(v, &format!("{}[{}]", path, i))),
+        serde_json::Value::Object(map) => map.iter().find_map(|(k, v)| {
+            let path = if path.is_empty() {
+                k.clone()
+            } else {
+                format!("{}.{}", path, k)
+            };
+
+            beyond_i64(v, &path)
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use plist::{Date, Dictionary, Uid, Value};
+
+    use crate::launchd::edit_format::EditFormat;
+
+    fn job() -> Dictionary {
+        let mut dict = Dictionary::new();
+        dict.insert("Label".to_string(), Value::from("com.example.job"));
+        dict.insert("Nice".to_string(), Value::from(-5_i64));
+        dict.insert("ExitTimeOut".to_string(), Value::Real(1.5));
+        dict.insert("Inf".to_string(), Value::Real(f64::INFINITY));
+        dict.insert(
+            "LastRun".to_string(),
+            Value::Date(Date::from(UNIX_EPOCH + Duration::from_secs(1622548800))),
+        );
+        dict.insert("Blob".to_string(), Value::Data(vec![0, 1, 2, 255]));
+        dict.insert("$weird".to_string(), Value::Array(vec![Value::from("a")]));
+        dict
+    }
+
+    fn round_trip(format: EditFormat, value: &Value) {
+        let text = format.render(value).unwrap();
+        assert_eq!(
+            &format.parse(&text).unwrap(),
+            value,
+            "{:?}:\n{}",
+            format,
+            text
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let value = Value::Dictionary(job());
+
+        for format in [
+            EditFormat::Xml,
+            EditFormat::Json,
+            EditFormat::Yaml,
+            EditFormat::Toml,
+        ] {
+            round_trip(format, &value);
+        }
+
+        // UIDs only exist in binary plists
+        let mut dict = job();
+        dict.insert("Uid".to_string(), Value::Uid(Uid::new(7)));
+        let value = Value::Dictionary(dict);
+
+        for format in [EditFormat::Json, EditFormat::Yaml, EditFormat::Toml] {
+            round_trip(format, &value);
+        }
+    }
+
+    #[test]
+    fn escapes() {
+        let text = EditFormat::Json.render(&Value::Dictionary(job())).unwrap();
+
+        assert!(text.contains(r#""$$weird""#));
+        assert!(text.contains(r#""$date": "2021-06-01T12:00:00Z""#));
+        assert!(text.contains(r#""$data": "AAEC/w==""#));
+        assert!(text.contains(r#""$real": "inf""#));
+    }
+
+    #[test]
+    fn toml_too_big() {
+        let mut dict = job();
+        dict.insert(
+            "Sockets".to_string(),
+            Value::Array(vec![Value::from(u64::MAX)]),
+        );
+        let value = Value::Dictionary(dict);
+
+        assert_eq!(
+            EditFormat::Toml.render(&value).unwrap_err(),
+            "Sockets[0] is too big for a TOML integer, use xml or json for this plist"
+        );
+
+        for format in [EditFormat::Xml, EditFormat::Json, EditFormat::Yaml] {
+            round_trip(format, &value);
+        }
+    }
+}
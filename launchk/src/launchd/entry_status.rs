@@ -1,13 +1,22 @@
 use std::collections::HashMap;
-use std::convert::TryInto;
 use std::sync::Mutex;
 use std::time::{Duration, SystemTime};
 
+use std::collections::HashSet;
+
+use crate::config::CONFIG;
 use crate::launchd::plist::LaunchdPlist;
-use crate::launchd::query::find_in_all;
+use crate::launchd::query::{domain_target, find_in_all, load, unload};
+use crate::offline::is_offline;
 
+use launchd_plist::drift::{drift, LoadedService};
+use launchd_plist::launchd_job::LaunchdJob;
 use xpc_sys::enums::{DomainType, SessionType};
-use xpc_sys::traits::xpc_value::TryXPCValue;
+use xpc_sys::traits::xpc_transport::XPCTransport;
+use xpc_sys::xpc_serde::from_xpc_value;
+
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer};
 
 const ENTRY_INFO_QUERY_TTL: Duration = Duration::from_secs(15);
 
@@ -23,6 +32,8 @@ pub struct LaunchdEntryStatus {
     pub domain: DomainType,
     // So, there is a pid_t, but it's i32, and the XPC response has an i64?
     pub pid: i64,
+    /// How the plist differs from the loaded job, empty unless it's stale
+    pub drift: Vec<String>,
     tick: SystemTime,
 }
 
@@ -33,49 +44,111 @@ impl Default for LaunchdEntryStatus {
             domain: DomainType::Unknown,
             plist: None,
             pid: 0,
+            drift: vec![],
             tick: SystemTime::now(),
         }
     }
 }
 
 /// Get entry info for label
-pub fn get_entry_status<S: Into<String>>(label: S) -> LaunchdEntryStatus {
+pub fn get_entry_status<T: XPCTransport + ?Sized, S: Into<String>>(
+    transport: &T,
+    label: S,
+) -> LaunchdEntryStatus {
     let label_string = label.into();
-    let mut cache = ENTRY_STATUS_CACHE.try_lock().unwrap();
 
-    if cache.contains_key(label_string.as_str()) {
-        let item = cache.get(label_string.as_str()).unwrap().clone();
+    // Just the plist, there's no launchd to ask
+    if is_offline() {
+        return LaunchdEntryStatus {
+            plist: crate::launchd::plist::for_label(label_string),
+            ..Default::default()
+        };
+    }
+
+    {
+        // Not try_lock, fsnotify can be evicting entries
+        let mut cache = ENTRY_STATUS_CACHE.lock().expect("Must read cache");
+
+        if let Some(item) = cache.get(label_string.as_str()) {
+            if item.tick.elapsed().unwrap() <= ENTRY_INFO_QUERY_TTL {
+                return item.clone();
+            }
 
-        if item.tick.elapsed().unwrap() > ENTRY_INFO_QUERY_TTL {
             cache.remove(label_string.as_str());
-            drop(cache);
-            return get_entry_status(label_string);
         }
-
-        return item;
     }
 
-    let meta = build_entry_status(&label_string);
-    cache.insert(label_string, meta.clone());
+    // Asking launchd and reading the plist is slow, don't hold the cache
+    let meta = build_entry_status(transport, &label_string);
+
+    ENTRY_STATUS_CACHE
+        .lock()
+        .expect("Must write cache")
+        .insert(label_string, meta.clone());
+
     meta
 }
 
-fn build_entry_status<S: Into<String>>(label: S) -> LaunchdEntryStatus {
+/// The parts of the find_in_all reply we care about
+#[derive(Deserialize)]
+struct ServiceReply {
+    service: Service,
+}
+
+#[derive(Deserialize)]
+struct Service {
+    #[serde(rename = "PID", default, deserialize_with = "lenient")]
+    pid: Option<i64>,
+    #[serde(
+        rename = "LimitLoadToSessionType",
+        default,
+        deserialize_with = "lenient"
+    )]
+    limit_load_to_session_type: Option<SessionType>,
+}
+
+/// None rather than an error if launchd sent another type, so one odd
+/// field doesn't lose the others
+fn lenient<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Or<T> {
+        Value(T),
+        Other(IgnoredAny),
+    }
+
+    Ok(match Or::deserialize(deserializer)? {
+        Or::Value(v) => Some(v),
+        Or::Other(_) => None,
+    })
+}
+
+/// The same reply, with what's compared to the plist
+#[derive(Deserialize)]
+struct LoadedReply {
+    service: LoadedService,
+}
+
+fn build_entry_status<T: XPCTransport + ?Sized, S: Into<String>>(
+    transport: &T,
+    label: S,
+) -> LaunchdEntryStatus {
     let label_string = label.into();
-    let response = find_in_all(label_string.clone());
+    let response = find_in_all(transport, label_string.clone());
 
-    let pid: i64 = response
+    let service = response
         .as_ref()
         .map_err(|e| e.clone())
-        .and_then(|(_, r)| r.get(&["service", "PID"]))
-        .and_then(|o| o.xpc_value())
-        .unwrap_or(0);
+        .and_then(|(_, r)| from_xpc_value::<ServiceReply>(r).map_err(|e| e.into()))
+        .map(|r| r.service);
 
-    let limit_load_to_session_type = response
-        .as_ref()
-        .map_err(|e| e.clone())
-        .and_then(|(_, r)| r.get(&["service", "LimitLoadToSessionType"]))
-        .and_then(|o| o.try_into())
+    let pid = service.as_ref().ok().and_then(|s| s.pid).unwrap_or(0);
+
+    let limit_load_to_session_type = service
+        .ok()
+        .and_then(|s| s.limit_load_to_session_type)
         .unwrap_or(SessionType::Unknown);
 
     let domain = response
@@ -85,11 +158,128 @@ fn build_entry_status<S: Into<String>>(label: S) -> LaunchdEntryStatus {
 
     let entry_config = crate::launchd::plist::for_label(label_string.clone());
 
+    // Only loaded jobs can be stale
+    let loaded = response
+        .as_ref()
+        .ok()
+        .and_then(|(_, r)| from_xpc_value::<LoadedReply>(r).ok());
+
+    let changes = match (loaded, &entry_config) {
+        (Some(LoadedReply { service }), Some(plist)) => plist::Value::from_file(&plist.plist_path)
+            .ok()
+            .and_then(|value| LaunchdJob::tolerant(&value).ok())
+            .map(|(job, _)| {
+                drift(&service, &job)
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        _ => vec![],
+    };
+
     LaunchdEntryStatus {
         limit_load_to_session_type,
         domain,
         plist: entry_config,
         pid,
+        drift: changes,
         tick: SystemTime::now(),
     }
 }
+
+/// Reload the stale ones of labels, if CONFIG.auto_reload has their location
+pub fn reload_stale<T: XPCTransport + ?Sized>(transport: &T, labels: &HashSet<String>) {
+    if is_offline() || CONFIG.auto_reload.is_empty() {
+        return;
+    }
+
+    for label in labels {
+        let LaunchdEntryStatus {
+            plist,
+            limit_load_to_session_type,
+            domain,
+            drift,
+            ..
+        } = get_entry_status(transport, label);
+
+        let plist = match plist {
+            Some(p) if !drift.is_empty() && CONFIG.auto_reload.contains(&p.entry_location) => p,
+            _ => continue,
+        };
+
+        if domain == DomainType::Unknown || limit_load_to_session_type == SessionType::Unknown {
+            log::warn!("Not reloading {}, unknown domain or session type", label);
+            continue;
+        }
+
+        let reloaded = unload(
+            transport,
+            label.clone(),
+            plist.plist_path.clone(),
+            &domain_target(domain.clone(), None),
+            Some(limit_load_to_session_type.clone()),
+        )
+        .and_then(|_| {
+            load(
+                transport,
+                label.clone(),
+                plist.plist_path,
+                &domain_target(domain, None),
+                Some(limit_load_to_session_type),
+            )
+        });
+
+        match reloaded {
+            Ok(_) => log::info!("Reloaded stale {}", label),
+            Err(e) => log::warn!("Couldn't reload stale {}: {}", label, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use xpc_sys::enums::SessionType;
+    use xpc_sys::objects::xpc_value::XPCValue;
+    use xpc_sys::xpc_serde::from_xpc_value;
+
+    use crate::launchd::entry_status::ServiceReply;
+
+    fn reply(service: Vec<(&str, XPCValue)>) -> ServiceReply {
+        let service: BTreeMap<&str, XPCValue> = service.into_iter().collect();
+        let mut reply: BTreeMap<&str, XPCValue> = BTreeMap::new();
+        reply.insert("service", service.into());
+
+        from_xpc_value(&reply.into()).unwrap()
+    }
+
+    #[test]
+    fn service_fields() {
+        let ServiceReply { service } = reply(vec![
+            ("PID", XPCValue::Int64(42)),
+            ("LimitLoadToSessionType", "Aqua".into()),
+        ]);
+        assert_eq!(service.pid, Some(42));
+        assert_eq!(service.limit_load_to_session_type, Some(SessionType::Aqua));
+
+        // One odd field doesn't lose the other
+        let ServiceReply { service } = reply(vec![
+            ("PID", "42".into()),
+            ("LimitLoadToSessionType", "Aqua".into()),
+        ]);
+        assert_eq!(service.pid, None);
+        assert_eq!(service.limit_load_to_session_type, Some(SessionType::Aqua));
+
+        let ServiceReply { service } = reply(vec![
+            ("PID", XPCValue::Int64(42)),
+            ("LimitLoadToSessionType", XPCValue::Int64(1)),
+        ]);
+        assert_eq!(service.pid, Some(42));
+        assert_eq!(service.limit_load_to_session_type, None);
+
+        let ServiceReply { service } = reply(vec![]);
+        assert_eq!(service.pid, None);
+    }
+}
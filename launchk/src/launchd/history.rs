@@ -0,0 +1,200 @@
+use std::cmp::Reverse;
+use std::env;
+use std::fmt;
+use std::fs::{self, DirBuilder};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::launchd::privileged::write_plist;
+
+lazy_static! {
+    static ref HISTORY: Result<History, String> = env::var("HOME")
+        .map(|home| History::new(home + "/Library/Application Support/launchk/history"))
+        .map_err(|_| "$HOME isn't set, nowhere to keep history".to_string());
+}
+
+/// ~/Library/Application Support/launchk/history
+pub fn history() -> Result<&'static History, String> {
+    HISTORY.as_ref().map_err(|e| e.clone())
+}
+
+/// A copy of a plist as it was before launchk wrote over it,
+/// byte for byte so binary plists stay binary
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Version {
+    pub path: PathBuf,
+    pub taken: SystemTime,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", plist::Date::from(self.taken).to_xml_format())
+    }
+}
+
+/// Versions of each job's plist, in a directory per label
+pub struct History {
+    root: PathBuf,
+}
+
+impl History {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// label as one path component, with % and / escaped so
+    /// different labels can't share a directory
+    fn label_dir(&self, label: &str) -> Result<PathBuf, String> {
+        if label.is_empty() || label == "." || label == ".." {
+            return Err(format!("{:?} can't have a history", label));
+        }
+
+        Ok(self
+            .root
+            .join(label.replace('%', "%25").replace('/', "%2F")))
+    }
+
+    /// Save contents, read from label's plist before writing over it, as
+    /// the newest version. Only once the write worked, so a failed one
+    /// doesn't leave a version that was never replaced
+    pub fn save(&self, label: &str, contents: &[u8]) -> Result<Version, String> {
+        let dir = self.label_dir(label)?;
+
+        // Can hold copies of root owned daemons, only for us to read
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)
+            .map_err(|e| format!("Couldn't create {}: {}", dir.display(), e))?;
+
+        let taken = SystemTime::now();
+        let since_epoch = taken.duration_since(UNIX_EPOCH).expect("Must get ts");
+
+        // Zero padded, so sorting names sorts by time
+        let path = dir.join(format!(
+            "{:010}.{:09}.plist",
+            since_epoch.as_secs(),
+            since_epoch.subsec_nanos()
+        ));
+
+        fs::write(&path, contents).map_err(|e| format!("Couldn't back up plist: {}", e))?;
+
+        Ok(Version { path, taken })
+    }
+
+    /// All versions for label, newest first
+    pub fn versions(&self, label: &str) -> Vec<Version> {
+        let mut versions: Vec<Version> = self
+            .label_dir(label)
+            .and_then(|dir| fs::read_dir(dir).map_err(|e| e.to_string()))
+            .map(|rd| {
+                rd.filter_map(|e| e.ok())
+                    .filter_map(|e| version_from_path(e.path()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        versions.sort_by_key(|v| Reverse(v.taken));
+        versions
+    }
+
+    /// Put a version back at plist_path, after saving what's there now so
+    /// the restore can be undone too. Keeps the plist's owner and mode
+    pub fn restore(&self, label: &str, version: &Path, plist_path: &str) -> Result<(), String> {
+        // Before writing, so there's somewhere to save what's there now
+        self.label_dir(label)?;
+
+        let contents = fs::read(version).map_err(|e| format!("Couldn't read version: {}", e))?;
+        let current =
+            fs::read(plist_path).map_err(|e| format!("Couldn't read {}: {}", plist_path, e))?;
+
+        write_plist(plist_path, &contents).map_err(|e| format!("Couldn't restore: {}", e))?;
+        self.save(label, &current).map(|_| ())
+    }
+}
+
+fn version_from_path(path: PathBuf) -> Option<Version> {
+    let stem = path.file_stem()?.to_str()?;
+    let (secs, nanos) = stem.split_once('.')?;
+    let since_epoch = Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+
+    Some(Version {
+        taken: UNIX_EPOCH + since_epoch,
+        path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::launchd::history::History;
+
+    #[test]
+    fn save_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let history = History::new(dir.join("history"));
+        let plist_path = dir.join("com.example.job.plist");
+        let plist_path = plist_path.to_str().unwrap();
+
+        let first = history.save("com.example.job", b"bplist00 first").unwrap();
+
+        fs::write(plist_path, "second").unwrap();
+        let second = history.save("com.example.job", b"second").unwrap();
+
+        assert_eq!(
+            history.versions("com.example.job"),
+            vec![second, first.clone()]
+        );
+        assert!(history.versions("com.example.other").is_empty());
+
+        history
+            .restore("com.example.job", &first.path, plist_path)
+            .unwrap();
+
+        assert_eq!(fs::read(plist_path).unwrap(), b"bplist00 first");
+
+        // What was there before restoring is the newest version now
+        let versions = history.versions("com.example.job");
+        assert_eq!(versions.len(), 3);
+        assert_eq!(fs::read(&versions[0].path).unwrap(), b"second");
+
+        // A restore that fails doesn't add a version
+        let missing = dir.join("missing").join("com.example.job.plist");
+        assert!(history
+            .restore("com.example.job", &first.path, missing.to_str().unwrap())
+            .is_err());
+        assert_eq!(history.versions("com.example.job").len(), 3);
+    }
+
+    #[test]
+    fn label_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = History::new(dir.path().join("history"));
+
+        for label in &["", ".", ".."] {
+            assert!(history.save(label, b"").is_err());
+            assert!(history.versions(label).is_empty());
+        }
+
+        history.save("a/b", b"slash").unwrap();
+        history.save("a%2Fb", b"escaped").unwrap();
+        history.save("a_b", b"underscore").unwrap();
+
+        for (label, contents) in &[
+            ("a/b", b"slash".to_vec()),
+            ("a%2Fb", b"escaped".to_vec()),
+            ("a_b", b"underscore".to_vec()),
+        ] {
+            let versions = history.versions(label);
+            assert_eq!(versions.len(), 1, "{}", label);
+            assert_eq!(&fs::read(&versions[0].path).unwrap(), contents);
+            assert_eq!(
+                versions[0].path.parent().unwrap().parent(),
+                Some(&*dir.path().join("history"))
+            );
+        }
+    }
+}
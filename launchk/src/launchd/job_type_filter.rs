@@ -12,6 +12,10 @@ bitflags! {
         const AGENT  = (1 << 4);
         const DAEMON = (1 << 5);
         const LOADED = (1 << 6);
+        /// The persistence audit found something
+        const RISKY  = (1 << 7);
+        /// Duplicate label, orphan, never loaded or misnamed
+        const ISSUES = (1 << 8);
     }
 }
 
@@ -27,7 +31,7 @@ impl JobTypeFilter {
 /// hotkey status
 impl fmt::Display for JobTypeFilter {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let mut display = format!("");
+        let mut display = String::new();
 
         if (*self & JobTypeFilter::SYSTEM) == JobTypeFilter::SYSTEM {
             display.push('s');
@@ -53,6 +57,14 @@ impl fmt::Display for JobTypeFilter {
             display.push('l');
         }
 
+        if (*self & JobTypeFilter::RISKY) == JobTypeFilter::RISKY {
+            display.push('r');
+        }
+
+        if (*self & JobTypeFilter::ISSUES) == JobTypeFilter::ISSUES {
+            display.push('i');
+        }
+
         write!(f, "{}", display)
     }
 }
@@ -66,6 +78,8 @@ impl fmt::Debug for JobTypeFilter {
             JobTypeFilter::AGENT => write!(f, "AGENT"),
             JobTypeFilter::DAEMON => write!(f, "DAEMON"),
             JobTypeFilter::LOADED => write!(f, "LOADED"),
+            JobTypeFilter::RISKY => write!(f, "RISKY"),
+            JobTypeFilter::ISSUES => write!(f, "ISSUES"),
             _ => Ok(()),
         }
     }
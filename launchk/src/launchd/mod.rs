@@ -8,3 +8,12 @@ pub mod job_type_filter;
 
 /// plist management
 pub mod plist;
+
+/// Formats plists can be edited in
+pub mod edit_format;
+
+/// Versions of plists from before launchk wrote to them
+pub mod history;
+
+/// Writing plists owned by someone else
+pub mod privileged;
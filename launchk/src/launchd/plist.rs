@@ -1,28 +1,67 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::env;
-use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::{Once, RwLock};
 
+use crate::config::CONFIG;
+use crate::launchd::edit_format::EditFormat;
+use crate::launchd::entry_status::ENTRY_STATUS_CACHE;
+use crate::launchd::history::History;
 use crate::launchd::job_type_filter::JobTypeFilter;
+use crate::launchd::privileged::write_plist;
+use crate::offline::{is_offline, OFFLINE_ROOT};
+use launchd_plist::audit::{audit, Finding};
+use launchd_plist::diff::{diff, Change};
+use launchd_plist::discovery::{
+    classify, find_plists, is_plist_path, plist_dirs, read_plist, PlistDir, RootedFs,
+};
+use launchd_plist::integrity::{check, Issue, PlistLabel};
+use launchd_plist::launchd_job::LaunchdJob;
+use launchd_plist::lint::{lint, Severity};
+use launchd_plist::next_run::RunSchedule;
 use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode, DebounceEventResult};
-use std::fs::File;
-use std::io::Read;
-use std::process::Command;
+use std::fs::{File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sudo::RunningAs;
 use tokio::runtime::Handle;
 
 pub static PLIST_MAP_INIT: Once = Once::new();
 
+/// Bumped when reconcile changes the maps, so what's worked out from
+/// them (e.g. integrity_issues) knows to do it again
+pub static PLIST_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
 lazy_static! {
     pub static ref LABEL_TO_ENTRY_CONFIG: RwLock<HashMap<String, LaunchdPlist>> =
         RwLock::new(HashMap::new());
+    /// Which label each plist path is in LABEL_TO_ENTRY_CONFIG under,
+    /// to find it again once the file is gone or its Label changed
+    static ref PATH_TO_LABEL: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
     static ref EDITOR: String = env::var("EDITOR").unwrap_or("vim".to_string());
     static ref TMP_DIR: String = env::var("TMPDIR").unwrap_or("/tmp".to_string());
-    static ref USER_LAUNCH_AGENTS: String =
-        env::var("HOME").expect("Must read $HOME") + "/Library/LaunchAgents";
+    /// / or --root
+    pub static ref PLIST_FS: RootedFs = OFFLINE_ROOT
+        .as_ref()
+        .map(RootedFs::new)
+        .unwrap_or_default();
+    /// CONFIG.plist_roots, every user's LaunchAgents if we're root or offline
+    pub static ref PLIST_DIRS: Vec<PlistDir> = if is_offline() {
+        plist_dirs(&*PLIST_FS, &CONFIG.plist_roots, Path::new("/var/root"), true)
+    } else {
+        plist_dirs(
+            &*PLIST_FS,
+            &CONFIG.plist_roots,
+            Path::new(&env::var("HOME").expect("Must read $HOME")),
+            sudo::check() == RunningAs::Root,
+        )
+    };
 }
 
 /*
@@ -32,44 +71,18 @@ od -xc binary.plist
 */
 static PLIST_MAGIC: &str = "bplist00";
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub enum LaunchdEntryType {
-    /// Runs on behalf of currently logged in user
-    Agent,
-    /// Global system daemon
-    Daemon,
-}
-
-impl fmt::Display for LaunchdEntryType {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
-    }
-}
-
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub enum LaunchdEntryLocation {
-    /// macOS system provided agent or daemon
-    System,
-    /// Admin provided agent or daemon in /Library,
-    /// would name it admin...but the [sguadl] filter
-    /// needs uniques
-    Global,
-    /// User provided agent
-    User,
-}
-
-impl fmt::Display for LaunchdEntryLocation {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
-    }
-}
+pub use launchd_plist::discovery::{LaunchdEntryLocation, LaunchdEntryType};
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct LaunchdPlist {
     pub entry_type: LaunchdEntryType,
     pub entry_location: LaunchdEntryLocation,
     pub plist_path: String,
-    pub readonly: bool,
+    /// StartInterval and StartCalendarInterval, if any
+    pub schedule: Option<RunSchedule>,
+    /// What the persistence audit found, worst first
+    pub findings: Vec<Finding>,
+    pub run_at_load: bool,
 }
 
 // TODO: This should be somewhere else
@@ -92,32 +105,26 @@ impl LaunchdPlist {
             jtf.toggle(JobTypeFilter::LOADED);
         }
 
+        if !self.findings.is_empty() {
+            jtf.toggle(JobTypeFilter::RISKY);
+        }
+
         jtf
     }
 }
 
-pub const ADMIN_LAUNCH_AGENTS: &str = "/Library/LaunchAgents";
-pub const SYSTEM_LAUNCH_AGENTS: &str = "/System/Library/LaunchAgents";
-
-pub const ADMIN_LAUNCH_DAEMONS: &str = "/Library/LaunchDaemons";
-pub const SYSTEM_LAUNCH_DAEMONS: &str = "/System/Library/LaunchDaemons";
-
-async fn fsnotify_subscriber() {
+async fn fsnotify_subscriber(on_change: impl Fn(&HashSet<String>)) {
     let (tx, rx): (Sender<DebounceEventResult>, Receiver<DebounceEventResult>) = channel();
     let mut debouncer = new_debouncer(Duration::from_secs(5), tx).unwrap();
     let watcher = debouncer.watcher();
 
-    // Register plist paths
-    let watchers = [
-        watcher.watch(Path::new(&*USER_LAUNCH_AGENTS), RecursiveMode::Recursive),
-        watcher.watch(Path::new(ADMIN_LAUNCH_AGENTS), RecursiveMode::Recursive),
-        watcher.watch(Path::new(SYSTEM_LAUNCH_AGENTS), RecursiveMode::Recursive),
-        watcher.watch(Path::new(ADMIN_LAUNCH_DAEMONS), RecursiveMode::Recursive),
-        watcher.watch(Path::new(SYSTEM_LAUNCH_DAEMONS), RecursiveMode::Recursive),
-    ];
-
-    for sub in watchers.iter() {
-        sub.as_ref().expect("Must subscribe to fs events");
+    // Register plist paths, not every root exists everywhere
+    for dir in PLIST_DIRS.iter() {
+        let path = PLIST_FS.host_path(&dir.path);
+
+        if let Err(e) = watcher.watch(&path, RecursiveMode::Recursive) {
+            log::warn!("Not watching {:?}: {}", path, e);
+        }
     }
 
     loop {
@@ -133,165 +140,482 @@ async fn fsnotify_subscriber() {
             .filter_map(|e| path_if_plist(&e.path))
             .collect();
 
-        insert_plists(paths.into_iter());
+        let changed = reconcile_plists(paths.into_iter());
+        if !changed.is_empty() {
+            on_change(&changed);
+        }
     }
 }
 
-fn build_label_map_entry(plist_path: PathBuf) -> Option<(String, LaunchdPlist)> {
-    let path_string = plist_path.to_string_lossy().to_string();
-    let label = plist::Value::from_file(&path_string).ok()?;
-    let label = label
+/// None unless it's a plist with a Label in one of dirs
+fn build_label_map_entry(
+    fs: &RootedFs,
+    dirs: &[PlistDir],
+    plist_path: PathBuf,
+) -> Option<(String, LaunchdPlist)> {
+    let path = fs.fs_path(&plist_path)?;
+    let dir = classify(dirs, &path)?;
+    let plist = read_plist(fs, &path)?;
+    // A wrong-typed key shouldn't hide everything else launchk knows about the job
+    let job = LaunchdJob::tolerant(&plist).ok().map(|(job, _)| job);
+    let label = plist
         .as_dictionary()
         .and_then(|d| d.get("Label"))
-        .and_then(|v| v.as_string());
-
-    let entry_type = if path_string.starts_with(ADMIN_LAUNCH_DAEMONS)
-        || path_string.starts_with(SYSTEM_LAUNCH_DAEMONS)
-    {
-        LaunchdEntryType::Daemon
-    } else {
-        LaunchdEntryType::Agent
-    };
-
-    let entry_location = if path_string.starts_with(&*USER_LAUNCH_AGENTS) {
-        LaunchdEntryLocation::User
-    } else if path_string.starts_with(ADMIN_LAUNCH_AGENTS)
-        || path_string.starts_with(ADMIN_LAUNCH_DAEMONS)
-    {
-        LaunchdEntryLocation::Global
-    } else {
-        LaunchdEntryLocation::System
-    };
+        .and_then(|v| v.as_string())?;
 
     Some((
-        label?.to_string(),
+        label.to_string(),
         LaunchdPlist {
-            entry_location,
-            entry_type,
-            plist_path: path_string,
-            readonly: plist_path
-                .metadata()
-                .map(|m| m.permissions().readonly())
-                .unwrap_or(true),
+            entry_location: dir.location.clone(),
+            entry_type: dir.entry_type.clone(),
+            plist_path: plist_path.to_string_lossy().to_string(),
+            schedule: job.as_ref().and_then(RunSchedule::from_job),
+            run_at_load: job.as_ref().and_then(|j| j.run_at_load) == Some(true),
+            findings: job
+                .map(|job| audit(fs, label, &dir.location, &dir.entry_type, &job))
+                .unwrap_or_default(),
         },
     ))
 }
 
-fn path_if_plist(path: &PathBuf) -> Option<PathBuf> {
-    if path.is_dir()
-        || path
-            .extension()
-            .map(|ex| ex.to_string_lossy().ne("plist"))
-            .unwrap_or(true)
-    {
+fn path_if_plist(path: &Path) -> Option<PathBuf> {
+    if path.is_dir() || !is_plist_path(path) {
         None
     } else {
-        Some(path.clone())
+        Some(path.to_path_buf())
     }
 }
 
-fn insert_plists(plists: impl Iterator<Item = PathBuf>) {
-    let mut label_map = LABEL_TO_ENTRY_CONFIG.write().expect("Must update");
+/// Update the maps for plists that were created, changed, removed or renamed
+/// (both paths), returns the labels that were added, changed or removed
+fn reconcile(
+    fs: &RootedFs,
+    dirs: &[PlistDir],
+    label_map: &mut HashMap<String, LaunchdPlist>,
+    path_map: &mut HashMap<String, String>,
+    plists: impl Iterator<Item = PathBuf>,
+) -> HashSet<String> {
+    let mut changed = HashSet::new();
 
     for plist_path in plists {
-        log::info!("Loading plist {:?}", plist_path);
+        let path_string = plist_path.to_string_lossy().to_string();
+
+        if let Some(old_label) = path_map.remove(&path_string) {
+            let is_ours = label_map
+                .get(&old_label)
+                .map(|e| e.plist_path == path_string)
+                .unwrap_or(false);
+
+            if is_ours {
+                label_map.remove(&old_label);
+
+                // Another plist with the same label can have it back
+                let other = path_map
+                    .iter()
+                    .find(|(_, l)| **l == old_label)
+                    .and_then(|(p, _)| build_label_map_entry(fs, dirs, PathBuf::from(p)));
 
-        let entry = build_label_map_entry(plist_path);
-        if entry.is_none() {
-            continue;
+                if let Some((label, entry)) = other {
+                    label_map.insert(label, entry);
+                }
+            }
+
+            changed.insert(old_label);
+        }
+
+        // None if it's gone, or no longer has a Label
+        match build_label_map_entry(fs, dirs, plist_path) {
+            Some((label, entry)) => {
+                log::info!("Loading plist {:?}", path_string);
+
+                if let Some(other) = label_map
+                    .get(&label)
+                    .filter(|e| e.plist_path != path_string)
+                {
+                    log::warn!(
+                        "{} is also in {:?}, using {:?}",
+                        label,
+                        other.plist_path,
+                        path_string
+                    );
+                }
+
+                path_map.insert(path_string, label.clone());
+                label_map.insert(label.clone(), entry);
+                changed.insert(label);
+            }
+            None => log::info!("Dropping plist {:?}", path_string),
         }
-        let (label, entry) = entry.unwrap();
-        label_map.insert(label, entry);
     }
+
+    changed
+}
+
+/// reconcile LABEL_TO_ENTRY_CONFIG, and forget the cached status of
+/// changed labels (it has the plist too)
+fn reconcile_plists(plists: impl Iterator<Item = PathBuf>) -> HashSet<String> {
+    let changed = {
+        let mut label_map = LABEL_TO_ENTRY_CONFIG.write().expect("Must update");
+        let mut path_map = PATH_TO_LABEL.write().expect("Must update");
+        reconcile(
+            &PLIST_FS,
+            &PLIST_DIRS,
+            &mut label_map,
+            &mut path_map,
+            plists,
+        )
+    };
+
+    if !changed.is_empty() {
+        PLIST_GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // get_entry_status holds this while reading LABEL_TO_ENTRY_CONFIG
+    let mut cache = ENTRY_STATUS_CACHE.lock().expect("Must invalidate");
+    for label in changed.iter() {
+        cache.remove(label);
+    }
+
+    changed
+}
+
+/// Track a plist launchk just created, without waiting for fsnotify
+pub fn insert_plist(plist_path: PathBuf) {
+    reconcile_plists(std::iter::once(plist_path));
+}
+
+/// All plists in the launchd job directories (CONFIG.plist_roots)
+pub fn plist_paths() -> impl Iterator<Item = PathBuf> {
+    find_plists(&*PLIST_FS, &PLIST_DIRS)
+        .into_iter()
+        .map(|p| PLIST_FS.host_path(&p))
+}
+
+/// Every plist with a Label, by path rather than label so none are
+/// hidden behind another with the same one. For headless commands,
+/// which run without the map
+pub fn read_plists() -> Vec<(String, LaunchdPlist)> {
+    plist_paths()
+        .filter_map(|p| build_label_map_entry(&PLIST_FS, &PLIST_DIRS, p))
+        .collect()
 }
 
 /// Unsure if this is overkill, since the filenames
 /// usually match the label property. Still looking for
-/// a way to do dumpstate, dumpjpcategory without parsing the string
-pub fn init_plist_map(runtime_handle: &Handle) {
-    let dirs = [
-        &USER_LAUNCH_AGENTS,
-        ADMIN_LAUNCH_AGENTS,
-        SYSTEM_LAUNCH_AGENTS,
-        ADMIN_LAUNCH_DAEMONS,
-        SYSTEM_LAUNCH_DAEMONS,
-    ];
-
-    // Get all the plists from everywhere into one stream
-    let plists = dirs
-        .iter()
-        .filter_map(|&dirname| fs::read_dir(Path::new(dirname)).ok())
-        .flat_map(|rd| rd.map(|e| e.ok()))
-        .flatten()
-        .filter_map(|d| path_if_plist(&d.path()));
-
-    insert_plists(plists);
+/// a way to do dumpstate, dumpjpcategory without parsing the string.
+/// on_change is called with the labels of plists that changed on disk,
+/// e.g. to redraw
+pub fn init_plist_map(
+    runtime_handle: &Handle,
+    on_change: impl Fn(&HashSet<String>) + Send + 'static,
+) {
+    reconcile_plists(plist_paths());
 
     // Spawn fsnotify subscriber
-    runtime_handle.spawn(fsnotify_subscriber());
+    runtime_handle.spawn(fsnotify_subscriber(on_change));
+}
+
+/// Duplicate labels, orphans, jobs with RunAtLoad that aren't loaded and
+/// mismatched file names. loaded is None offline
+pub fn integrity_issues(loaded: Option<&HashSet<String>>) -> Vec<Issue> {
+    let label_map = LABEL_TO_ENTRY_CONFIG.read().expect("Must read");
+    let path_map = PATH_TO_LABEL.read().expect("Must read");
+
+    // Every plist, also ones hidden behind another with the same label
+    let plists: Vec<PlistLabel> = path_map
+        .iter()
+        .map(|(path, label)| PlistLabel {
+            path: path.clone(),
+            label: label.clone(),
+            run_at_load: label_map
+                .get(label)
+                .filter(|e| e.plist_path == *path)
+                .map(|e| e.run_at_load)
+                .unwrap_or(false),
+        })
+        .collect();
+
+    check(&plists, loaded)
 }
 
 /// Get plist for a label
 pub fn for_label<S: Into<String>>(label: S) -> Option<LaunchdPlist> {
     let label_map = LABEL_TO_ENTRY_CONFIG.read().ok()?;
-    label_map.get(label.into().as_str()).map(|c| c.clone())
+    label_map.get(label.into().as_str()).cloned()
 }
 
-/// Given a LaunchdPlist, start editor pointing to temporary file
-/// and replace on exit. Uses plist crate to validate changes and
-/// help show contents for binary encoded files
-pub fn edit_and_replace(plist_meta: &LaunchdPlist) -> Result<(), String> {
-    if plist_meta.readonly {
-        return Err("plist is read-only!".to_string());
+/// A plist being edited in $EDITOR, through a copy in $TMPDIR (as XML, JSON, ...).
+/// The copy is kept until the edit is written back or aborted, so
+/// an invalid edit can be opened again
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EditSession {
+    pub label: String,
+    pub plist_path: String,
+    pub temp_path: PathBuf,
+    pub format: EditFormat,
+}
+
+pub enum EditError {
+    /// Edit is kept, can open it again
+    Invalid(Vec<String>),
+    /// Edit was discarded
+    Failed(String),
+}
+
+/// Unique name, only readable by us, and fail rather than follow
+/// anything already there (e.g. a symlink someone left for us)
+pub fn create_private_file(dir: &Path, name: &str) -> io::Result<(PathBuf, File)> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    for _ in 0..100 {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Must get ts")
+            .subsec_nanos();
+
+        let path = dir.join(format!(
+            "launchk-{}-{}-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+            nanos,
+            name
+        ));
+
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
     }
 
-    let mut file =
-        File::open(&plist_meta.plist_path).map_err(|_| "Couldn't read file".to_string())?;
+    Err(io::Error::new(ErrorKind::AlreadyExists, "no unused name"))
+}
+
+/// create_private_file in $TMPDIR
+pub fn create_temp_file(name: &str) -> Result<(PathBuf, File), String> {
+    create_private_file(Path::new(&*TMP_DIR), name)
+        .map_err(|e| format!("Couldn't create temp file: {}", e))
+}
 
-    // We want to write back in the correct format,
-    // can't assume we can safely write XML everywhere?
-    let mut magic_buf: [u8; 8] = [0; 8];
-    file.read_exact(&mut magic_buf)
-        .map_err(|_| "Couldn't read magic".to_string())?;
-    let is_binary = std::str::from_utf8(&magic_buf)
-        .map_err(|_| "Couldn't read magic".to_string())?
-        == PLIST_MAGIC;
+/// Binary plists start with PLIST_MAGIC, anything else is XML
+pub fn is_binary_plist(contents: &[u8]) -> bool {
+    contents.starts_with(PLIST_MAGIC.as_bytes())
+}
 
-    // plist -> validate with crate -> temp file
+/// Copy the plist to a temp file in format (binary plists too) to start editing
+pub fn begin_edit<S: Into<String>>(
+    label: S,
+    plist_meta: &LaunchdPlist,
+    format: EditFormat,
+) -> Result<EditSession, String> {
     let og_plist = plist::Value::from_file(&plist_meta.plist_path).map_err(|e| e.to_string())?;
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Must get ts");
-    let temp_path = Path::new(&*TMP_DIR).join(format!("{}", now.as_secs()));
-    og_plist
-        .to_file_xml(&temp_path)
+    let text = format.render(&og_plist)?;
+    let file_name = Path::new(&plist_meta.plist_path)
+        .file_stem()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or("job".to_string());
+    let (temp_path, mut file) = create_temp_file(&format!("{}.{}", file_name, format.extension()))?;
+
+    let session = EditSession {
+        label: label.into(),
+        plist_path: plist_meta.plist_path.clone(),
+        temp_path,
+        format,
+    };
+
+    file.write_all(text.as_bytes()).map_err(|e| {
+        session.abort();
+        e.to_string()
+    })?;
+
+    Ok(session)
+}
+
+impl EditSession {
+    /// Start $EDITOR on the temp file, then validate it
+    pub fn edit(&self) -> Result<(plist::Value, Vec<Change>, Vec<String>), EditError> {
+        let exit = Command::new(&*EDITOR)
+            .arg(&self.temp_path)
+            .status()
+            .map_err(|e| self.fail(format!("{} failed: {}", &*EDITOR, e)))?;
+
+        if !exit.success() {
+            return Err(self.fail(format!("{} did not exit successfully", &*EDITOR)));
+        }
+
+        self.validate()
+    }
+
+    /// Convert the temp file back to a plist and check it with the linter. Returns the
+    /// edited plist if it can be written back, how it differs from the original, and
+    /// the linter's errors. Only a plist that isn't a job (e.g. a key of the wrong type)
+    /// can't be written back, the rest is up to the user
+    pub fn validate(&self) -> Result<(plist::Value, Vec<Change>, Vec<String>), EditError> {
+        let text = fs::read_to_string(&self.temp_path).map_err(|e| self.fail(e.to_string()))?;
+        let plist = self
+            .format
+            .parse(&text)
+            .map_err(|e| EditError::Invalid(vec![e]))?;
+
+        let job =
+            LaunchdJob::try_from(&plist).map_err(|e| EditError::Invalid(vec![e.to_string()]))?;
+
+        let errors: Vec<String> = lint(&*PLIST_FS, &job, Some(Path::new(&self.plist_path)))
+            .into_iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| d.to_string())
+            .collect();
+
+        let og_plist =
+            plist::Value::from_file(&self.plist_path).map_err(|e| self.fail(e.to_string()))?;
+        let changes = diff(&og_plist, &plist);
+
+        if changes.is_empty() {
+            return Err(self.fail("No changes made".to_string()));
+        }
+
+        Ok((plist, changes, errors))
+    }
+
+    /// Replace the original with the edited plist, in the same format
+    /// (binary or XML) it was in. The original is kept in history
+    pub fn write_back(&self, plist: &plist::Value, history: &History) -> Result<(), String> {
+        // We want to write back in the correct format,
+        // can't assume we can safely write XML everywhere?
+        let og_contents =
+            fs::read(&self.plist_path).map_err(|_| "Couldn't read file".to_string())?;
+
+        let mut contents = vec![];
+        if is_binary_plist(&og_contents) {
+            plist.to_writer_binary(&mut contents)
+        } else {
+            plist.to_writer_xml(&mut contents)
+        }
         .map_err(|e| e.to_string())?;
 
-    // Start $EDITOR
-    let exit = Command::new(&*EDITOR)
-        .arg(&temp_path)
-        .status()
-        .map_err(|e| format!("{} failed: {}", &*EDITOR, e.to_string()))?;
+        write_plist(&self.plist_path, &contents)?;
+        history.save(&self.label, &og_contents)?;
+        self.abort();
 
-    if !exit.success() {
-        return Err(format!("{} did not exit successfully", &*EDITOR));
+        Ok(())
     }
 
-    // temp file -> validate with crate -> original
-    let plist =
-        plist::Value::from_file(&temp_path).map_err(|e| format!("Changes not saved: {}", e))?;
+    /// Throw away the edit
+    pub fn abort(&self) {
+        if let Err(e) = fs::remove_file(&self.temp_path) {
+            log::error!("Couldn't remove {:?}: {}", self.temp_path, e);
+        }
+    }
 
-    if og_plist == plist {
-        return Err("No changes made".to_string());
+    fn fail(&self, msg: String) -> EditError {
+        self.abort();
+        EditError::Failed(msg)
     }
+}
 
-    let writer = if is_binary {
-        plist::Value::to_file_binary
-    } else {
-        plist::Value::to_file_xml
-    };
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::fs;
+    use std::path::{Path, PathBuf};
 
-    writer(&plist, &plist_meta.plist_path).map_err(|e| e.to_string())?;
+    use launchd_plist::discovery::{LaunchdEntryLocation, LaunchdEntryType, PlistDir, RootedFs};
+
+    use crate::launchd::plist::reconcile;
+
+    fn write_plist(path: &Path, label: &str) {
+        let mut dict = plist::Dictionary::new();
+        dict.insert("Label".to_string(), label.into());
+        plist::Value::Dictionary(dict).to_file_xml(path).unwrap();
+    }
 
-    Ok(())
+    fn labels(changed: &[&str]) -> HashSet<String> {
+        changed.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn reconcile_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        let fs = RootedFs::default();
+        let dirs = vec![PlistDir {
+            path: dir.to_path_buf(),
+            location: LaunchdEntryLocation::User,
+            entry_type: LaunchdEntryType::Agent,
+        }];
+
+        let (mut label_map, mut path_map) = (HashMap::new(), HashMap::new());
+        let mut apply = |paths: &[&PathBuf]| {
+            let changed = reconcile(
+                &fs,
+                &dirs,
+                &mut label_map,
+                &mut path_map,
+                paths.iter().map(|p| p.to_path_buf()),
+            );
+            let mut mapped: Vec<(String, String)> = label_map
+                .iter()
+                .map(|(l, e)| (l.clone(), e.plist_path.clone()))
+                .collect();
+            mapped.sort();
+            (changed, mapped)
+        };
+
+        let a = dir.join("a.plist");
+        let b = dir.join("b.plist");
+        let path = |p: &PathBuf| p.to_string_lossy().to_string();
+
+        // Create
+        write_plist(&a, "com.example.a");
+        assert_eq!(
+            apply(&[&a]),
+            (
+                labels(&["com.example.a"]),
+                vec![("com.example.a".to_string(), path(&a))]
+            )
+        );
+
+        // Label changed inside the file
+        write_plist(&a, "com.example.renamed");
+        assert_eq!(
+            apply(&[&a]),
+            (
+                labels(&["com.example.a", "com.example.renamed"]),
+                vec![("com.example.renamed".to_string(), path(&a))]
+            )
+        );
+
+        // Rename
+        fs::rename(&a, &b).unwrap();
+        assert_eq!(
+            apply(&[&a, &b]),
+            (
+                labels(&["com.example.renamed"]),
+                vec![("com.example.renamed".to_string(), path(&b))]
+            )
+        );
+
+        // Same label in two files, the other takes over once one is removed
+        write_plist(&a, "com.example.renamed");
+        apply(&[&a]);
+        fs::remove_file(&a).unwrap();
+        assert_eq!(
+            apply(&[&a]),
+            (
+                labels(&["com.example.renamed"]),
+                vec![("com.example.renamed".to_string(), path(&b))]
+            )
+        );
+
+        // Remove
+        fs::remove_file(&b).unwrap();
+        assert_eq!(apply(&[&b]), (labels(&["com.example.renamed"]), vec![]));
+        assert!(path_map.is_empty());
+    }
 }
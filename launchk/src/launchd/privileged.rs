@@ -0,0 +1,200 @@
+use std::env;
+use std::fs::{self, OpenOptions, Permissions};
+use std::io::{self, Cursor, ErrorKind, Write};
+use std::os::unix::fs::{fchown, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::launchd::plist::{create_private_file, create_temp_file, is_binary_plist, PLIST_DIRS};
+
+/// Write contents over the plist at path (what it links to, if it's a
+/// symlink), keeping owner and mode. If we can't (e.g. a root owned
+/// daemon), `sudo launchk install-plist` does it instead, so the TUI
+/// doesn't have to run as root
+pub fn write_plist(path: &str, contents: &[u8]) -> Result<(), String> {
+    let target = fs::canonicalize(path).map_err(|e| format!("{}: {}", path, e))?;
+    let meta = target.metadata().map_err(|e| format!("{}: {}", path, e))?;
+
+    match replace(
+        &target,
+        contents,
+        (meta.uid(), meta.gid(), meta.mode() & 0o7777),
+        true,
+    ) {
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            sudo_install(&target.to_string_lossy(), contents)
+        }
+        result => result.map_err(|e| format!("{}: {}", path, e)),
+    }
+}
+
+/// Write contents to a temp file next to target, owned by uid:gid with
+/// mode, and move it into place, so target is never half written. If
+/// there wasn't a target (exists false), fails if one appeared meanwhile
+fn replace(
+    target: &Path,
+    contents: &[u8],
+    (uid, gid, mode): (u32, u32, u32),
+    exists: bool,
+) -> io::Result<()> {
+    let dir = target
+        .parent()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "no parent directory"))?;
+
+    // Next to target, so the rename is atomic
+    let (temp_path, mut file) = create_private_file(dir, "install.tmp")?;
+
+    let result = file
+        .write_all(contents)
+        .and_then(|_| fchown(&file, Some(uid), Some(gid)))
+        .and_then(|_| file.set_permissions(Permissions::from_mode(mode)))
+        .and_then(|_| file.sync_all())
+        .and_then(|_| {
+            if exists {
+                fs::rename(&temp_path, target)
+            } else {
+                // Unlike rename, fails if something appeared at target meanwhile
+                fs::hard_link(&temp_path, target).and_then(|_| fs::remove_file(&temp_path))
+            }
+        });
+
+    if result.is_err() {
+        fs::remove_file(&temp_path).unwrap_or(());
+    }
+
+    result
+}
+
+/// Write a new plist at path, failing if there is one already. Like
+/// write_plist, goes through sudo if we can't write to the directory
+pub fn create_plist(path: &str, contents: &[u8]) -> Result<(), String> {
+    if let Some(dir) = Path::new(path).parent() {
+        // ~/Library/LaunchAgents may not exist yet
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+
+    match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o644)
+        .open(path)
+    {
+        Ok(mut file) => file.write_all(contents).map_err(|e| {
+            fs::remove_file(path).unwrap_or(());
+            format!("{}: {}", path, e)
+        }),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => sudo_install(path, contents),
+        Err(e) => Err(format!("{}: {}", path, e)),
+    }
+}
+
+fn sudo_install(path: &str, contents: &[u8]) -> Result<(), String> {
+    let (temp_path, mut file) = create_temp_file("install.plist")?;
+
+    let output = file
+        .write_all(contents)
+        .map_err(|e| e.to_string())
+        .and_then(|_| env::current_exe().map_err(|e| e.to_string()))
+        .and_then(|exe| {
+            // Only so sudo's prompt isn't drawn over the TUI
+            if let Err(e) = clearscreen::clear() {
+                log::warn!("sudo_install: can't clear screen: {}", e);
+            }
+
+            Command::new("sudo")
+                .arg("-p")
+                .arg(format!("Password to write {}: ", path))
+                .arg(exe)
+                .arg("install-plist")
+                .arg(&temp_path)
+                .arg(path)
+                .stdin(Stdio::inherit())
+                .output()
+                .map_err(|e| format!("sudo failed: {}", e))
+        });
+
+    fs::remove_file(&temp_path).unwrap_or(());
+    let output = output?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Couldn't write {}: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Only write plists in the directories launchk looks for them in
+/// (PLIST_DIRS). None if target doesn't exist yet
+fn check_target(target: &Path) -> Result<Option<fs::Metadata>, String> {
+    let not_allowed = || {
+        format!(
+            "{} is not a plist in one of launchk's plist directories",
+            target.display()
+        )
+    };
+
+    let dir = target
+        .parent()
+        .and_then(|d| d.canonicalize().ok())
+        .ok_or_else(not_allowed)?;
+
+    // As root, that's every user's LaunchAgents too
+    let is_plist_dir = PLIST_DIRS
+        .iter()
+        .filter_map(|d| d.path.canonicalize().ok())
+        .any(|d| d == dir);
+
+    let is_plist = target.extension().map(|e| e == "plist").unwrap_or(false);
+
+    if !is_plist_dir || !is_plist {
+        return Err(not_allowed());
+    }
+
+    match target.symlink_metadata() {
+        Ok(meta) if meta.file_type().is_file() => Ok(Some(meta)),
+        Ok(_) => Err(format!("{} is not a regular file", target.display())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("{}: {}", target.display(), e)),
+    }
+}
+
+/// The privileged half of write_plist, run as root by
+/// `launchk install-plist <source> <target>`. Replaces target
+/// with source, keeping target's owner, mode and encoding. A new
+/// target gets the directory's owner (e.g. root:wheel) and 0644
+pub fn install(source: &Path, target: &Path) -> Result<(), String> {
+    let meta = check_target(target)?;
+    let dir = target.parent().expect("Checked in check_target");
+
+    let contents = fs::read(source).map_err(|e| format!("{}: {}", source.display(), e))?;
+    plist::Value::from_reader(Cursor::new(&contents))
+        .map_err(|e| format!("{}: {}", source.display(), e))?;
+
+    let (uid, gid, mode) = match &meta {
+        Some(meta) => {
+            let og_contents =
+                fs::read(target).map_err(|e| format!("{}: {}", target.display(), e))?;
+            if is_binary_plist(&contents) != is_binary_plist(&og_contents) {
+                return Err(format!(
+                    "{} would change encoding (binary/XML)",
+                    target.display()
+                ));
+            }
+
+            (meta.uid(), meta.gid(), meta.mode() & 0o7777)
+        }
+        None => {
+            let dir_meta = dir
+                .metadata()
+                .map_err(|e| format!("{}: {}", dir.display(), e))?;
+            (dir_meta.uid(), dir_meta.gid(), 0o644)
+        }
+    };
+
+    replace(target, &contents, (uid, gid, mode), meta.is_some())
+        .map_err(|e| format!("{}: {}", target.display(), e))
+}
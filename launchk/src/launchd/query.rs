@@ -1,34 +1,39 @@
 use crate::launchd::message::{
-    DISABLE_NAMES, DUMPJPCATEGORY, DUMPSTATE, ENABLE_NAMES, LIST_SERVICES, LOAD_PATHS, PROCINFO,
-    UNLOAD_PATHS,
+    DISABLE_NAMES, ENABLE_NAMES, LIST_SERVICES, LOAD_PATHS, UNLOAD_PATHS,
 };
+#[cfg(target_os = "macos")]
+use crate::launchd::message::{DUMPJPCATEGORY, DUMPSTATE, PROCINFO};
 use std::collections::HashSet;
+#[cfg(target_os = "macos")]
 use std::convert::TryFrom;
 
-use xpc_sys::{
-    objects::xpc_shmem::XPCShmem,
-    rs_geteuid,
-    traits::{xpc_pipeable::XPCPipeable, xpc_value::TryXPCValue},
-    MAP_SHARED,
-};
+#[cfg(target_os = "macos")]
+use xpc_sys::{objects::xpc_shmem::XPCShmem, traits::xpc_value::TryXPCValue, MAP_SHARED};
+use xpc_sys::{rs_geteuid, traits::xpc_transport::XPCTransport};
 
 use crate::launchd::entry_status::ENTRY_STATUS_CACHE;
 use std::iter::FromIterator;
-use xpc_sys::objects::xpc_dictionary::XPCDictionary;
 use xpc_sys::objects::xpc_error::XPCError;
+use xpc_sys::objects::xpc_value::XPCValue;
+use xpc_sys::routines::Routine;
 use xpc_sys::traits::query_builder::QueryBuilder;
 
+use xpc_sys::domain_target::DomainTarget;
 use xpc_sys::enums::{DomainType, SessionType};
 
-pub fn find_in_all<S: Into<String>>(label: S) -> Result<(DomainType, XPCDictionary), XPCError> {
+pub fn find_in_all<T: XPCTransport + ?Sized, S: Into<String>>(
+    transport: &T,
+    label: S,
+) -> Result<(DomainType, XPCValue), XPCError> {
     let label_string = label.into();
 
     for domain_type in DomainType::System as u64..DomainType::RequestorDomain as u64 {
-        let response = XPCDictionary::new()
+        let request = XPCValue::new_dictionary()
             .extend(&LIST_SERVICES)
             .entry("type", domain_type)
-            .entry("name", label_string.clone())
-            .pipe_routine_with_error_handling();
+            .entry("name", label_string.clone());
+
+        let response = Routine::List.pipe(transport, &request);
 
         if response.is_ok() {
             return response.map(|r| (domain_type.into(), r));
@@ -39,16 +44,21 @@ pub fn find_in_all<S: Into<String>>(label: S) -> Result<(DomainType, XPCDictiona
 }
 
 /// Query for jobs in a domain
-pub fn list(domain_type: DomainType, name: Option<String>) -> Result<XPCDictionary, XPCError> {
-    XPCDictionary::new()
+pub fn list<T: XPCTransport + ?Sized>(
+    transport: &T,
+    domain_type: DomainType,
+    name: Option<String>,
+) -> Result<XPCValue, XPCError> {
+    let request = XPCValue::new_dictionary()
         .extend(&LIST_SERVICES)
         .with_domain_type_or_default(Some(domain_type))
-        .entry_if_present("name", name)
-        .pipe_routine_with_error_handling()
+        .entry_if_present("name", name);
+
+    Routine::List.pipe(transport, &request)
 }
 
 /// Query for jobs across all domain types
-pub fn list_all() -> HashSet<String> {
+pub fn list_all<T: XPCTransport + ?Sized>(transport: &T) -> HashSet<String> {
     let mut everything = vec![
         DomainType::System,
         DomainType::RequestorUserDomain,
@@ -62,9 +72,10 @@ pub fn list_all() -> HashSet<String> {
     let list = everything
         .iter()
         .filter_map(|t| {
-            let svc_for_type = list(t.clone(), None)
-                .and_then(|d| d.get_as_dictionary(&["services"]))
-                .map(|XPCDictionary(ref hm)| hm.keys().map(|k| k.clone()).collect());
+            let svc_for_type = list(transport, t.clone(), None).and_then(|d| {
+                d.get_as_dictionary(["services"])
+                    .map(|services| services.keys().cloned().collect())
+            });
 
             if svc_for_type.is_err() {
                 log::error!(
@@ -82,126 +93,274 @@ pub fn list_all() -> HashSet<String> {
     HashSet::from_iter(list)
 }
 
-pub fn load<S: Into<String>>(
+/// The domain launchk means by a domain type: gui and user are the
+/// euid's unless a handle is given, the rest fall back on handle 0
+pub fn domain_target(domain_type: DomainType, handle: Option<u64>) -> DomainTarget {
+    let handle = handle.unwrap_or_else(|| match domain_type {
+        DomainType::Gui | DomainType::User => rs_geteuid() as u64,
+        _ => 0,
+    });
+
+    DomainTarget::new(domain_type, handle, None)
+}
+
+pub fn load<T: XPCTransport + ?Sized, S: Into<String>>(
+    transport: &T,
     label: S,
     plist_path: S,
-    domain_type: Option<DomainType>,
+    domain: &DomainTarget,
     session: Option<SessionType>,
-    handle: Option<u64>,
-) -> Result<XPCDictionary, XPCError> {
+) -> Result<XPCValue, XPCError> {
     ENTRY_STATUS_CACHE
         .lock()
         .expect("Must invalidate")
         .remove(&label.into());
 
-    XPCDictionary::new()
+    let request = XPCValue::new_dictionary()
         .extend(&LOAD_PATHS)
-        .with_domain_type_or_default(domain_type)
+        .with_domain_target(&domain.domain())
         .with_session_type_or_default(session)
-        .with_handle_or_default(handle)
-        .entry("paths", vec![plist_path.into()])
-        .pipe_routine_with_error_handling()
+        .entry("paths", vec![plist_path.into()]);
+
+    Routine::Load.pipe(transport, &request)
 }
 
-pub fn unload<S: Into<String>>(
+pub fn unload<T: XPCTransport + ?Sized, S: Into<String>>(
+    transport: &T,
     label: S,
     plist_path: S,
-    domain_type: Option<DomainType>,
+    domain: &DomainTarget,
     session: Option<SessionType>,
-    handle: Option<u64>,
-) -> Result<XPCDictionary, XPCError> {
+) -> Result<XPCValue, XPCError> {
     ENTRY_STATUS_CACHE
         .lock()
         .expect("Must invalidate")
         .remove(&label.into());
 
-    XPCDictionary::new()
+    let request = XPCValue::new_dictionary()
         .extend(&UNLOAD_PATHS)
-        .with_domain_type_or_default(domain_type)
+        .with_domain_target(&domain.domain())
         .with_session_type_or_default(session)
-        .with_handle_or_default(handle)
-        .entry("paths", vec![plist_path.into()])
-        .pipe_routine_with_error_handling()
+        .entry("paths", vec![plist_path.into()]);
+
+    Routine::Unload.pipe(transport, &request)
 }
 
-pub fn enable<S: Into<String>>(
+pub fn enable<T: XPCTransport + ?Sized, S: Into<String>>(
+    transport: &T,
     label: S,
-    domain_type: DomainType,
-) -> Result<XPCDictionary, XPCError> {
+    domain: &DomainTarget,
+) -> Result<XPCValue, XPCError> {
     let label_string = label.into();
 
-    XPCDictionary::new()
+    let request = XPCValue::new_dictionary()
         .extend(&ENABLE_NAMES)
-        .with_domain_type_or_default(Some(domain_type))
-        .entry("name", label_string.clone())
-        .entry("names", vec![label_string])
-        .with_handle_or_default(None)
-        .pipe_routine_with_error_handling()
+        .with_domain_target(&domain.clone().with_label(label_string.clone()))
+        .entry("names", vec![label_string]);
+
+    Routine::Enable.pipe(transport, &request)
 }
 
-pub fn disable<S: Into<String>>(
+pub fn disable<T: XPCTransport + ?Sized, S: Into<String>>(
+    transport: &T,
     label: S,
-    domain_type: DomainType,
-) -> Result<XPCDictionary, XPCError> {
+    domain: &DomainTarget,
+) -> Result<XPCValue, XPCError> {
     let label_string = label.into();
 
-    XPCDictionary::new()
+    let request = XPCValue::new_dictionary()
         .extend(&DISABLE_NAMES)
-        .with_domain_type_or_default(Some(domain_type))
-        .entry("name", label_string.clone())
-        .entry("names", vec![label_string])
-        .with_handle_or_default(None)
-        .pipe_routine_with_error_handling()
+        .with_domain_target(&domain.clone().with_label(label_string.clone()))
+        .entry("names", vec![label_string]);
+
+    Routine::Disable.pipe(transport, &request)
 }
 
 /// Create a shared shmem region for the XPC routine to write
 /// dumpstate contents into, and return the bytes written and
 /// shmem region
-pub fn dumpstate() -> Result<(usize, XPCShmem), XPCError> {
+#[cfg(target_os = "macos")]
+pub fn dumpstate<T: XPCTransport + ?Sized>(transport: &T) -> Result<(usize, XPCShmem), XPCError> {
     let shmem = XPCShmem::new_task_self(
         0x1400000,
         i32::try_from(MAP_SHARED).expect("Must conv flags"),
     )?;
 
-    let response = XPCDictionary::new()
+    let request = XPCValue::new_dictionary()
         .extend(&DUMPSTATE)
-        .entry("shmem", &shmem.xpc_object)
-        .pipe_routine_with_error_handling()?;
+        .entry("shmem", &shmem);
+
+    let response = Routine::Dumpstate.pipe(transport, &request)?;
 
     let bytes_written: u64 = response.get(&["bytes-written"])?.xpc_value()?;
 
     Ok((usize::try_from(bytes_written).unwrap(), shmem))
 }
 
-pub fn dumpjpcategory() -> Result<(usize, XPCShmem), XPCError> {
+#[cfg(target_os = "macos")]
+pub fn dumpjpcategory<T: XPCTransport + ?Sized>(
+    transport: &T,
+) -> Result<(usize, XPCShmem), XPCError> {
     let shmem = XPCShmem::new_task_self(
         0x1400000,
         i32::try_from(MAP_SHARED).expect("Must conv flags"),
     )?;
 
-    let response = XPCDictionary::new()
+    let request = XPCValue::new_dictionary()
         .extend(&DUMPJPCATEGORY)
-        .entry("shmem", &shmem.xpc_object)
-        .pipe_routine_with_error_handling()?;
+        .entry("shmem", &shmem);
+
+    let response = Routine::Dumpjpcategory.pipe(transport, &request)?;
 
     let bytes_written: u64 = response.get(&["bytes-written"])?.xpc_value()?;
 
     Ok((usize::try_from(bytes_written).unwrap(), shmem))
 }
 
-pub fn procinfo(pid: i64) -> Result<(usize, XPCShmem), XPCError> {
+#[cfg(target_os = "macos")]
+pub fn procinfo<T: XPCTransport + ?Sized>(
+    transport: &T,
+    pid: i64,
+) -> Result<(usize, XPCShmem), XPCError> {
     let shmem = XPCShmem::new_task_self(
         0x1400000,
         i32::try_from(MAP_SHARED).expect("Must conv flags"),
     )?;
 
-    let response = XPCDictionary::new()
+    let request = XPCValue::new_dictionary()
         .extend(&PROCINFO)
-        .entry("shmem", &shmem.xpc_object)
-        .entry("pid", pid)
-        .pipe_routine_with_error_handling()?;
+        .entry("shmem", &shmem)
+        .entry("pid", pid);
+
+    let response = Routine::Procinfo.pipe(transport, &request)?;
 
     let bytes_written: u64 = response.get(&["bytes-written"])?.xpc_value()?;
 
     Ok((usize::try_from(bytes_written).unwrap(), shmem))
 }
+
+#[cfg(test)]
+mod tests {
+    use xpc_sys::enums::{DomainType, SessionType};
+    use xpc_sys::transport::fake_launchd::{FakeLaunchd, FakeService};
+
+    use xpc_sys::rs_geteuid;
+    use xpc_sys::traits::xpc_value::TryXPCValue;
+
+    use crate::launchd::query::{
+        disable, domain_target, enable, find_in_all, list_all, load, unload,
+    };
+
+    const PLIST_PATH: &str = "/Library/LaunchDaemons/com.example.job.plist";
+
+    fn fake() -> FakeLaunchd {
+        FakeLaunchd::new()
+            .with_service(FakeService::new("com.apple.running").loaded(42))
+            .with_service(FakeService::new("com.example.job").with_plist_path(PLIST_PATH))
+    }
+
+    #[test]
+    fn load_then_unload() {
+        let fake = fake();
+
+        assert!(find_in_all(&fake, "com.example.job").is_err());
+
+        load(
+            &fake,
+            "com.example.job",
+            PLIST_PATH,
+            &domain_target(DomainType::System, None),
+            Some(SessionType::System),
+        )
+        .unwrap();
+
+        let (domain, _) = find_in_all(&fake, "com.example.job").unwrap();
+        assert_eq!(domain, DomainType::System);
+        assert!(list_all(&fake).contains("com.example.job"));
+
+        unload(
+            &fake,
+            "com.example.job",
+            PLIST_PATH,
+            &domain_target(DomainType::System, None),
+            None,
+        )
+        .unwrap();
+
+        assert!(!list_all(&fake).contains("com.example.job"));
+    }
+
+    #[test]
+    fn disable_blocks_load() {
+        let fake = fake();
+
+        disable(
+            &fake,
+            "com.example.job",
+            &domain_target(DomainType::System, None),
+        )
+        .unwrap();
+        assert!(load(
+            &fake,
+            "com.example.job",
+            PLIST_PATH,
+            &domain_target(DomainType::System, None),
+            None
+        )
+        .is_err());
+
+        enable(
+            &fake,
+            "com.example.job",
+            &domain_target(DomainType::System, None),
+        )
+        .unwrap();
+        assert!(load(
+            &fake,
+            "com.example.job",
+            PLIST_PATH,
+            &domain_target(DomainType::System, None),
+            None
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn gui_domain_is_ours() {
+        let fake = fake();
+
+        enable(
+            &fake,
+            "com.example.job",
+            &domain_target(DomainType::Gui, None),
+        )
+        .unwrap();
+        load(
+            &fake,
+            "com.example.job",
+            PLIST_PATH,
+            &domain_target(DomainType::Gui, Some(501)),
+            None,
+        )
+        .unwrap();
+
+        let handles: Vec<(u64, u64)> = fake
+            .requests()
+            .iter()
+            .map(|r| {
+                (
+                    r.get(["type"]).unwrap().xpc_value().unwrap(),
+                    r.get(["handle"]).unwrap().xpc_value().unwrap(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            handles,
+            vec![
+                (DomainType::Gui as u64, rs_geteuid() as u64),
+                (DomainType::Gui as u64, 501)
+            ]
+        );
+    }
+}
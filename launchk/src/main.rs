@@ -9,38 +9,113 @@ extern crate bitflags;
 
 extern crate plist;
 
-use clearscreen;
 use cursive::view::Resizable;
 use cursive::views::{NamedView, Panel};
+use cursive::Cursive;
 use git_version::git_version;
+use std::env;
 use std::process::exit;
+use std::sync::Arc;
+use xpc_sys::traits::xpc_transport::XPCTransport;
+#[cfg(target_os = "macos")]
+use xpc_sys::transport::bootstrap_pipe::BootstrapPipe;
+#[cfg(not(target_os = "macos"))]
+use xpc_sys::transport::fake_launchd::FakeLaunchd;
+use xpc_sys::transport::recording::RecordingTransport;
+use xpc_sys::transport::replay::{ReplayMode, ReplayTransport};
 
+use crate::launchd::entry_status::reload_stale;
 use crate::launchd::plist::{init_plist_map, PLIST_MAP_INIT};
 use crate::tui::root::RootLayout;
 
+mod cli;
+mod config;
 mod launchd;
+mod offline;
 mod tui;
 
+/// launchd over the bootstrap pipe
+#[cfg(target_os = "macos")]
+fn launchd() -> BootstrapPipe {
+    BootstrapPipe
+}
+
+/// There's no launchd to ask, only a volume to read with --root
+#[cfg(not(target_os = "macos"))]
+fn launchd() -> FakeLaunchd {
+    if !offline::is_offline() {
+        eprintln!("launchd is only on macOS, use --root <volume> to read one offline");
+        exit(1);
+    }
+
+    // Nothing is sent offline
+    FakeLaunchd::new()
+}
+
+/// launchd, or with $LAUNCHK_REPLAY a session saved by $LAUNCHK_RECORD
+/// (JSON, or a plist if the path ends in .plist) answering instead
+fn transport() -> Arc<dyn XPCTransport> {
+    if let Ok(path) = env::var("LAUNCHK_REPLAY") {
+        return match ReplayTransport::from_file(&path, ReplayMode::Matched) {
+            Ok(replay) => Arc::new(replay),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                exit(1);
+            }
+        };
+    }
+
+    match env::var("LAUNCHK_RECORD") {
+        Ok(path) => Arc::new(RecordingTransport::new(launchd(), path)),
+        Err(_) => Arc::new(launchd()),
+    }
+}
+
 fn main() {
     env_logger::init();
 
+    // Exits with the error if the config can't be read
+    lazy_static::initialize(&config::CONFIG);
+    // Or if --root isn't a directory
+    lazy_static::initialize(&offline::OFFLINE_ROOT);
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(code) = cli::run(offline::split_root(&args).1) {
+        exit(code);
+    }
+
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .expect("Must build tokio runtime");
 
-    // Cache launchd job plist paths, spawn fsnotify to keep up with changes
-    PLIST_MAP_INIT.call_once(|| init_plist_map(runtime.handle()));
-
+    let transport = transport();
     let mut siv = cursive::default();
+
+    // Cache launchd job plist paths, spawn fsnotify to keep up with changes,
+    // reload stale jobs if configured and redraw when they happen
+    let cb_sink = siv.cb_sink().clone();
+    let reload_transport = transport.clone();
+    PLIST_MAP_INIT.call_once(|| {
+        init_plist_map(runtime.handle(), move |labels| {
+            reload_stale(&*reload_transport, labels);
+            cb_sink.send(Box::new(Cursive::noop)).expect("Must send")
+        })
+    });
+
     siv.load_toml(include_str!("tui/style.toml"))
         .expect("Must load styles");
 
-    let root_layout = RootLayout::new(&mut siv, runtime.handle());
+    let root_layout = RootLayout::new(&mut siv, runtime.handle(), transport);
     let root_layout = NamedView::new("root_layout", root_layout);
 
+    let title = match &*offline::OFFLINE_ROOT {
+        Some(root) => format!("launchk ({}) offline: {}", git_version!(), root.display()),
+        None => format!("launchk ({})", git_version!()),
+    };
+
     let panel = Panel::new(root_layout)
-        .title(format!("launchk ({})", git_version!()))
+        .title(title)
         .full_width()
         .full_height();
 
@@ -0,0 +1,62 @@
+use std::env;
+use std::path::PathBuf;
+use std::process::exit;
+
+use launchd_plist::discovery::RootedFs;
+use launchd_plist::overrides::{read_overrides, Overrides};
+
+lazy_static! {
+    /// `launchk --root <volume>`: another Mac's disk (or a copy of one)
+    /// to look at instead of this one
+    pub static ref OFFLINE_ROOT: Option<PathBuf> = {
+        let args: Vec<String> = env::args().skip(1).collect();
+
+        match split_root(&args) {
+            (Some(root), _) if !root.is_dir() => {
+                eprintln!("{}: not a directory", root.display());
+                exit(1);
+            }
+            (root, _) => root,
+        }
+    };
+    /// Its disabled and override databases
+    pub static ref OVERRIDES: Vec<Overrides> = OFFLINE_ROOT
+        .as_ref()
+        .map(|root| read_overrides(&RootedFs::new(root)))
+        .unwrap_or_default();
+}
+
+/// No launchd to ask, and nothing on the volume is changed
+pub fn is_offline() -> bool {
+    OFFLINE_ROOT.is_some()
+}
+
+/// `--root <volume>` if the arguments start with it, and the rest
+pub fn split_root(args: &[String]) -> (Option<PathBuf>, &[String]) {
+    match args {
+        [flag, root, rest @ ..] if flag == "--root" => (Some(PathBuf::from(root)), rest),
+        _ => (None, args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::offline::split_root;
+
+    #[test]
+    fn root_argument() {
+        let args: Vec<String> = vec!["--root", "/Volumes/Evidence", "lint"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        assert_eq!(
+            split_root(&args),
+            (Some(PathBuf::from("/Volumes/Evidence")), &args[2..])
+        );
+        assert_eq!(split_root(&args[2..]), (None, &args[2..]));
+        assert_eq!(split_root(&args[..1]), (None, &args[..1]));
+    }
+}
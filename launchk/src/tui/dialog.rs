@@ -1,19 +1,38 @@
+use std::env;
+use std::path::Path;
 use std::sync::mpsc::Sender;
 
+use cursive::traits::{Nameable, Resizable, Scrollable};
 use cursive::Cursive;
 use cursive::{
     theme::Effect,
     view::Margins,
-    views::{Dialog, DummyView, LinearLayout, RadioGroup, TextView},
+    views::{
+        Checkbox, Dialog, DummyView, EditView, LinearLayout, ListView, RadioGroup, SelectView,
+        TextArea, TextView,
+    },
 };
 
 use crate::launchd::entry_status::{get_entry_status, LaunchdEntryStatus};
+use crate::launchd::history::Version;
+use crate::launchd::plist::LaunchdPlist;
+use crate::offline::{is_offline, OVERRIDES};
 use crate::tui::omnibox::command::OmniboxCommand;
 use crate::tui::omnibox::command::OMNIBOX_COMMANDS;
 use crate::tui::omnibox::view::OmniboxEvent;
 use crate::tui::root::CbSinkMessage;
+use launchd_plist::diff::diff;
+use launchd_plist::integrity::Issue;
+use launchd_plist::lint::Diagnostic;
+use launchd_plist::next_run::{CalendarSpec, Clock, LocalClock, RunSchedule};
+use launchd_plist::overrides::overrides_for;
+use launchd_plist::template::{
+    parse_calendar, NewJob, Schedule, Scope, Template, SCOPES, TEMPLATES,
+};
+#[cfg(target_os = "macos")]
 use xpc_sys::csr::{csr_check, CsrConfig};
 use xpc_sys::enums::{DomainType, SessionType};
+use xpc_sys::traits::xpc_transport::XPCTransport;
 
 /// XPC "error" key can be present with no failure..."notice"?
 pub fn show_error(err: String) -> CbSinkMessage {
@@ -39,10 +58,10 @@ pub fn show_prompt(
     let cl = move |siv: &mut Cursive| {
         let ask = Dialog::around(TextView::new(prompt.clone()))
             .button("Yes", move |s| {
-                commands
-                    .iter()
-                    .try_for_each(|c| tx.send(OmniboxEvent::Command(c.clone())))
-                    .expect("Must send commands");
+                for c in commands.iter() {
+                    tx.send(OmniboxEvent::Command(c.clone()))
+                        .expect("Must send commands");
+                }
 
                 s.pop_layer();
             })
@@ -55,9 +74,39 @@ pub fn show_prompt(
     Box::new(cl)
 }
 
+/// OmniboxCommand::Choice(msg, [(button, commands)])
+pub fn show_choice(
+    tx: Sender<OmniboxEvent>,
+    prompt: String,
+    choices: Vec<(String, Vec<OmniboxCommand>)>,
+) -> CbSinkMessage {
+    let cl = move |siv: &mut Cursive| {
+        // e.g. a long diff
+        let mut ask = Dialog::around(TextView::new(prompt.clone()).scrollable()).title("Notice");
+
+        for (button, commands) in choices.iter().cloned() {
+            let tx = tx.clone();
+
+            ask.add_button(button, move |s| {
+                for c in commands.iter() {
+                    tx.send(OmniboxEvent::Command(c.clone()))
+                        .expect("Must send commands");
+                }
+
+                s.pop_layer();
+            });
+        }
+
+        siv.add_layer(ask);
+    };
+
+    Box::new(cl)
+}
+
 /// Don't know how to get this info when job is not running,
 /// so we can ask user and suggest a default (domain 7, aqua)
-pub fn domain_session_prompt<S: Into<String>>(
+pub fn domain_session_prompt<T: XPCTransport + ?Sized, S: Into<String>>(
+    transport: &T,
     label: S,
     domain_only: bool,
     tx: Sender<OmniboxEvent>,
@@ -67,7 +116,7 @@ pub fn domain_session_prompt<S: Into<String>>(
         limit_load_to_session_type,
         domain,
         ..
-    } = get_entry_status(label);
+    } = get_entry_status(transport, label);
 
     let cl = move |siv: &mut Cursive| {
         let mut domain_group: RadioGroup<DomainType> = RadioGroup::new();
@@ -122,10 +171,10 @@ pub fn domain_session_prompt<S: Into<String>>(
                     Some(st_group.selection().as_ref().clone())
                 };
 
-                f(dt, st)
-                    .iter()
-                    .try_for_each(|c| tx.send(OmniboxEvent::Command(c.clone())))
-                    .expect("Must send commands");
+                for c in f(dt, st) {
+                    tx.send(OmniboxEvent::Command(c))
+                        .expect("Must send commands");
+                }
 
                 s.pop_layer();
             })
@@ -138,6 +187,93 @@ pub fn domain_session_prompt<S: Into<String>>(
     Box::new(cl)
 }
 
+/// Pick one of a plist's earlier versions to diff against the
+/// current one, or to restore (and reload)
+pub fn history_prompt(
+    tx: Sender<OmniboxEvent>,
+    label: String,
+    plist_path: String,
+    versions: Vec<Version>,
+) -> CbSinkMessage {
+    let cl = move |siv: &mut Cursive| {
+        let select = SelectView::new()
+            .with_all(versions.iter().map(|v| (v.to_string(), v.clone())))
+            .with_name("history_select");
+
+        let selected = |s: &mut Cursive| {
+            s.call_on_name("history_select", |v: &mut SelectView<Version>| {
+                v.selection()
+            })
+            .flatten()
+        };
+
+        // The job this was opened for, not whichever is highlighted now
+        let restore = |tx: Sender<OmniboxEvent>, and_reload: bool| {
+            let (label, plist_path) = (label.clone(), plist_path.clone());
+
+            move |s: &mut Cursive| {
+                if let Some(version) = selected(s) {
+                    let restore = OmniboxCommand::restore(
+                        &label,
+                        &plist_path,
+                        version.path.clone(),
+                        and_reload,
+                    );
+
+                    tx.send(OmniboxEvent::Command(restore))
+                        .expect("Must send commands");
+                }
+
+                s.pop_layer();
+            }
+        };
+
+        let (restore, restore_and_reload) = (restore(tx.clone(), false), restore(tx.clone(), true));
+
+        let ask = Dialog::new()
+            .title(format!("History: {}", label))
+            .content(select)
+            .button("Diff", move |s| {
+                if let Some(version) = selected(s) {
+                    s.add_layer(show_version_diff(&plist_path, &version));
+                }
+            })
+            .button("Restore", restore)
+            .button("Restore and reload", restore_and_reload)
+            .dismiss_button("Close")
+            .padding(Margins::trbl(4, 4, 4, 4));
+
+        siv.add_layer(ask);
+    };
+
+    Box::new(cl)
+}
+
+/// What restoring version would change in the plist at plist_path
+fn show_version_diff(plist_path: &str, version: &Version) -> Dialog {
+    let changes = plist::Value::from_file(plist_path)
+        .and_then(|current| plist::Value::from_file(&version.path).map(|old| diff(&current, &old)))
+        .map(|changes| {
+            if changes.is_empty() {
+                "Same as current".to_string()
+            } else {
+                changes
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            }
+        })
+        .unwrap_or_else(|e| e.to_string());
+
+    Dialog::new()
+        .title(format!("Restoring {}", version))
+        .content(TextView::new(changes))
+        .dismiss_button("OK")
+        .padding(Margins::trbl(4, 4, 4, 4))
+}
+
+#[cfg(target_os = "macos")]
 pub fn show_csr_info() -> CbSinkMessage {
     let csr_flags = (0..11)
         .map(|s| {
@@ -179,3 +315,411 @@ pub fn show_help() -> CbSinkMessage {
         )
     })
 }
+
+/// Where a job comes from, what it's doing, and when it runs next
+pub fn show_info(label: String, status: LaunchdEntryStatus, is_loaded: bool) -> CbSinkMessage {
+    let LaunchdEntryStatus {
+        plist,
+        limit_load_to_session_type,
+        domain,
+        pid,
+        drift,
+        ..
+    } = status;
+
+    let mut lines = vec![
+        format!("{:<10}{}", "Label", label),
+        format!(
+            "{:<10}{}",
+            "Plist",
+            plist.as_ref().map(|p| p.plist_path.as_str()).unwrap_or("-")
+        ),
+        format!(
+            "{:<10}{}",
+            "Job Type",
+            plist
+                .as_ref()
+                .map(|p| format!("{}/{}", p.entry_location, p.entry_type))
+                .unwrap_or("-".to_string())
+        ),
+    ];
+
+    // Offline there's no launchd, but there are its databases
+    if is_offline() {
+        lines.extend(
+            overrides_for(&OVERRIDES, &label)
+                .into_iter()
+                .map(|(domain, disabled)| {
+                    format!(
+                        "{:<10}{}: {}",
+                        "Disabled",
+                        domain,
+                        if disabled { "yes" } else { "no" }
+                    )
+                }),
+        );
+    } else {
+        lines.extend(vec![
+            format!("{:<10}{}", "Domain", domain),
+            format!("{:<10}{}", "Session", limit_load_to_session_type),
+            format!(
+                "{:<10}{}",
+                "PID",
+                if pid > 0 {
+                    pid.to_string()
+                } else {
+                    "-".to_string()
+                }
+            ),
+            format!("{:<10}{}", "Loaded", if is_loaded { "yes" } else { "no" }),
+        ]);
+    }
+
+    if let Some(LaunchdPlist {
+        schedule: Some(schedule),
+        ..
+    }) = &plist
+    {
+        lines.push("".to_string());
+        lines.extend(describe_schedule(schedule, is_loaded));
+    }
+
+    if !drift.is_empty() {
+        lines.push("".to_string());
+        lines.push("Stale, the plist changed since it was loaded:".to_string());
+        lines.extend(drift.iter().map(|d| format!("  {}", d)));
+    }
+
+    let findings = plist
+        .as_ref()
+        .map(|p| p.findings.as_slice())
+        .unwrap_or_default();
+    if !findings.is_empty() {
+        lines.push("".to_string());
+        lines.push("Audit:".to_string());
+        lines.extend(findings.iter().map(|f| format!("  {}", f)));
+    }
+
+    Box::new(move |siv| {
+        siv.add_layer(
+            Dialog::new()
+                .title(format!("Info: {}", label))
+                .content(TextView::new(lines.join("\n")))
+                .dismiss_button("OK")
+                .padding(Margins::trbl(4, 4, 4, 4)),
+        )
+    })
+}
+
+fn describe_schedule(schedule: &RunSchedule, is_loaded: bool) -> Vec<String> {
+    let mut lines = vec![];
+
+    if let Some(interval) = schedule.start_interval {
+        lines.push(format!("StartInterval: every {}s after load", interval));
+    }
+
+    if !schedule.start_calendar_interval.is_empty() {
+        lines.push("StartCalendarInterval:".to_string());
+        lines.extend(
+            schedule
+                .start_calendar_interval
+                .iter()
+                .map(|spec| format!("  {}", describe_calendar(spec))),
+        );
+    }
+
+    if is_offline() {
+        return lines;
+    }
+
+    if !is_loaded {
+        lines.push("Not loaded, so launchd won't start it".to_string());
+        return lines;
+    }
+
+    let runs = schedule.next_runs(&LocalClock, None, 5);
+    if !runs.is_empty() {
+        lines.push("Next runs:".to_string());
+        lines.extend(
+            runs.iter()
+                .map(|secs| format!("  {}", LocalClock.to_wall(*secs))),
+        );
+    }
+
+    lines
+}
+
+/// e.g. "Weekday 1, Hour 9, Minute 0"
+fn describe_calendar(spec: &CalendarSpec) -> String {
+    let fields = [
+        ("Month", spec.month),
+        ("Day", spec.day),
+        ("Weekday", spec.weekday),
+        ("Hour", spec.hour),
+        ("Minute", spec.minute),
+    ];
+
+    let set: Vec<String> = fields
+        .iter()
+        .filter_map(|(name, value)| value.map(|v| format!("{} {}", name, v)))
+        .collect();
+
+    if set.is_empty() {
+        "every minute".to_string()
+    } else {
+        set.join(", ")
+    }
+}
+
+pub fn show_lint(label: String, diagnostics: Vec<Diagnostic>) -> CbSinkMessage {
+    let text = if diagnostics.is_empty() {
+        "No problems found".to_string()
+    } else {
+        diagnostics
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    Box::new(move |siv| {
+        siv.add_layer(
+            Dialog::new()
+                .title(format!("Lint: {}", label))
+                .content(TextView::new(text))
+                .dismiss_button("OK")
+                .padding(Margins::trbl(4, 4, 4, 4)),
+        )
+    })
+}
+
+/// Integrity report, by kind of issue
+pub fn show_integrity(issues: Vec<Issue>) -> CbSinkMessage {
+    let sections = [
+        ("duplicate-label", "Labels in more than one plist"),
+        ("orphan", "Loaded without a plist"),
+        ("never-loaded", "RunAtLoad, but not loaded"),
+        ("name-mismatch", "File name isn't the Label"),
+    ];
+
+    let mut lines = vec![];
+
+    for (kind, title) in sections.iter() {
+        let section: Vec<String> = issues
+            .iter()
+            .filter(|i| i.kind() == *kind)
+            .map(|i| format!("  {}", i))
+            .collect();
+
+        if section.is_empty() {
+            continue;
+        }
+
+        if !lines.is_empty() {
+            lines.push("".to_string());
+        }
+
+        lines.push(format!("{} ({}):", title, section.len()));
+        lines.extend(section);
+    }
+
+    if lines.is_empty() {
+        lines.push("No problems found".to_string());
+    }
+
+    if is_offline() {
+        lines.push("".to_string());
+        lines.push("Offline, so nothing was checked against launchd".to_string());
+    }
+
+    Box::new(move |siv| {
+        siv.add_layer(
+            Dialog::new()
+                .title("Integrity")
+                .content(TextView::new(lines.join("\n")).scrollable())
+                .dismiss_button("OK")
+                .padding(Margins::trbl(4, 4, 4, 4)),
+        )
+    })
+}
+
+/// :new, pick a template then fill in the rest of the job
+pub fn new_job_wizard(tx: Sender<OmniboxEvent>) -> CbSinkMessage {
+    let cl = move |siv: &mut Cursive| {
+        let select = SelectView::new()
+            .with_all(TEMPLATES.iter().map(|t| (t.to_string(), *t)))
+            .on_submit(move |s, template: &Template| {
+                s.pop_layer();
+                s.add_layer(new_job_form(tx.clone(), *template));
+            });
+
+        let ask = Dialog::new()
+            .title("New job")
+            .content(select)
+            .dismiss_button("Cancel")
+            .padding(Margins::trbl(4, 4, 4, 4));
+
+        siv.add_layer(ask);
+    };
+
+    Box::new(cl)
+}
+
+fn new_job_form(tx: Sender<OmniboxEvent>, template: Template) -> Dialog {
+    let Schedule {
+        run_at_load,
+        keep_alive,
+        start_interval,
+        start_calendar_interval: _,
+        watch_paths,
+    } = template.schedule();
+
+    let mut scope_group: RadioGroup<Scope> = RadioGroup::new();
+    let mut scopes = LinearLayout::vertical();
+    for scope in SCOPES.iter() {
+        scopes = scopes.child(scope_group.button(*scope, scope.to_string()));
+    }
+
+    let form = ListView::new()
+        .child("Label", EditView::new().with_name("new_label"))
+        .child("Scope", scopes)
+        .child(
+            "ProgramArguments",
+            TextArea::new().with_name("new_arguments").min_height(3),
+        )
+        .child(
+            "RunAtLoad",
+            Checkbox::new()
+                .with_checked(run_at_load)
+                .with_name("new_run_at_load"),
+        )
+        .child(
+            "KeepAlive",
+            Checkbox::new()
+                .with_checked(keep_alive)
+                .with_name("new_keep_alive"),
+        )
+        .child(
+            "StartInterval",
+            EditView::new()
+                .content(start_interval.map(|i| i.to_string()).unwrap_or_default())
+                .with_name("new_start_interval"),
+        )
+        .child(
+            "StartCalendarInterval",
+            TextArea::new().with_name("new_calendar").min_height(2),
+        )
+        .child(
+            "WatchPaths",
+            TextArea::new()
+                .content(watch_paths.join("\n"))
+                .with_name("new_watch_paths")
+                .min_height(2),
+        );
+
+    let layout = LinearLayout::vertical()
+        .child(form.fixed_width(72))
+        .child(DummyView)
+        .child(TextView::new(
+            "One per line for ProgramArguments, StartCalendarInterval and WatchPaths.\n\
+             StartCalendarInterval is crontab style: minute hour day month weekday",
+        ))
+        .child(DummyView)
+        .child(
+            TextView::new("")
+                .style(Effect::Bold)
+                .with_name("new_errors"),
+        );
+
+    Dialog::new()
+        .title(format!("New job: {}", template))
+        .content(layout)
+        .button("Create", move |s| {
+            let scope = *scope_group.selection();
+
+            match read_new_job(s, scope).and_then(|job| new_job_command(&job)) {
+                Ok(cmd) => {
+                    tx.send(OmniboxEvent::Command(cmd))
+                        .expect("Must send commands");
+                    s.pop_layer();
+                }
+                Err(e) => {
+                    s.call_on_name("new_errors", |v: &mut TextView| v.set_content(e));
+                }
+            }
+        })
+        .dismiss_button("Cancel")
+        .padding(Margins::trbl(2, 2, 2, 2))
+}
+
+fn read_new_job(s: &mut Cursive, scope: Scope) -> Result<NewJob, String> {
+    let edit_view = |s: &mut Cursive, name: &str| {
+        s.call_on_name(name, |v: &mut EditView| v.get_content().trim().to_string())
+            .unwrap_or_default()
+    };
+    let lines = |s: &mut Cursive, name: &str| {
+        s.call_on_name(name, |v: &mut TextArea| {
+            v.get_content()
+                .lines()
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty())
+                .collect::<Vec<String>>()
+        })
+        .unwrap_or_default()
+    };
+    let checked = |s: &mut Cursive, name: &str| {
+        s.call_on_name(name, |v: &mut Checkbox| v.is_checked())
+            .unwrap_or_default()
+    };
+
+    let start_interval = match edit_view(s, "new_start_interval") {
+        i if i.is_empty() => None,
+        i => Some(
+            i.parse()
+                .map_err(|_| format!("StartInterval: {} is not a number", i))?,
+        ),
+    };
+
+    let start_calendar_interval = lines(s, "new_calendar")
+        .iter()
+        .map(|l| parse_calendar(l).map_err(|e| format!("StartCalendarInterval: {}", e)))
+        .collect::<Result<_, _>>()?;
+
+    Ok(NewJob {
+        label: edit_view(s, "new_label"),
+        scope,
+        program_arguments: lines(s, "new_arguments"),
+        schedule: Schedule {
+            run_at_load: checked(s, "new_run_at_load"),
+            keep_alive: checked(s, "new_keep_alive"),
+            start_interval,
+            start_calendar_interval,
+            watch_paths: lines(s, "new_watch_paths"),
+        },
+    })
+}
+
+/// Generate the plist, or why we can't
+fn new_job_command(job: &NewJob) -> Result<OmniboxCommand, String> {
+    let home = env::var("HOME").expect("Must read $HOME");
+
+    let (path, launchd_job) = job.generate(Path::new(&home)).map_err(|errors| {
+        errors
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    })?;
+
+    let mut contents = vec![];
+    plist::Value::Dictionary(launchd_job.to_dictionary())
+        .to_writer_xml(&mut contents)
+        .map_err(|e| e.to_string())?;
+
+    Ok(OmniboxCommand::CreateJob(
+        job.label.clone(),
+        path.to_string_lossy().to_string(),
+        contents,
+        job.scope,
+    ))
+}
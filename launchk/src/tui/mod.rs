@@ -1,5 +1,6 @@
 mod dialog;
 mod omnibox;
+#[cfg(target_os = "macos")]
 mod pager;
 pub mod root;
 mod service_list;
@@ -1,11 +1,20 @@
+use launchd_plist::template::Scope;
 use std::fmt;
+use std::path::PathBuf;
 use xpc_sys::enums::{DomainType, SessionType};
 
+use crate::launchd::edit_format::EditFormat;
+use crate::launchd::plist::EditSession;
+
+// DomainSessionPrompt's fn is only compared to tell commands apart
+#[allow(unknown_lints, unpredictable_function_pointer_comparisons)]
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum OmniboxCommand {
     Chain(Vec<OmniboxCommand>),
     // (message, on ok)
     Confirm(String, Vec<OmniboxCommand>),
+    // (message, [(button, on press)]), no dismiss, must pick one
+    Choice(String, Vec<(String, Vec<OmniboxCommand>)>),
     // Try to see if we have session type & domain in entry_status,
     // to avoid having to prompt the user
     LoadRequest,
@@ -19,6 +28,31 @@ pub enum OmniboxCommand {
     Enable(DomainType),
     Disable(DomainType),
     Edit,
+    EditAs(EditFormat),
+    // Write a reviewed edit, open it again, or throw it away
+    EditWrite(EditSession),
+    EditReopen(EditSession),
+    EditAbort(EditSession),
+    Lint,
+    Info,
+    Integrity,
+    History,
+    // (unit label, plist path)
+    HistoryPrompt(String, String),
+    // Version to put back, for the job the prompt was opened for
+    HistoryRestore {
+        label: String,
+        plist_path: String,
+        version: PathBuf,
+    },
+    // Reload by label, e.g. after a dialog, when the
+    // highlighted row may not be the job anymore
+    ReloadLabel(String),
+    New,
+    // (label, plist path, XML, scope) from the :new wizard
+    CreateJob(String, String, Vec<u8>, Scope),
+    // (label, plist path, scope)
+    LoadJob(String, String, Scope),
     // (unit label, prompt for domain only?, action gen fn)
     DomainSessionPrompt(
         String,
@@ -35,13 +69,61 @@ pub enum OmniboxCommand {
     Quit,
 }
 
+impl OmniboxCommand {
+    /// Restore version of label's plist, then reload it if that worked
+    pub fn restore(
+        label: &str,
+        plist_path: &str,
+        version: PathBuf,
+        and_reload: bool,
+    ) -> OmniboxCommand {
+        let mut commands = vec![OmniboxCommand::HistoryRestore {
+            label: label.to_string(),
+            plist_path: plist_path.to_string(),
+            version,
+        }];
+
+        if and_reload {
+            commands.push(OmniboxCommand::ReloadLabel(label.to_string()));
+        }
+
+        OmniboxCommand::Chain(commands)
+    }
+
+    /// Run a command, or a chain's commands in order (nested chains flattened),
+    /// stopping at the first one run says failed. False if one did
+    pub fn run_chain(self, run: &mut impl FnMut(OmniboxCommand) -> bool) -> bool {
+        match self {
+            OmniboxCommand::Chain(cmds) => cmds.into_iter().all(|c| c.run_chain(run)),
+            cmd => run(cmd),
+        }
+    }
+
+    /// Doesn't need launchd or change anything, so works with --root
+    pub fn available_offline(&self) -> bool {
+        match self {
+            OmniboxCommand::Chain(cmds) => cmds.iter().all(|c| c.available_offline()),
+            OmniboxCommand::Confirm(_, _)
+            | OmniboxCommand::Choice(_, _)
+            | OmniboxCommand::EditAbort(_)
+            | OmniboxCommand::Lint
+            | OmniboxCommand::Info
+            | OmniboxCommand::Integrity
+            | OmniboxCommand::FocusServiceList
+            | OmniboxCommand::Help
+            | OmniboxCommand::Quit => true,
+            _ => false,
+        }
+    }
+}
+
 impl fmt::Display for OmniboxCommand {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", format!("{:?}", self).to_ascii_lowercase())
     }
 }
 
-pub static OMNIBOX_COMMANDS: [(&str, &str, OmniboxCommand); 12] = [
+pub static OMNIBOX_COMMANDS: [(&str, &str, OmniboxCommand); 21] = [
     (
         "load",
         "▶️  Load highlighted job",
@@ -67,6 +149,51 @@ pub static OMNIBOX_COMMANDS: [(&str, &str, OmniboxCommand); 12] = [
         "✍️  Edit plist with $EDITOR then reload job",
         OmniboxCommand::Edit,
     ),
+    (
+        "edit xml",
+        "✍️  Edit plist as XML",
+        OmniboxCommand::EditAs(EditFormat::Xml),
+    ),
+    (
+        "edit json",
+        "✍️  Edit plist as JSON",
+        OmniboxCommand::EditAs(EditFormat::Json),
+    ),
+    (
+        "edit yaml",
+        "✍️  Edit plist as YAML",
+        OmniboxCommand::EditAs(EditFormat::Yaml),
+    ),
+    (
+        "edit toml",
+        "✍️  Edit plist as TOML",
+        OmniboxCommand::EditAs(EditFormat::Toml),
+    ),
+    (
+        "lint",
+        "🔍  Check highlighted job's plist for mistakes",
+        OmniboxCommand::Lint,
+    ),
+    (
+        "info",
+        "ℹ️  Show highlighted job's details, schedule and next runs",
+        OmniboxCommand::Info,
+    ),
+    (
+        "integrity",
+        "🧩  Show duplicate labels, orphans and jobs that didn't load",
+        OmniboxCommand::Integrity,
+    ),
+    (
+        "history",
+        "🕘  Diff or restore earlier versions of highlighted job's plist",
+        OmniboxCommand::History,
+    ),
+    (
+        "new",
+        "✨  Create a job from a template",
+        OmniboxCommand::New,
+    ),
     (
         "reload",
         "🔄  Reload highlighted job",
@@ -91,3 +218,69 @@ pub static OMNIBOX_COMMANDS: [(&str, &str, OmniboxCommand); 12] = [
     ("help", "🤔  Show all commands", OmniboxCommand::Help),
     ("exit", "🚪  see ya!", OmniboxCommand::Quit),
 ];
+
+#[cfg(test)]
+mod tests {
+    use crate::tui::omnibox::command::OmniboxCommand;
+
+    fn run(cmd: OmniboxCommand, failing: &OmniboxCommand) -> (bool, Vec<OmniboxCommand>) {
+        let mut ran = vec![];
+        let ok = cmd.run_chain(&mut |c| {
+            ran.push(c.clone());
+            &c != failing
+        });
+        (ok, ran)
+    }
+
+    #[test]
+    fn chain_runs_in_order() {
+        let chain = OmniboxCommand::Chain(vec![
+            OmniboxCommand::Info,
+            OmniboxCommand::Chain(vec![OmniboxCommand::Lint, OmniboxCommand::Edit]),
+            OmniboxCommand::Reload,
+        ]);
+
+        assert_eq!(
+            run(chain, &OmniboxCommand::Quit),
+            (
+                true,
+                vec![
+                    OmniboxCommand::Info,
+                    OmniboxCommand::Lint,
+                    OmniboxCommand::Edit,
+                    OmniboxCommand::Reload
+                ]
+            )
+        );
+        assert_eq!(
+            run(OmniboxCommand::Help, &OmniboxCommand::Quit),
+            (true, vec![OmniboxCommand::Help])
+        );
+    }
+
+    #[test]
+    fn chain_stops_at_failure() {
+        let chain = OmniboxCommand::Chain(vec![
+            OmniboxCommand::Info,
+            OmniboxCommand::Chain(vec![OmniboxCommand::Lint, OmniboxCommand::Edit]),
+            OmniboxCommand::Reload,
+        ]);
+
+        assert_eq!(
+            run(chain, &OmniboxCommand::Lint),
+            (false, vec![OmniboxCommand::Info, OmniboxCommand::Lint])
+        );
+    }
+
+    #[test]
+    fn no_reload_after_failed_restore() {
+        let chain = OmniboxCommand::restore("com.example.a", "/a.plist", "/v".into(), true);
+        let restore = OmniboxCommand::HistoryRestore {
+            label: "com.example.a".to_string(),
+            plist_path: "/a.plist".to_string(),
+            version: "/v".into(),
+        };
+
+        assert_eq!(run(chain, &restore), (false, vec![restore]));
+    }
+}
@@ -27,7 +27,7 @@ impl OmniboxState {
             mode: mode.unwrap_or(self.mode.clone()),
             label_filter: label_filter.unwrap_or(self.label_filter.clone()),
             command_filter: command_filter.unwrap_or(self.command_filter.clone()),
-            job_type_filter: job_type_filter.unwrap_or(self.job_type_filter.clone()),
+            job_type_filter: job_type_filter.unwrap_or(self.job_type_filter),
         }
     }
 
@@ -45,9 +45,8 @@ impl OmniboxState {
 
         OMNIBOX_COMMANDS
             .iter()
-            .filter(|(c, _, _)| c.to_string().starts_with(command_filter))
-            .next()
-            .map(|s| s.clone())
+            .find(|(c, _, _)| c.to_string().starts_with(command_filter))
+            .cloned()
     }
 }
 
@@ -28,7 +28,6 @@ pub enum OmniboxEvent {
 pub enum OmniboxError {
     ReferenceError,
     CommandError(String),
-    Many(Vec<OmniboxError>),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -71,13 +70,12 @@ async fn tick(state: Arc<RwLock<OmniboxState>>, tx: Sender<OmniboxEvent>) {
         drop(read);
         let mut write = state.write().expect("Must write");
 
-        [
+        for e in [
             OmniboxEvent::Command(OmniboxCommand::FocusServiceList),
             OmniboxEvent::StateUpdate(new.clone()),
-        ]
-        .iter()
-        .try_for_each(|e| tx.send(e.clone()))
-        .expect("Must send events");
+        ] {
+            tx.send(e).expect("Must send events");
+        }
 
         log::debug!("[omnibox/tick]: New state: {:?}", &new);
 
@@ -195,7 +193,7 @@ impl OmniboxView {
 
     /// Toggle bitmask on key
     fn handle_job_type_filter(event: &Event, state: &OmniboxState) -> Option<OmniboxState> {
-        let mut jtf = state.job_type_filter.clone();
+        let mut jtf = state.job_type_filter;
 
         match event {
             Event::Char('s') => jtf.toggle(JobTypeFilter::SYSTEM),
@@ -204,6 +202,8 @@ impl OmniboxView {
             Event::Char('a') => jtf.toggle(JobTypeFilter::AGENT),
             Event::Char('d') => jtf.toggle(JobTypeFilter::DAEMON),
             Event::Char('l') => jtf.toggle(JobTypeFilter::LOADED),
+            Event::Char('r') => jtf.toggle(JobTypeFilter::RISKY),
+            Event::Char('i') => jtf.toggle(JobTypeFilter::ISSUES),
             _ => return None,
         };
 
@@ -223,7 +223,7 @@ impl OmniboxView {
             OmniboxMode::LabelFilter => "Filter > ",
             OmniboxMode::CommandFilter => "Command > ",
             OmniboxMode::CommandConfirm(_) => "OK! > ",
-            _ if command_filter.len() < 1 && label_filter.len() > 0 => "Filter > ",
+            _ if command_filter.is_empty() && !label_filter.is_empty() => "Filter > ",
             _ => "",
         };
 
@@ -236,7 +236,7 @@ impl OmniboxView {
             purple
         };
 
-        let visible_filter = if command_filter.len() > 0 || *mode == OmniboxMode::CommandFilter {
+        let visible_filter = if !command_filter.is_empty() || *mode == OmniboxMode::CommandFilter {
             command_filter
         } else {
             label_filter
@@ -282,9 +282,9 @@ impl OmniboxView {
         } = &*read;
 
         let mut jtf_ofs = if *mode != OmniboxMode::JobTypeFilter {
-            "[sguadl]".len()
+            "[sguadlri]".len()
         } else {
-            "[system global user agent daemon loaded]".len()
+            "[system global user agent daemon loaded risky issues]".len()
         };
 
         if jtf_ofs < self.last_size.borrow().x {
@@ -304,6 +304,8 @@ impl OmniboxView {
             JobTypeFilter::AGENT,
             JobTypeFilter::DAEMON,
             JobTypeFilter::LOADED,
+            JobTypeFilter::RISKY,
+            JobTypeFilter::ISSUES,
         ]
         .iter()
         {
@@ -313,7 +315,7 @@ impl OmniboxView {
             }
 
             // No space at end if expanded
-            if *mask == JobTypeFilter::LOADED && *mode == OmniboxMode::JobTypeFilter {
+            if *mask == JobTypeFilter::ISSUES && *mode == OmniboxMode::JobTypeFilter {
                 mask_string.truncate(mask_string.len() - 1);
             }
 
@@ -369,8 +371,8 @@ impl View for OmniboxView {
                 Some("".to_string()),
                 None,
             )),
-            (e, OmniboxMode::Idle) => Self::handle_job_type_filter(&e, &*state),
-            (e, _) => Self::handle_active(&e, &*state),
+            (e, OmniboxMode::Idle) => Self::handle_job_type_filter(&e, &state),
+            (e, _) => Self::handle_active(&e, &state),
         };
 
         if new_state.is_none() {
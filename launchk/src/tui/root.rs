@@ -1,7 +1,10 @@
 use std::collections::VecDeque;
+use std::path::PathBuf;
 
+#[cfg(target_os = "macos")]
 use std::ptr::slice_from_raw_parts;
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
 
 use cursive::event::{Event, EventResult, Key};
 use cursive::traits::{Resizable, Scrollable};
@@ -9,20 +12,31 @@ use cursive::view::ViewWrapper;
 use cursive::views::{LinearLayout, NamedView, Panel};
 use cursive::{Cursive, Vec2, View};
 
+use launchd_plist::lint::{lint_file, Severity};
+use launchd_plist::template::Scope;
 use tokio::runtime::Handle;
+use xpc_sys::enums::{DomainType, SessionType};
+use xpc_sys::traits::xpc_transport::XPCTransport;
+
+use crate::launchd::plist::{insert_plist, integrity_issues, PLIST_FS};
+use crate::launchd::privileged::create_plist;
+use crate::launchd::query::{domain_target, list_all, load};
+use crate::offline::is_offline;
 
 use crate::tui::omnibox::command::OmniboxCommand;
 use crate::tui::omnibox::subscribed_view::{
     OmniboxResult, OmniboxSubscribedView, OmniboxSubscriber, Subscribable,
 };
 use crate::tui::omnibox::view::{OmniboxError, OmniboxEvent, OmniboxView};
+#[cfg(target_os = "macos")]
 use crate::tui::pager::show_pager;
-use crate::tui::service_list::view::ServiceListView;
+use crate::tui::service_list::view::{offer_sudo, ServiceListView};
+use crate::{launchd::history::history, tui::dialog, tui::dialog::show_help};
+#[cfg(target_os = "macos")]
 use crate::{
-    launchd::query::dumpjpcategory,
-    tui::dialog::{show_csr_info, show_help},
+    launchd::query::{dumpjpcategory, dumpstate},
+    tui::dialog::show_csr_info,
 };
-use crate::{launchd::query::dumpstate, tui::dialog};
 use std::thread;
 
 pub type CbSinkMessage = Box<dyn FnOnce(&mut Cursive) + Send>;
@@ -33,6 +47,7 @@ pub struct RootLayout {
     runtime_handle: Handle,
     cbsink_channel: Sender<CbSinkMessage>,
     key_ring: VecDeque<Event>,
+    transport: Arc<dyn XPCTransport>,
 }
 
 #[derive(Debug)]
@@ -60,7 +75,11 @@ async fn poll_omnibox(cb_sink: Sender<CbSinkMessage>, rx: Receiver<OmniboxEvent>
 }
 
 impl RootLayout {
-    pub fn new(siv: &mut Cursive, runtime_handle: &Handle) -> Self {
+    pub fn new(
+        siv: &mut Cursive,
+        runtime_handle: &Handle,
+        transport: Arc<dyn XPCTransport>,
+    ) -> Self {
         let (omnibox, omnibox_tx, omnibox_rx) = OmniboxView::new(runtime_handle);
         let cbsink_channel = RootLayout::cbsink_channel(siv);
 
@@ -72,6 +91,7 @@ impl RootLayout {
             layout: LinearLayout::vertical(),
             runtime_handle: runtime_handle.clone(),
             key_ring: VecDeque::with_capacity(3),
+            transport,
         };
 
         new.setup(omnibox);
@@ -85,11 +105,15 @@ impl RootLayout {
             .full_width()
             .max_height(3);
 
-        let service_list = ServiceListView::new(&self.runtime_handle, self.cbsink_channel.clone())
-            .full_width()
-            .full_height()
-            .scrollable()
-            .subscribable();
+        let service_list = ServiceListView::new(
+            &self.runtime_handle,
+            self.cbsink_channel.clone(),
+            self.transport.clone(),
+        )
+        .full_width()
+        .full_height()
+        .scrollable()
+        .subscribable();
 
         self.with_view_mut(|v| {
             v.add_child(sysinfo);
@@ -125,6 +149,27 @@ impl RootLayout {
     }
 
     fn handle_omnibox_event(&mut self, recv: OmniboxEvent) {
+        if let OmniboxEvent::Command(cmd) = &recv {
+            if is_offline() && !cmd.available_offline() {
+                self.cbsink_channel
+                    .send(dialog::show_error(
+                        "Not available offline, only lint, info and help are".to_string(),
+                    ))
+                    .expect("Must show error");
+                return;
+            }
+        }
+
+        self.dispatch(recv);
+    }
+
+    /// Send to root and ServiceList, a chain one command at a time.
+    /// False (and the rest of the chain skipped) if one failed
+    fn dispatch(&mut self, recv: OmniboxEvent) -> bool {
+        if let OmniboxEvent::Command(cmd @ OmniboxCommand::Chain(_)) = recv {
+            return cmd.run_chain(&mut |c| self.dispatch(OmniboxEvent::Command(c)));
+        }
+
         let self_event = self.on_omnibox(recv.clone());
 
         let target = self
@@ -149,6 +194,8 @@ impl RootLayout {
                 _ => {}
             }
         }
+
+        omnibox_events.iter().all(|e| e.is_ok())
     }
 
     fn ring_to_arrows(&mut self) -> Option<Event> {
@@ -189,7 +236,7 @@ impl ViewWrapper for RootLayout {
     fn wrap_on_event(&mut self, event: Event) -> EventResult {
         log::trace!("on_event: {:?}", event);
 
-        let ev = match event {
+        match event {
             Event::Char('/')
             | Event::Char(':')
             | Event::CtrlChar('u')
@@ -199,6 +246,8 @@ impl ViewWrapper for RootLayout {
             | Event::Char('a')
             | Event::Char('d')
             | Event::Char('l')
+            | Event::Char('r')
+            | Event::Char('i')
             | Event::Key(Key::Backspace) => {
                 self.focus_and_forward(RootLayoutChildren::Omnibox, event)
             }
@@ -216,9 +265,7 @@ impl ViewWrapper for RootLayout {
                 self.layout.on_event(event)
             }
             _ => self.layout.on_event(event),
-        };
-
-        ev
+        }
     }
 
     fn wrap_layout(&mut self, size: Vec2) {
@@ -229,18 +276,6 @@ impl ViewWrapper for RootLayout {
 impl OmniboxSubscriber for RootLayout {
     fn on_omnibox(&mut self, cmd: OmniboxEvent) -> OmniboxResult {
         match cmd {
-            OmniboxEvent::Command(OmniboxCommand::Chain(cmds)) => {
-                let errors: Vec<OmniboxError> = cmds
-                    .iter()
-                    .filter_map(|c| self.on_omnibox(OmniboxEvent::Command(c.clone())).err())
-                    .collect();
-
-                if errors.is_empty() {
-                    Ok(None)
-                } else {
-                    Err(OmniboxError::Many(errors))
-                }
-            }
             OmniboxEvent::Command(OmniboxCommand::Quit) => {
                 self.cbsink_channel
                     .send(Box::new(|s| {
@@ -269,9 +304,88 @@ impl OmniboxSubscriber for RootLayout {
                     .expect("Must show prompt");
                 Ok(None)
             }
+            OmniboxEvent::Command(OmniboxCommand::Choice(p, c)) => {
+                self.cbsink_channel
+                    .send(dialog::show_choice(self.omnibox_tx.clone(), p, c))
+                    .expect("Must show prompt");
+                Ok(None)
+            }
+            OmniboxEvent::Command(OmniboxCommand::HistoryPrompt(label, plist_path)) => {
+                let versions = history()
+                    .map_err(OmniboxError::CommandError)?
+                    .versions(&label);
+
+                if versions.is_empty() {
+                    return Err(OmniboxError::CommandError(format!(
+                        "No history for {}",
+                        label
+                    )));
+                }
+
+                self.cbsink_channel
+                    .send(dialog::history_prompt(
+                        self.omnibox_tx.clone(),
+                        label,
+                        plist_path,
+                        versions,
+                    ))
+                    .expect("Must show prompt");
+                Ok(None)
+            }
+            OmniboxEvent::Command(OmniboxCommand::New) => {
+                self.cbsink_channel
+                    .send(dialog::new_job_wizard(self.omnibox_tx.clone()))
+                    .expect("Must show prompt");
+                Ok(None)
+            }
+            OmniboxEvent::Command(OmniboxCommand::CreateJob(
+                label,
+                plist_path,
+                contents,
+                scope,
+            )) => {
+                let created = create_plist(&plist_path, &contents);
+
+                // sudo may have prompted
+                self.cbsink_channel
+                    .send(Box::new(Cursive::clear))
+                    .expect("Must clear");
+
+                created.map_err(OmniboxError::CommandError)?;
+                insert_plist(PathBuf::from(&plist_path));
+
+                let warnings: Vec<String> = lint_file(&*PLIST_FS, &plist_path)
+                    .into_iter()
+                    .filter(|d| d.severity == Severity::Warning)
+                    .map(|d| d.to_string())
+                    .collect();
+
+                let mut prompt = format!("Created {}", plist_path);
+                if !warnings.is_empty() {
+                    prompt = format!("{}\n\n{}", prompt, warnings.join("\n"));
+                }
+
+                Ok(Some(OmniboxCommand::Confirm(
+                    format!("{}\n\nLoad {}?", prompt, label),
+                    vec![OmniboxCommand::LoadJob(label, plist_path, scope)],
+                )))
+            }
+            OmniboxEvent::Command(OmniboxCommand::LoadJob(label, plist_path, scope)) => {
+                let (domain_type, session_type) = domain_session(scope);
+
+                load(
+                    &*self.transport,
+                    label,
+                    plist_path,
+                    &domain_target(domain_type, None),
+                    Some(session_type),
+                )
+                .map_or_else(offer_sudo, |_| Ok(None))
+            }
             OmniboxEvent::Command(OmniboxCommand::DomainSessionPrompt(label, domain_only, f)) => {
                 self.cbsink_channel
                     .send(dialog::domain_session_prompt(
+                        &*self.transport,
                         label,
                         domain_only,
                         self.omnibox_tx.clone(),
@@ -280,6 +394,7 @@ impl OmniboxSubscriber for RootLayout {
                     .expect("Must show prompt");
                 Ok(None)
             }
+            #[cfg(target_os = "macos")]
             OmniboxEvent::Command(OmniboxCommand::CSRInfo) => {
                 self.cbsink_channel
                     .send(show_csr_info())
@@ -287,9 +402,10 @@ impl OmniboxSubscriber for RootLayout {
 
                 Ok(None)
             }
+            #[cfg(target_os = "macos")]
             OmniboxEvent::Command(OmniboxCommand::DumpState) => {
-                let (size, shmem) =
-                    dumpstate().map_err(|e| OmniboxError::CommandError(e.to_string()))?;
+                let (size, shmem) = dumpstate(&*self.transport)
+                    .map_err(|e| OmniboxError::CommandError(e.to_string()))?;
 
                 log::info!("shmem response sz {}", size);
 
@@ -300,9 +416,10 @@ impl OmniboxSubscriber for RootLayout {
 
                 Ok(None)
             }
+            #[cfg(target_os = "macos")]
             OmniboxEvent::Command(OmniboxCommand::DumpJetsamPropertiesCategory) => {
-                let (size, shmem) =
-                    dumpjpcategory().map_err(|e| OmniboxError::CommandError(e.to_string()))?;
+                let (size, shmem) = dumpjpcategory(&*self.transport)
+                    .map_err(|e| OmniboxError::CommandError(e.to_string()))?;
 
                 show_pager(&self.cbsink_channel, unsafe {
                     &*slice_from_raw_parts(shmem.region as *mut u8, size)
@@ -311,6 +428,19 @@ impl OmniboxSubscriber for RootLayout {
 
                 Ok(None)
             }
+            OmniboxEvent::Command(OmniboxCommand::Integrity) => {
+                let loaded = if is_offline() {
+                    None
+                } else {
+                    Some(list_all(&*self.transport))
+                };
+
+                self.cbsink_channel
+                    .send(dialog::show_integrity(integrity_issues(loaded.as_ref())))
+                    .expect("Must show integrity");
+
+                Ok(None)
+            }
             OmniboxEvent::Command(OmniboxCommand::Help) => {
                 self.cbsink_channel
                     .send(show_help())
@@ -322,3 +452,11 @@ impl OmniboxSubscriber for RootLayout {
         }
     }
 }
+
+/// Agents load in the requestor's (i.e. the logged in user's) domain
+fn domain_session(scope: Scope) -> (DomainType, SessionType) {
+    match scope {
+        Scope::UserAgent | Scope::GlobalAgent => (DomainType::RequestorDomain, SessionType::Aqua),
+        Scope::GlobalDaemon => (DomainType::System, SessionType::System),
+    }
+}
@@ -1,7 +1,13 @@
 use std::borrow::Borrow;
 
+use launchd_plist::audit::max_severity;
+use launchd_plist::next_run::{Clock, LocalClock};
+use launchd_plist::overrides::is_disabled;
+
+use crate::config::CONFIG;
 use crate::launchd::entry_status::LaunchdEntryStatus;
 use crate::launchd::job_type_filter::JobTypeFilter;
+use crate::offline::{is_offline, OVERRIDES};
 use crate::tui::table::table_list_view::TableListItem;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -11,9 +17,57 @@ pub struct ServiceListItem {
     pub job_type_filter: JobTypeFilter,
 }
 
+impl ServiceListItem {
+    /// When launchd next starts a loaded job on its calendar, or its interval
+    fn next_run(&self) -> String {
+        let schedule = self.status.plist.as_ref().and_then(|p| p.schedule.as_ref());
+
+        match schedule {
+            Some(schedule) if self.job_type_filter.intersects(JobTypeFilter::LOADED) => schedule
+                .next_runs(&LocalClock, None, 1)
+                .first()
+                .map(|secs| LocalClock.to_wall(*secs).to_string())
+                .or_else(|| schedule.start_interval.map(|i| format!("every {}s", i)))
+                .unwrap_or("-".to_string()),
+            _ => "-".to_string(),
+        }
+    }
+
+    /// What the volume's disabled databases say, offline
+    fn disabled(&self) -> String {
+        let disabled = self
+            .status
+            .plist
+            .as_ref()
+            .and_then(|p| is_disabled(&OVERRIDES, &self.name, &p.entry_type));
+
+        match disabled {
+            Some(true) => "✔",
+            Some(false) => "✘",
+            None => "-",
+        }
+        .to_string()
+    }
+
+    /// Worst thing the persistence audit found
+    fn severity(&self) -> String {
+        self.status
+            .plist
+            .as_ref()
+            .and_then(|p| max_severity(&p.findings))
+            .map(|s| s.to_string())
+            .unwrap_or("-".to_string())
+    }
+}
+
 impl TableListItem for ServiceListItem {
     fn as_row(&self) -> Vec<String> {
-        let session_type = self.status.limit_load_to_session_type.to_string();
+        // Live columns are blank offline
+        let session_type = if is_offline() {
+            "-".to_string()
+        } else {
+            self.status.limit_load_to_session_type.to_string()
+        };
 
         let entry_type = self
             .status
@@ -29,18 +83,33 @@ impl TableListItem for ServiceListItem {
             "-".to_string()
         };
 
-        let loaded = if self.job_type_filter.intersects(JobTypeFilter::LOADED) {
+        let loaded = if is_offline() {
+            "-"
+        } else if !self.status.drift.is_empty() {
+            "stale"
+        } else if self.job_type_filter.intersects(JobTypeFilter::LOADED) {
             "✔"
         } else {
             "✘"
         };
 
-        vec![
+        let mut row = vec![
             self.name.clone(),
             session_type,
             entry_type,
             pid,
             loaded.to_string(),
-        ]
+            self.severity(),
+        ];
+
+        if CONFIG.next_run_column {
+            row.push(self.next_run());
+        }
+
+        if is_offline() {
+            row.push(self.disabled());
+        }
+
+        row
     }
 }
@@ -1,10 +1,12 @@
 use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::collections::HashSet;
+use std::path::Path;
+#[cfg(target_os = "macos")]
 use std::ptr::slice_from_raw_parts;
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
-use std::sync::{Arc, RwLock};
+use std::sync::{atomic, Arc, RwLock};
 use std::time::Duration;
 
 use cursive::direction::Direction;
@@ -14,29 +16,59 @@ use cursive::view::ViewWrapper;
 use cursive::{Cursive, View, XY};
 use sudo::RunningAs;
 
+use launchd_plist::lint::lint_file;
 use tokio::runtime::Handle;
 use tokio::time::interval;
 use xpc_sys::enums::{DomainType, SessionType};
+use xpc_sys::objects::launchd_error::ErrorCategory;
+use xpc_sys::objects::xpc_error::XPCError;
+use xpc_sys::traits::xpc_transport::XPCTransport;
 
+use crate::config::CONFIG;
+use crate::launchd::history::{history, History};
 use crate::launchd::job_type_filter::JobTypeFilter;
-use crate::launchd::plist::{edit_and_replace, LaunchdEntryLocation, LABEL_TO_ENTRY_CONFIG};
+use crate::launchd::plist::{
+    begin_edit, integrity_issues, EditError, EditSession, LaunchdEntryLocation,
+    LABEL_TO_ENTRY_CONFIG, PLIST_FS, PLIST_GENERATION,
+};
+#[cfg(target_os = "macos")]
 use crate::launchd::query::procinfo;
-use crate::launchd::query::{disable, enable, list_all, load, unload};
+use crate::launchd::query::{disable, domain_target, enable, list_all, load, unload};
 use crate::launchd::{
     entry_status::get_entry_status, entry_status::LaunchdEntryStatus, plist::LaunchdPlist,
 };
+use crate::offline::is_offline;
+use crate::tui::dialog::{show_info, show_lint};
 use crate::tui::omnibox::command::OmniboxCommand;
 
 use crate::tui::omnibox::state::OmniboxState;
 use crate::tui::omnibox::subscribed_view::{OmniboxResult, OmniboxSubscriber};
 use crate::tui::omnibox::view::{OmniboxError, OmniboxEvent, OmniboxMode};
+#[cfg(target_os = "macos")]
 use crate::tui::pager::show_pager;
 use crate::tui::root::CbSinkMessage;
 use crate::tui::service_list::list_item::ServiceListItem;
 use crate::tui::table::table_list_view::TableListView;
 
+/// Offer to sudo and restart if launchd said EPERM, otherwise show the error
+pub(crate) fn offer_sudo(e: XPCError) -> OmniboxResult {
+    match e.category() {
+        Some(ErrorCategory::NotPermitted) if sudo::check() != RunningAs::Root => {
+            Ok(Some(OmniboxCommand::Confirm(
+                format!("{}. Sudo and restart?", e),
+                vec![OmniboxCommand::Quit, OmniboxCommand::Sudo],
+            )))
+        }
+        _ => Err(OmniboxError::CommandError(e.to_string())),
+    }
+}
+
 /// Polls XPC for job list
-async fn poll_running_jobs(svcs: Arc<RwLock<HashSet<String>>>, cb_sink: Sender<CbSinkMessage>) {
+async fn poll_running_jobs(
+    svcs: Arc<RwLock<HashSet<String>>>,
+    cb_sink: Sender<CbSinkMessage>,
+    transport: Arc<dyn XPCTransport>,
+) {
     let mut interval = interval(Duration::from_secs(1));
 
     loop {
@@ -48,43 +80,97 @@ async fn poll_running_jobs(svcs: Arc<RwLock<HashSet<String>>>, cb_sink: Sender<C
         }
 
         let mut write = write.unwrap();
-        *write = list_all();
+        *write = list_all(&*transport);
 
         cb_sink.send(Box::new(Cursive::noop)).expect("Must send");
     }
 }
 
+/// Labels with integrity_issues, and what they were worked out from
+#[derive(Default)]
+struct IssueCache {
+    generation: Option<usize>,
+    loaded: HashSet<String>,
+    labels: HashSet<String>,
+}
+
 pub struct ServiceListView {
     cb_sink: Sender<CbSinkMessage>,
+    transport: Arc<dyn XPCTransport>,
     running_jobs: Arc<RwLock<HashSet<String>>>,
     table_list_view: TableListView<ServiceListItem>,
     label_filter: RefCell<String>,
     job_type_filter: RefCell<JobTypeFilter>,
+    issue_cache: RefCell<IssueCache>,
+    history: Result<&'static History, String>,
 }
 
 impl ServiceListView {
-    pub fn new(runtime_handle: &Handle, cb_sink: Sender<CbSinkMessage>) -> Self {
+    pub fn new(
+        runtime_handle: &Handle,
+        cb_sink: Sender<CbSinkMessage>,
+        transport: Arc<dyn XPCTransport>,
+    ) -> Self {
         let arc_svc = Arc::new(RwLock::new(HashSet::new()));
-        runtime_handle.spawn(poll_running_jobs(arc_svc.clone(), cb_sink.clone()));
+        let mut columns = vec![
+            ("Name", None),
+            ("Session", Some(12)),
+            ("Job Type", Some(14)),
+            ("PID", Some(6)),
+            ("Loaded", Some(6)),
+            ("Severity", Some(8)),
+        ];
+
+        if CONFIG.next_run_column {
+            columns.push(("Next Run", Some(16)));
+        }
+
+        // Nothing is running on a volume, it has its launchd databases instead
+        if is_offline() {
+            columns.push(("Disabled", Some(8)));
+        } else {
+            runtime_handle.spawn(poll_running_jobs(
+                arc_svc.clone(),
+                cb_sink.clone(),
+                transport.clone(),
+            ));
+        }
 
         Self {
             cb_sink,
+            transport,
             running_jobs: arc_svc.clone(),
             label_filter: RefCell::new("".into()),
             job_type_filter: RefCell::new(JobTypeFilter::launchk_default()),
-            table_list_view: TableListView::new(vec![
-                ("Name", None),
-                ("Session", Some(12)),
-                ("Job Type", Some(14)),
-                ("PID", Some(6)),
-                ("Loaded", Some(6)),
-            ]),
+            table_list_view: TableListView::new(columns),
+            issue_cache: RefCell::new(IssueCache::default()),
+            history: history(),
         }
     }
 
+    /// Labels with integrity_issues, only worked out again after a
+    /// reconcile or when what's loaded changed
+    fn labels_with_issues(&self, running: &HashSet<String>) -> HashSet<String> {
+        let generation = PLIST_GENERATION.load(atomic::Ordering::Relaxed);
+        let mut cache = self.issue_cache.borrow_mut();
+
+        if cache.generation != Some(generation) || cache.loaded != *running {
+            cache.labels = integrity_issues(if is_offline() { None } else { Some(running) })
+                .into_iter()
+                .map(|i| i.label().to_string())
+                .collect();
+            cache.loaded = running.clone();
+            cache.generation = Some(generation);
+        }
+
+        cache.labels.clone()
+    }
+
     fn present_services(&self) -> Option<Vec<ServiceListItem>> {
-        let plists = LABEL_TO_ENTRY_CONFIG.read().ok()?;
         let running = self.running_jobs.read().ok()?;
+        // Before LABEL_TO_ENTRY_CONFIG, it reads it too
+        let with_issues = self.labels_with_issues(&running);
+        let plists = LABEL_TO_ENTRY_CONFIG.read().ok()?;
 
         let name_filter = self.label_filter.borrow();
         let job_type_filter = self.job_type_filter.borrow();
@@ -93,7 +179,6 @@ impl ServiceListView {
 
         let mut items: Vec<ServiceListItem> = plists
             .keys()
-            .into_iter()
             .chain(running_no_plist)
             .filter_map(|label| {
                 if !name_filter.is_empty()
@@ -104,10 +189,10 @@ impl ServiceListView {
                     return None;
                 }
 
-                let status = get_entry_status(label);
+                let status = get_entry_status(&*self.transport, label);
                 let is_loaded = running.contains(label);
 
-                let entry_job_type_filter = status
+                let mut entry_job_type_filter = status
                     .plist
                     .as_ref()
                     .map(|ec| ec.job_type_filter(is_loaded))
@@ -117,6 +202,10 @@ impl ServiceListView {
                         JobTypeFilter::default()
                     });
 
+                if with_issues.contains(label) {
+                    entry_job_type_filter.toggle(JobTypeFilter::ISSUES);
+                }
+
                 if !job_type_filter.is_empty() && !entry_job_type_filter.contains(*job_type_filter)
                 {
                     return None;
@@ -194,46 +283,235 @@ impl ServiceListView {
             plist.ok_or_else(|| OmniboxError::CommandError("Cannot find plist".to_string()))?;
 
         match cmd {
-            OmniboxCommand::Edit => {
-                edit_and_replace(&plist).map_err(OmniboxError::CommandError)?;
-
-                // Clear term
+            OmniboxCommand::Edit => Ok(Some(OmniboxCommand::EditAs(CONFIG.edit_format))),
+            OmniboxCommand::EditAs(format) => {
+                let session =
+                    begin_edit(&name, &plist, format).map_err(OmniboxError::CommandError)?;
+                self.edit_plist(session)
+            }
+            OmniboxCommand::Lint => {
                 self.cb_sink
-                    .send(Box::new(Cursive::clear))
-                    .expect("Must clear");
+                    .send(show_lint(name, lint_file(&*PLIST_FS, &plist.plist_path)))
+                    .expect("Must show lint");
 
-                Ok(Some(OmniboxCommand::Confirm(
-                    format!("Reload {}?", name),
-                    vec![OmniboxCommand::Reload],
-                )))
+                Ok(None)
             }
-            OmniboxCommand::Load(st, dt, _handle) => {
-                load(name, plist.plist_path, Some(dt), Some(st), None)
-                    .map(|_| None)
-                    .map_err(|e| OmniboxError::CommandError(e.to_string()))
+            OmniboxCommand::History => {
+                Ok(Some(OmniboxCommand::HistoryPrompt(name, plist.plist_path)))
             }
-            OmniboxCommand::Unload(dt, _handle) => {
+            OmniboxCommand::Load(st, dt, handle) => load(
+                &*self.transport,
+                name,
+                plist.plist_path,
+                &domain_target(dt, handle),
+                Some(st),
+            )
+            .map_or_else(offer_sudo, |_| Ok(None)),
+            OmniboxCommand::Unload(dt, handle) => {
                 let LaunchdEntryStatus {
                     limit_load_to_session_type,
                     ..
                 } = status;
 
                 unload(
+                    &*self.transport,
                     name,
                     plist.plist_path,
-                    Some(dt),
+                    &domain_target(dt, handle),
                     Some(limit_load_to_session_type),
-                    None,
                 )
-                .map(|_| None)
-                .map_err(|e| OmniboxError::CommandError(e.to_string()))
+                .map_or_else(offer_sudo, |_| Ok(None))
             }
             _ => Ok(None),
         }
     }
 
+    /// Run $EDITOR until the edit is valid or the user gives up,
+    /// then show what changed before writing it
+    fn edit_plist(&self, session: EditSession) -> OmniboxResult {
+        let edited = session.edit();
+
+        // Clear term
+        self.cb_sink
+            .send(Box::new(Cursive::clear))
+            .expect("Must clear");
+
+        let reopen = (
+            "Edit again".to_string(),
+            vec![OmniboxCommand::EditReopen(session.clone())],
+        );
+        let discard = (
+            "Discard changes".to_string(),
+            vec![OmniboxCommand::EditAbort(session.clone())],
+        );
+
+        match edited {
+            Ok((_, changes, errors)) => {
+                let changes = changes
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<String>>()
+                    .join("\n");
+
+                let (prompt, write) = if errors.is_empty() {
+                    (
+                        format!(
+                            "Write these changes to {}?\n\n{}",
+                            session.plist_path, changes
+                        ),
+                        "Write",
+                    )
+                } else {
+                    (
+                        format!(
+                            "Write these changes to {} anyway?\n\n{}\n\n{}",
+                            session.plist_path,
+                            errors.join("\n"),
+                            changes
+                        ),
+                        "Write anyway",
+                    )
+                };
+
+                Ok(Some(OmniboxCommand::Choice(
+                    prompt,
+                    vec![
+                        (write.to_string(), vec![OmniboxCommand::EditWrite(session)]),
+                        reopen,
+                        discard,
+                    ],
+                )))
+            }
+            Err(EditError::Invalid(errors)) => Ok(Some(OmniboxCommand::Choice(
+                format!("Changes not saved:\n\n{}", errors.join("\n")),
+                vec![reopen, discard],
+            ))),
+            Err(EditError::Failed(e)) => Err(OmniboxError::CommandError(e)),
+        }
+    }
+
+    fn write_plist(&self, session: EditSession) -> OmniboxResult {
+        let history = self
+            .history
+            .as_ref()
+            .map_err(|e| OmniboxError::CommandError(e.clone()))?;
+
+        // Validate again, the temp file could have changed since review
+        let (plist, _, _) = session.validate().map_err(|e| match e {
+            EditError::Invalid(errors) => {
+                session.abort();
+                OmniboxError::CommandError(errors.join("\n"))
+            }
+            EditError::Failed(e) => OmniboxError::CommandError(e),
+        })?;
+
+        let written = session.write_back(&plist, history);
+
+        // sudo may have prompted
+        self.cb_sink
+            .send(Box::new(Cursive::clear))
+            .expect("Must clear");
+
+        written.map_err(OmniboxError::CommandError)?;
+
+        Ok(Some(OmniboxCommand::Confirm(
+            format!("Reload {}?", session.label),
+            vec![OmniboxCommand::ReloadLabel(session.label)],
+        )))
+    }
+
+    /// Commands that say which job they're for, rather than going by
+    /// the highlighted row, which can change while a dialog is open
+    fn handle_job_command(&self, cmd: &OmniboxCommand) -> Option<OmniboxResult> {
+        match cmd {
+            OmniboxCommand::HistoryRestore {
+                label,
+                plist_path,
+                version,
+            } => Some(self.restore(label, plist_path, version)),
+            OmniboxCommand::ReloadLabel(label) => Some(self.reload(label)),
+            // The session has the label and plist
+            OmniboxCommand::EditWrite(session) => Some(self.write_plist(session.clone())),
+            OmniboxCommand::EditReopen(session) => Some(self.edit_plist(session.clone())),
+            OmniboxCommand::EditAbort(session) => {
+                session.abort();
+                Some(Ok(None))
+            }
+            _ => None,
+        }
+    }
+
+    fn restore(&self, label: &str, plist_path: &str, version: &Path) -> OmniboxResult {
+        let restored = self
+            .history
+            .as_ref()
+            .map_err(|e| e.clone())
+            .and_then(|h| h.restore(label, version, plist_path));
+
+        // sudo may have prompted
+        self.cb_sink
+            .send(Box::new(Cursive::clear))
+            .expect("Must clear");
+
+        restored.map_err(OmniboxError::CommandError)?;
+        Ok(None)
+    }
+
+    /// Unload and load label again, where it's loaded now
+    fn reload(&self, label: &str) -> OmniboxResult {
+        let LaunchdEntryStatus {
+            plist,
+            limit_load_to_session_type,
+            domain,
+            ..
+        } = get_entry_status(&*self.transport, label);
+
+        let plist = plist.ok_or_else(|| {
+            OmniboxError::CommandError(format!("Cannot find plist for {}", label))
+        })?;
+
+        match (limit_load_to_session_type, domain) {
+            (_, DomainType::Unknown) | (SessionType::Unknown, _) => Err(
+                OmniboxError::CommandError(format!("{} isn't loaded, load it instead", label)),
+            ),
+            (st, dt) => {
+                let unloaded = unload(
+                    &*self.transport,
+                    label.to_string(),
+                    plist.plist_path.clone(),
+                    &domain_target(dt.clone(), None),
+                    Some(st.clone()),
+                );
+
+                if let Err(e) = unloaded {
+                    return offer_sudo(e);
+                }
+
+                load(
+                    &*self.transport,
+                    label.to_string(),
+                    plist.plist_path,
+                    &domain_target(dt, None),
+                    Some(st),
+                )
+                .map_or_else(offer_sudo, |_| Ok(None))
+            }
+        }
+    }
+
     fn handle_command(&self, cmd: OmniboxCommand) -> OmniboxResult {
-        let (ServiceListItem { name, status, .. }, plist) = self.with_active_item_plist()?;
+        if let Some(result) = self.handle_job_command(&cmd) {
+            return result;
+        }
+
+        let (
+            ServiceListItem {
+                name,
+                status,
+                job_type_filter,
+            },
+            plist,
+        ) = self.with_active_item_plist()?;
 
         let need_escalate = plist
             .map(|LaunchdPlist { entry_location, .. }| {
@@ -248,13 +526,12 @@ impl ServiceListView {
             | OmniboxCommand::DisableRequest
             | OmniboxCommand::EnableRequest
             | OmniboxCommand::ProcInfo
-            | OmniboxCommand::Edit => {
-                if (sudo::check() != RunningAs::Root) && need_escalate {
-                    return Ok(Some(OmniboxCommand::Confirm(
-                        "This requires root privileges. Sudo and restart?".to_string(),
-                        vec![OmniboxCommand::Quit, OmniboxCommand::Sudo],
-                    )));
-                }
+                if (sudo::check() != RunningAs::Root) && need_escalate =>
+            {
+                return Ok(Some(OmniboxCommand::Confirm(
+                    "This requires root privileges. Sudo and restart?".to_string(),
+                    vec![OmniboxCommand::Quit, OmniboxCommand::Sudo],
+                )));
             }
             _ => (),
         };
@@ -324,18 +601,19 @@ impl ServiceListView {
                     )]))),
                 }
             }
-            OmniboxCommand::Enable(dt) => enable(name, dt)
-                .map(|_| None)
-                .map_err(|e| OmniboxError::CommandError(e.to_string())),
-            OmniboxCommand::Disable(dt) => disable(name, dt)
-                .map(|_| None)
-                .map_err(|e| OmniboxError::CommandError(e.to_string())),
+            OmniboxCommand::Enable(dt) => enable(&*self.transport, name, &domain_target(dt, None))
+                .map_or_else(offer_sudo, |_| Ok(None)),
+            OmniboxCommand::Disable(dt) => {
+                disable(&*self.transport, name, &domain_target(dt, None))
+                    .map_or_else(offer_sudo, |_| Ok(None))
+            }
+            #[cfg(target_os = "macos")]
             OmniboxCommand::ProcInfo => {
                 if status.pid == 0 {
                     return Err(OmniboxError::CommandError(format!("No PID for {}", name)));
                 }
-                let (size, shmem) =
-                    procinfo(status.pid).map_err(|e| OmniboxError::CommandError(e.to_string()))?;
+                let (size, shmem) = procinfo(&*self.transport, status.pid)
+                    .map_err(|e| OmniboxError::CommandError(e.to_string()))?;
 
                 show_pager(&self.cb_sink, unsafe {
                     &*slice_from_raw_parts(shmem.region as *mut u8, size)
@@ -344,9 +622,21 @@ impl ServiceListView {
 
                 Ok(None)
             }
-            OmniboxCommand::Edit | OmniboxCommand::Load(_, _, _) | OmniboxCommand::Unload(_, _) => {
-                self.handle_plist_command(cmd)
+            OmniboxCommand::Info => {
+                let is_loaded = job_type_filter.intersects(JobTypeFilter::LOADED);
+
+                self.cb_sink
+                    .send(show_info(name, status, is_loaded))
+                    .expect("Must show info");
+
+                Ok(None)
             }
+            OmniboxCommand::Edit
+            | OmniboxCommand::EditAs(_)
+            | OmniboxCommand::Lint
+            | OmniboxCommand::History
+            | OmniboxCommand::Load(_, _, _)
+            | OmniboxCommand::Unload(_, _) => self.handle_plist_command(cmd),
             _ => Ok(None),
         }
     }
@@ -376,3 +666,152 @@ impl OmniboxSubscriber for ServiceListView {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::mpsc::channel;
+    use std::sync::Arc;
+
+    use xpc_sys::transport::fake_launchd::FakeLaunchd;
+
+    use crate::launchd::edit_format::EditFormat;
+    use crate::launchd::entry_status::LaunchdEntryStatus;
+    use crate::launchd::history::History;
+    use crate::launchd::job_type_filter::JobTypeFilter;
+    use crate::launchd::plist::{
+        EditSession, LaunchdEntryLocation, LaunchdEntryType, LaunchdPlist,
+    };
+    use crate::tui::omnibox::command::OmniboxCommand;
+    use crate::tui::omnibox::subscribed_view::OmniboxSubscriber;
+    use crate::tui::omnibox::view::OmniboxEvent;
+    use crate::tui::service_list::list_item::ServiceListItem;
+    use crate::tui::service_list::view::ServiceListView;
+
+    fn item(name: &str, plist_path: &str) -> ServiceListItem {
+        let mut status = LaunchdEntryStatus::default();
+        status.plist = Some(LaunchdPlist {
+            entry_type: LaunchdEntryType::Agent,
+            entry_location: LaunchdEntryLocation::User,
+            plist_path: plist_path.to_string(),
+            schedule: None,
+            findings: vec![],
+            run_at_load: false,
+        });
+
+        ServiceListItem {
+            name: name.to_string(),
+            status,
+            job_type_filter: JobTypeFilter::default(),
+        }
+    }
+
+    fn view(history: &'static History) -> ServiceListView {
+        // Never driven, so nothing polls the fake
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let (cb_sink, rx) = channel();
+        // Dialogs and redraws go nowhere
+        Box::leak(Box::new(rx));
+
+        let mut view =
+            ServiceListView::new(runtime.handle(), cb_sink, Arc::new(FakeLaunchd::new()));
+        view.history = Ok(history);
+        view
+    }
+
+    #[test]
+    fn restore_outlives_selection() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("com.example.a.plist");
+        let b = dir.path().join("com.example.b.plist");
+        let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+        fs::write(a, "a now").unwrap();
+        fs::write(b, "b").unwrap();
+
+        let history: &'static History = Box::leak(Box::new(History::new(dir.path().join("h"))));
+        let old = history.save("com.example.a", b"a before").unwrap();
+
+        let mut view = view(history);
+
+        view.table_list_view.replace_and_preserve_selection(vec![
+            item("com.example.a", a),
+            item("com.example.b", b),
+        ]);
+
+        let prompt = view
+            .on_omnibox(OmniboxEvent::Command(OmniboxCommand::History))
+            .unwrap();
+        assert_eq!(
+            prompt,
+            Some(OmniboxCommand::HistoryPrompt(
+                "com.example.a".to_string(),
+                a.to_string()
+            ))
+        );
+
+        // Re-sorted while the prompt is open, b is highlighted now
+        view.table_list_view.replace_and_preserve_selection(vec![
+            item("com.example.b", b),
+            item("com.example.a", a),
+        ]);
+        assert_eq!(view.get_active_list_item().unwrap().name, "com.example.b");
+
+        let restore = match OmniboxCommand::restore("com.example.a", a, old.path, false) {
+            OmniboxCommand::Chain(mut cmds) => cmds.remove(0),
+            other => panic!("expected a chain, got {:?}", other),
+        };
+        view.on_omnibox(OmniboxEvent::Command(restore)).unwrap();
+
+        assert_eq!(fs::read(a).unwrap(), b"a before");
+        assert_eq!(fs::read(b).unwrap(), b"b");
+        assert_eq!(history.versions("com.example.a").len(), 2);
+        assert!(history.versions("com.example.b").is_empty());
+    }
+
+    #[test]
+    fn edit_write_without_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let plist_path = dir.path().join("com.example.a.plist");
+        let temp_path = dir.path().join("com.example.a.edit.xml");
+        let plist_path_str = plist_path.to_str().unwrap().to_string();
+
+        let job = |program: &str| {
+            let mut dict = plist::Dictionary::new();
+            dict.insert("Label".to_string(), "com.example.a".into());
+            dict.insert("Program".to_string(), program.into());
+            plist::Value::Dictionary(dict)
+        };
+        job("/bin/true").to_file_xml(&plist_path).unwrap();
+        job("/bin/false").to_file_xml(&temp_path).unwrap();
+
+        let history: &'static History = Box::leak(Box::new(History::new(dir.path().join("h"))));
+        let mut view = view(history);
+
+        // e.g. filtered down to nothing while the review dialog was open
+        let session = EditSession {
+            label: "com.example.a".to_string(),
+            plist_path: plist_path_str,
+            temp_path: temp_path.clone(),
+            format: EditFormat::Xml,
+        };
+        let reply = view
+            .on_omnibox(OmniboxEvent::Command(OmniboxCommand::EditWrite(session)))
+            .unwrap();
+
+        assert_eq!(
+            reply,
+            Some(OmniboxCommand::Confirm(
+                "Reload com.example.a?".to_string(),
+                vec![OmniboxCommand::ReloadLabel("com.example.a".to_string())]
+            ))
+        );
+        assert_eq!(
+            plist::Value::from_file(&plist_path).unwrap(),
+            job("/bin/false")
+        );
+        assert_eq!(history.versions("com.example.a").len(), 1);
+        assert!(!temp_path.exists());
+    }
+}
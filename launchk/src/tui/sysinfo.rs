@@ -4,7 +4,9 @@ use cursive::utils::markup::StyledString;
 use cursive::views::{LinearLayout, ResizedView, TextView};
 
 use sudo::RunningAs;
+#[cfg(target_os = "macos")]
 use xpc_sys::csr::{csr_check, CsrConfig};
+#[cfg(target_os = "macos")]
 use xpc_sys::rs_sysctlbyname;
 
 pub fn make_layout() -> LinearLayout {
@@ -12,10 +14,7 @@ pub fn make_layout() -> LinearLayout {
     let mut layout = LinearLayout::horizontal();
 
     let mut macos = StyledString::styled("macOS: ", bold);
-    let osproductversion =
-        unsafe { rs_sysctlbyname("kern.osproductversion").unwrap_or("".to_string()) };
-    let osversion = unsafe { rs_sysctlbyname("kern.osversion").unwrap_or("".to_string()) };
-    macos.append_plain(format!("{} ({})", osproductversion, osversion));
+    macos.append_plain(macos_version());
 
     if sudo::check() == RunningAs::Root {
         macos.append_styled(" (root)", bold.combine(Color::Light(BaseColor::Red)));
@@ -23,17 +22,40 @@ pub fn make_layout() -> LinearLayout {
 
     layout.add_child(ResizedView::with_full_width(TextView::new(macos)));
 
-    // If granted CSR_ALLOW_UNTRUSTED_KEXTS, SIP is probably off
     let mut sip = StyledString::styled("SIP: ", bold);
-    let sip_data = unsafe {
+    sip.append_plain(sip_enabled());
+
+    layout.add_child(ResizedView::with_full_width(TextView::new(sip)));
+
+    layout
+}
+
+#[cfg(target_os = "macos")]
+fn macos_version() -> String {
+    let osproductversion =
+        unsafe { rs_sysctlbyname("kern.osproductversion").unwrap_or("".to_string()) };
+    let osversion = unsafe { rs_sysctlbyname("kern.osversion").unwrap_or("".to_string()) };
+    format!("{} ({})", osproductversion, osversion)
+}
+
+/// If granted CSR_ALLOW_UNTRUSTED_KEXTS, SIP is probably off
+#[cfg(target_os = "macos")]
+fn sip_enabled() -> String {
+    unsafe {
         format!(
             "{}",
             csr_check(CsrConfig::ALLOW_UNTRUSTED_KEXTS.bits()) != 0
         )
-    };
-    sip.append_plain(sip_data);
+    }
+}
 
-    layout.add_child(ResizedView::with_full_width(TextView::new(sip)));
+/// Not running on a Mac, only reading one with --root
+#[cfg(not(target_os = "macos"))]
+fn macos_version() -> String {
+    "-".to_string()
+}
 
-    layout
+#[cfg(not(target_os = "macos"))]
+fn sip_enabled() -> String {
+    "-".to_string()
 }
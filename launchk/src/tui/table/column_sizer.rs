@@ -1,4 +1,4 @@
-use std::{cell::Cell, collections::HashMap, sync::Arc};
+use std::{cell::Cell, collections::HashMap, rc::Rc};
 
 /// Width oriented column sizing utility
 pub struct ColumnSizer {
@@ -18,7 +18,7 @@ pub struct ColumnSizer {
 
 impl ColumnSizer {
     /// Create a new ColumnSizer
-    pub fn new<I, K>(columns: I) -> Arc<Self>
+    pub fn new<I, K>(columns: I) -> Rc<Self>
     where
         I: IntoIterator<Item = (K, Option<usize>)> + Clone,
         K: AsRef<str>,
@@ -43,7 +43,7 @@ impl ColumnSizer {
             padding: Default::default(),
         };
 
-        Arc::new(cs)
+        Rc::new(cs)
     }
 
     /// Get the width for a column by index
@@ -51,7 +51,7 @@ impl ColumnSizer {
         let size = self
             .user_sizes
             .get(&i)
-            .map(Clone::clone)
+            .copied()
             .unwrap_or(self.dynamic_column_size.get());
 
         // I have 'sized' my user defined columns around how much
@@ -72,11 +72,7 @@ impl ColumnSizer {
 
     /// Call when x changes to recompute dynamic_column_size and padding
     pub fn update_x(&self, x: usize) {
-        let mut remaining = if x > self.user_sizes_total {
-            x - self.user_sizes_total
-        } else {
-            0
-        };
+        let mut remaining = x.saturating_sub(self.user_sizes_total);
 
         let mut dcs = remaining / self.num_dynamic_columns;
         if dcs > 35 {
@@ -84,7 +80,7 @@ impl ColumnSizer {
         }
 
         if remaining > (self.num_dynamic_columns * dcs) {
-            remaining = remaining - (self.num_dynamic_columns * dcs);
+            remaining -= self.num_dynamic_columns * dcs;
         }
 
         self.dynamic_column_size.set(dcs);
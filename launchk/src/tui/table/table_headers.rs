@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::rc::Rc;
 
 use cursive::theme::{BaseColor, Color, Effect, Style};
 use cursive::{Printer, View, XY};
@@ -8,13 +8,13 @@ use super::column_sizer::ColumnSizer;
 /// Draw column headers from their names + a column sizer
 pub struct TableHeaders {
     columns: Vec<String>,
-    column_sizer: Arc<ColumnSizer>,
+    column_sizer: Rc<ColumnSizer>,
 }
 
 impl TableHeaders {
     pub fn new<S: Into<String>>(
         columns: impl Iterator<Item = S>,
-        column_sizer: Arc<ColumnSizer>,
+        column_sizer: Rc<ColumnSizer>,
     ) -> Self {
         Self {
             columns: columns.map(|f| f.into()).collect(),
@@ -4,7 +4,6 @@ use std::marker::PhantomData;
 use std::rc::Rc;
 
 use std::collections::hash_map::DefaultHasher;
-use std::sync::Arc;
 
 use cursive::event::{Event, EventResult};
 use cursive::traits::{Resizable, Scrollable};
@@ -22,7 +21,7 @@ pub trait TableListItem {
 /// A "table" implemented on top of SelectView<T> where we
 /// divvy up x into columns
 pub struct TableListView<T> {
-    column_sizer: Arc<ColumnSizer>,
+    column_sizer: Rc<ColumnSizer>,
     linear_layout: LinearLayout,
     // LinearLayout swallows T from , but we still need it
     inner: PhantomData<T>,
@@ -58,7 +57,7 @@ impl<T: 'static + TableListItem> TableListView<T> {
         Self {
             linear_layout,
             column_sizer,
-            inner: PhantomData::default(),
+            inner: PhantomData,
             last_hash,
         }
     }
@@ -119,9 +118,9 @@ impl<T: 'static + TableListItem> TableListView<T> {
                 c.as_any_mut()
                     .downcast_mut::<ScrollView<ResizedView<ResizedView<SelectView<T>>>>>()
             })
-            .and_then(|v| Some(v.get_inner_mut()))
-            .and_then(|v| Some(v.get_inner_mut()))
-            .and_then(|v| Some(v.get_inner_mut()))
+            .map(|v| v.get_inner_mut())
+            .map(|v| v.get_inner_mut())
+            .map(|v| v.get_inner_mut())
             .expect("Unable to get SelectView")
     }
 
@@ -132,9 +131,9 @@ impl<T: 'static + TableListItem> TableListView<T> {
                 c.as_any()
                     .downcast_ref::<ScrollView<ResizedView<ResizedView<SelectView<T>>>>>()
             })
-            .and_then(|v| Some(v.get_inner()))
-            .and_then(|v| Some(v.get_inner()))
-            .and_then(|v| Some(v.get_inner()))
+            .map(|v| v.get_inner())
+            .map(|v| v.get_inner())
+            .map(|v| v.get_inner())
             .expect("Unable to get SelectView")
     }
 }
@@ -0,0 +1,116 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use plist::{Dictionary, Value};
+use tempfile::TempDir;
+
+fn write_plist(root: &Path, path: &str, label: &str, program: &str) {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+    let mut dict = Dictionary::new();
+    dict.insert("Label".to_string(), label.into());
+    dict.insert("Program".to_string(), program.into());
+    dict.insert("RunAtLoad".to_string(), true.into());
+    Value::Dictionary(dict).to_file_xml(path).unwrap();
+}
+
+/// launchk with --root, and a HOME without a config
+fn launchk(root: &Path, args: &[&str]) -> Output {
+    let home = TempDir::new().unwrap();
+
+    Command::new(env!("CARGO_BIN_EXE_launchk"))
+        .env("HOME", home.path())
+        .arg("--root")
+        .arg(root)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn audit_volume() {
+    let root = TempDir::new().unwrap();
+    write_plist(
+        root.path(),
+        "Library/LaunchDaemons/com.example.agent.plist",
+        "com.example.agent",
+        "/tmp/.x/agent",
+    );
+    write_plist(
+        root.path(),
+        "Users/me/Library/LaunchAgents/com.example.user.plist",
+        "com.example.user",
+        "/Users/me/bin/user",
+    );
+
+    let output = launchk(root.path(), &["audit"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let entries: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let found: Vec<(&str, &str, &str)> = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| {
+            (
+                e["label"].as_str().unwrap(),
+                e["location"].as_str().unwrap(),
+                e["severity"].as_str().unwrap(),
+            )
+        })
+        .collect();
+
+    // Both programs are missing from the volume, one is also hidden in /tmp
+    assert_eq!(
+        found,
+        vec![
+            ("com.example.agent", "global", "high"),
+            ("com.example.user", "user", "medium"),
+        ]
+    );
+}
+
+#[test]
+fn lint_volume() {
+    let root = TempDir::new().unwrap();
+    let program = root.path().join("opt/example/bin/job");
+    fs::create_dir_all(program.parent().unwrap()).unwrap();
+    fs::write(&program, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+
+    write_plist(
+        root.path(),
+        "Library/LaunchDaemons/com.example.job.plist",
+        "com.example.job",
+        "/opt/example/bin/job",
+    );
+
+    // Only on the volume, not this machine
+    let output = launchk(root.path(), &["lint"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    fs::remove_file(&program).unwrap();
+
+    let output = launchk(root.path(), &["lint"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .contains("error: Program: /opt/example/bin/job does not exist"));
+}
+
+/// There's no launchd to ask
+#[cfg(not(target_os = "macos"))]
+#[test]
+fn needs_root() {
+    let home = TempDir::new().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_launchk"))
+        .env("HOME", home.path())
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--root"));
+}
@@ -1,14 +1,40 @@
+#[cfg(feature = "generate-bindings")]
 extern crate bindgen;
+#[cfg(feature = "generate-bindings")]
 extern crate xcrun;
 
 use std::env;
+
+#[cfg(feature = "generate-bindings")]
 use std::path::PathBuf;
 
+#[cfg(feature = "generate-bindings")]
 use xcrun::SDK;
 
+#[cfg(feature = "generate-bindings")]
 static MACOS_INCLUDE_PATH: &str = "/usr/include";
 
 fn main() {
+    // Off macOS only the portable parts are built, there is nothing to bind
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("macos") {
+        return;
+    }
+
+    // lib.rs includes src/bindings.rs
+    if env::var_os("CARGO_FEATURE_PREGENERATED_BINDINGS").is_some() {
+        return;
+    }
+
+    generate();
+}
+
+#[cfg(not(feature = "generate-bindings"))]
+fn generate() {
+    panic!("Enable the generate-bindings or pregenerated-bindings feature to build for macOS");
+}
+
+#[cfg(feature = "generate-bindings")]
+fn generate() {
     let sdk_path = xcrun::find_sdk(SDK::macOS(None))
         .and_then(|pb| pb.to_str().map(String::from))
         .and_then(|p| p.strip_suffix("\n").map(String::from))
@@ -0,0 +1,131 @@
+// Pre-generated bindings, used with the pregenerated-bindings feature instead
+// of running bindgen against the macOS SDK in build.rs.
+//
+// This is the subset of bindgen's output for xpc/xpc.h and bootstrap.h
+// (with the allowlist in build.rs) that xpc-sys uses. To pick up new
+// symbols, build without the feature on macOS and copy what is needed
+// from $OUT_DIR/bindings.rs.
+
+pub type __darwin_natural_t = ::std::os::raw::c_uint;
+pub type __darwin_mach_port_name_t = __darwin_natural_t;
+pub type __darwin_mach_port_t = __darwin_mach_port_name_t;
+pub type __darwin_uid_t = __uint32_t;
+pub type __uint32_t = ::std::os::raw::c_uint;
+pub type uid_t = __darwin_uid_t;
+pub type natural_t = __darwin_natural_t;
+pub type kern_return_t = ::std::os::raw::c_int;
+pub type vm_offset_t = usize;
+pub type vm_size_t = usize;
+pub type vm_address_t = vm_offset_t;
+pub type mach_port_name_t = natural_t;
+pub type mach_port_t = __darwin_mach_port_t;
+pub type mach_port_array_t = *mut mach_port_t;
+pub type mach_msg_type_number_t = natural_t;
+pub type ipc_space_t = mach_port_t;
+pub type task_t = mach_port_t;
+pub type vm_map_t = mach_port_t;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _xpc_type_s {
+    _unused: [u8; 0],
+}
+pub type xpc_type_t = *const _xpc_type_s;
+pub type xpc_object_t = *mut ::std::os::raw::c_void;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _xpc_bool_s {
+    _unused: [u8; 0],
+}
+
+pub type xpc_array_applier_t = *mut ::std::os::raw::c_void;
+pub type xpc_dictionary_applier_t = *mut ::std::os::raw::c_void;
+
+extern "C" {
+    pub static mut bootstrap_port: mach_port_t;
+
+    pub static _xpc_type_null: _xpc_type_s;
+    pub static _xpc_type_bool: _xpc_type_s;
+    pub static _xpc_bool_true: _xpc_bool_s;
+    pub static _xpc_bool_false: _xpc_bool_s;
+    pub static _xpc_type_int64: _xpc_type_s;
+    pub static _xpc_type_uint64: _xpc_type_s;
+    pub static _xpc_type_double: _xpc_type_s;
+    pub static _xpc_type_date: _xpc_type_s;
+    pub static _xpc_type_data: _xpc_type_s;
+    pub static _xpc_type_string: _xpc_type_s;
+    pub static _xpc_type_uuid: _xpc_type_s;
+    pub static _xpc_type_fd: _xpc_type_s;
+    pub static _xpc_type_shmem: _xpc_type_s;
+    pub static _xpc_type_array: _xpc_type_s;
+    pub static _xpc_type_dictionary: _xpc_type_s;
+    pub static _xpc_type_error: _xpc_type_s;
+
+    pub fn mach_port_deallocate(task: ipc_space_t, name: mach_port_name_t) -> kern_return_t;
+    pub fn mach_ports_lookup(
+        target_task: task_t,
+        init_port_set: *mut mach_port_array_t,
+        init_port_setCnt: *mut mach_msg_type_number_t,
+    ) -> kern_return_t;
+    pub fn vm_allocate(
+        target_task: vm_map_t,
+        address: *mut vm_address_t,
+        size: vm_size_t,
+        flags: ::std::os::raw::c_int,
+    ) -> kern_return_t;
+
+    pub fn xpc_retain(object: xpc_object_t) -> xpc_object_t;
+    pub fn xpc_release(object: xpc_object_t);
+    pub fn xpc_get_type(object: xpc_object_t) -> xpc_type_t;
+    pub fn xpc_type_get_name(type_: xpc_type_t) -> *const ::std::os::raw::c_char;
+    pub fn xpc_copy(object: xpc_object_t) -> xpc_object_t;
+    pub fn xpc_copy_description(object: xpc_object_t) -> *mut ::std::os::raw::c_char;
+
+    pub fn xpc_null_create() -> xpc_object_t;
+    pub fn xpc_bool_create(value: bool) -> xpc_object_t;
+    pub fn xpc_bool_get_value(xbool: xpc_object_t) -> bool;
+    pub fn xpc_int64_create(value: i64) -> xpc_object_t;
+    pub fn xpc_int64_get_value(xint: xpc_object_t) -> i64;
+    pub fn xpc_uint64_create(value: u64) -> xpc_object_t;
+    pub fn xpc_uint64_get_value(xuint: xpc_object_t) -> u64;
+    pub fn xpc_double_create(value: f64) -> xpc_object_t;
+    pub fn xpc_double_get_value(xdouble: xpc_object_t) -> f64;
+    pub fn xpc_date_create(interval: i64) -> xpc_object_t;
+    pub fn xpc_date_get_value(xdate: xpc_object_t) -> i64;
+    pub fn xpc_data_create(bytes: *const ::std::os::raw::c_void, length: usize) -> xpc_object_t;
+    pub fn xpc_data_get_bytes_ptr(xdata: xpc_object_t) -> *const ::std::os::raw::c_void;
+    pub fn xpc_data_get_length(xdata: xpc_object_t) -> usize;
+    pub fn xpc_string_create(string: *const ::std::os::raw::c_char) -> xpc_object_t;
+    pub fn xpc_string_get_string_ptr(xstring: xpc_object_t) -> *const ::std::os::raw::c_char;
+    pub fn xpc_uuid_create(uuid: *const ::std::os::raw::c_uchar) -> xpc_object_t;
+    pub fn xpc_uuid_get_bytes(xuuid: xpc_object_t) -> *const u8;
+    pub fn xpc_fd_create(fd: ::std::os::raw::c_int) -> xpc_object_t;
+    pub fn xpc_fd_dup(xfd: xpc_object_t) -> ::std::os::raw::c_int;
+    pub fn xpc_shmem_create(region: *mut ::std::os::raw::c_void, length: usize) -> xpc_object_t;
+
+    pub fn xpc_array_create(objects: *mut xpc_object_t, count: usize) -> xpc_object_t;
+    pub fn xpc_array_append_value(xarray: xpc_object_t, value: xpc_object_t);
+    pub fn xpc_array_apply(xarray: xpc_object_t, applier: xpc_array_applier_t) -> bool;
+
+    pub fn xpc_dictionary_create(
+        keys: *const *const ::std::os::raw::c_char,
+        values: *mut xpc_object_t,
+        count: usize,
+    ) -> xpc_object_t;
+    pub fn xpc_dictionary_set_value(
+        xdict: xpc_object_t,
+        key: *const ::std::os::raw::c_char,
+        value: xpc_object_t,
+    );
+    pub fn xpc_dictionary_apply(xdict: xpc_object_t, applier: xpc_dictionary_applier_t) -> bool;
+    pub fn xpc_dictionary_get_string(
+        xdict: xpc_object_t,
+        key: *const ::std::os::raw::c_char,
+    ) -> *const ::std::os::raw::c_char;
+    pub fn xpc_dictionary_set_int64(
+        xdict: xpc_object_t,
+        key: *const ::std::os::raw::c_char,
+        value: i64,
+    );
+}
@@ -1,4 +1,4 @@
-use std::{fmt, fmt::Formatter, os::raw::c_int};
+use std::{fmt, fmt::Formatter};
 
 pub type csr_config_t = u32;
 
@@ -46,7 +46,28 @@ impl fmt::Debug for CsrConfig {
     }
 }
 
+#[cfg(target_os = "macos")]
 extern "C" {
     /// 0 if has mask
-    pub fn csr_check(mask: csr_config_t) -> c_int;
+    pub fn csr_check(mask: csr_config_t) -> std::os::raw::c_int;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::csr::CsrConfig;
+
+    #[test]
+    fn decode_flags() {
+        let mask = CsrConfig::from_bits(1 << 5).unwrap();
+        assert_eq!(
+            format!("{:?}", mask),
+            "ALLOW_DESTRUCTIVE_DTRACE | ALLOW_UNRESTRICTED_DTRACE"
+        );
+
+        assert_eq!(
+            format!("{:?}", CsrConfig::from_bits(1 << 11).unwrap()),
+            "ALLOW_UNAUTHENTICATED_ROOT"
+        );
+        assert!(CsrConfig::from_bits(1 << 12).is_none());
+    }
 }
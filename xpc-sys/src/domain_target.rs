@@ -0,0 +1,193 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::enums::DomainType;
+use crate::objects::xpc_error::XPCError;
+
+/// launchctl domain target, e.g. system/com.apple.foo, gui/501/homebrew.mxcl.redis,
+/// user/501, login/100006 or pid/1613/com.apple.security.pboxd
+///
+/// Domain types launchctl has no name for (the requestor domains) display
+/// as their Debug name and don't parse back
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DomainTarget {
+    pub domain_type: DomainType,
+    /// UID (user, gui), ASID (login, session) or PID (pid), 0 for system
+    pub handle: u64,
+    pub label: Option<String>,
+}
+
+impl DomainTarget {
+    pub fn new(domain_type: DomainType, handle: u64, label: Option<String>) -> Self {
+        Self {
+            domain_type,
+            handle,
+            label,
+        }
+    }
+
+    pub fn system() -> Self {
+        Self::new(DomainType::System, 0, None)
+    }
+
+    pub fn gui(uid: u64) -> Self {
+        Self::new(DomainType::Gui, uid, None)
+    }
+
+    pub fn user(uid: u64) -> Self {
+        Self::new(DomainType::User, uid, None)
+    }
+
+    pub fn login(asid: u64) -> Self {
+        Self::new(DomainType::UserLogin, asid, None)
+    }
+
+    pub fn pid(pid: u64) -> Self {
+        Self::new(DomainType::PID, pid, None)
+    }
+
+    /// Same domain, targeting a service
+    pub fn with_label<S: Into<String>>(mut self, label: S) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// The domain without the label
+    pub fn domain(&self) -> Self {
+        Self::new(self.domain_type.clone(), self.handle, None)
+    }
+}
+
+/// launchctl's name for the domain type, if it has one
+fn prefix(domain_type: &DomainType) -> Option<&'static str> {
+    match domain_type {
+        DomainType::System => Some("system"),
+        DomainType::User => Some("user"),
+        DomainType::UserLogin => Some("login"),
+        DomainType::Session => Some("session"),
+        DomainType::PID => Some("pid"),
+        DomainType::Gui => Some("gui"),
+        _ => None,
+    }
+}
+
+impl FromStr for DomainTarget {
+    type Err = XPCError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, '/');
+        let err = |msg: &str| XPCError::ValueError(format!("Bad domain target {}: {}", s, msg));
+
+        let domain_type = match parts.next().unwrap_or_default() {
+            "system" => DomainType::System,
+            "user" => DomainType::User,
+            "login" => DomainType::UserLogin,
+            "session" => DomainType::Session,
+            "pid" => DomainType::PID,
+            "gui" => DomainType::Gui,
+            _ => return Err(err("expected system, user, login, session, pid or gui")),
+        };
+
+        // system has no handle, so what follows is the label
+        let handle = if domain_type == DomainType::System {
+            0
+        } else {
+            parts
+                .next()
+                .ok_or_else(|| err("missing UID, ASID or PID"))?
+                .parse::<u64>()
+                .map_err(|e| err(&e.to_string()))?
+        };
+
+        let label = match domain_type {
+            DomainType::System => s.split_once('/').map(|(_, label)| label),
+            _ => parts.next(),
+        };
+
+        match label {
+            Some("") => Err(err("empty label")),
+            label => Ok(Self::new(domain_type, handle, label.map(|l| l.to_string()))),
+        }
+    }
+}
+
+impl fmt::Display for DomainTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.domain_type, prefix(&self.domain_type)) {
+            (DomainType::System, _) => write!(f, "system")?,
+            (_, Some(p)) => write!(f, "{}/{}", p, self.handle)?,
+            (_, None) => write!(f, "{:?}", self.domain_type)?,
+        };
+
+        match &self.label {
+            Some(label) => write!(f, "/{}", label),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain_target::DomainTarget;
+    use crate::enums::DomainType;
+    use crate::objects::xpc_value::XPCValue;
+    use crate::traits::query_builder::QueryBuilder;
+    use crate::traits::xpc_value::TryXPCValue;
+
+    #[test]
+    fn parse_and_display() {
+        let cases = [
+            ("system", DomainType::System, 0, None),
+            (
+                "system/com.apple.lskdd",
+                DomainType::System,
+                0,
+                Some("com.apple.lskdd"),
+            ),
+            (
+                "gui/501/homebrew.mxcl.redis",
+                DomainType::Gui,
+                501,
+                Some("homebrew.mxcl.redis"),
+            ),
+            ("user/501", DomainType::User, 501, None),
+            ("login/100006", DomainType::UserLogin, 100006, None),
+            (
+                "pid/1613/com.apple.security.pboxd",
+                DomainType::PID,
+                1613,
+                Some("com.apple.security.pboxd"),
+            ),
+        ];
+
+        for (s, domain_type, handle, label) in cases.iter() {
+            let target: DomainTarget = s.parse().unwrap();
+            assert_eq!(
+                target,
+                DomainTarget::new(domain_type.clone(), *handle, label.map(|l| l.to_string()))
+            );
+            assert_eq!(&target.to_string(), s);
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        for s in ["", "nope/501", "gui", "gui/me", "user/501/", "system/"].iter() {
+            assert!(s.parse::<DomainTarget>().is_err(), "{} should not parse", s);
+        }
+    }
+
+    #[test]
+    fn query() {
+        let target: DomainTarget = "gui/501/com.docker.vmnetd".parse().unwrap();
+        let query = XPCValue::new_dictionary().with_domain_target(&target);
+
+        let domain_type: u64 = query.get(["type"]).unwrap().xpc_value().unwrap();
+        let handle: u64 = query.get(["handle"]).unwrap().xpc_value().unwrap();
+        let name: String = query.get(["name"]).unwrap().xpc_value().unwrap();
+
+        assert_eq!(domain_type, 8);
+        assert_eq!(handle, 501);
+        assert_eq!(name, "com.docker.vmnetd");
+    }
+}
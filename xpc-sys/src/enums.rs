@@ -1,12 +1,13 @@
-use std::convert::TryFrom;
 use std::fmt;
-use std::sync::Arc;
 
-use crate::objects::xpc_error::XPCError;
-use crate::objects::xpc_object::XPCObject;
-use crate::objects::xpc_type;
-use crate::objects::xpc_type::check_xpc_type;
-use crate::traits::xpc_value::TryXPCValue;
+use serde::{Deserialize, Deserializer};
+
+#[cfg(target_os = "macos")]
+use {
+    crate::objects::xpc_error::XPCError, crate::objects::xpc_object::XPCObject,
+    crate::objects::xpc_type, crate::objects::xpc_type::check_xpc_type,
+    crate::traits::xpc_value::TryXPCValue, std::convert::TryFrom, std::sync::Arc,
+};
 
 /// LimitLoadToSessionType key in XPC response
 /// https://developer.apple.com/library/archive/technotes/tn2083/_index.html
@@ -59,6 +60,13 @@ impl From<String> for SessionType {
     }
 }
 
+impl<'de> Deserialize<'de> for SessionType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(|s| s.into())
+    }
+}
+
+#[cfg(target_os = "macos")]
 impl TryFrom<Arc<XPCObject>> for SessionType {
     type Error = XPCError;
 
@@ -69,6 +77,13 @@ impl TryFrom<Arc<XPCObject>> for SessionType {
     }
 }
 
+/// Enum used for selecting between _xpc_type_mach_send and _xpc_type_mach_recv
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MachPortType {
+    Send,
+    Recv,
+}
+
 // Huge thanks to: https://saelo.github.io/presentations/bits_of_launchd.pdf
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum DomainType {
@@ -79,6 +94,7 @@ pub enum DomainType {
     PID = 5,
     RequestorUserDomain = 6,
     RequestorDomain = 7,
+    Gui = 8,
     Unknown,
 }
 
@@ -92,6 +108,7 @@ impl From<u64> for DomainType {
             5 => DomainType::PID,
             6 => DomainType::RequestorUserDomain,
             7 => DomainType::RequestorDomain,
+            8 => DomainType::Gui,
             _ => DomainType::Unknown,
         }
     }
@@ -2,33 +2,53 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 
-#[macro_use]
+#[cfg_attr(target_os = "macos", macro_use)]
 extern crate lazy_static;
 
 #[macro_use]
 extern crate bitflags;
 
 pub use libc::MAP_SHARED;
-use libc::{geteuid, mach_task_self_, strerror, sysctlbyname, KERN_SUCCESS, MACH_PORT_NULL};
-use std::ffi::{CStr, CString};
+use libc::{geteuid, strerror};
+#[cfg(target_os = "macos")]
+use libc::{mach_task_self_, sysctlbyname, KERN_SUCCESS, MACH_PORT_NULL};
+use std::ffi::CStr;
+#[cfg(target_os = "macos")]
+use std::ffi::CString;
+#[cfg(target_os = "macos")]
 use std::os::raw::{c_char, c_int, c_long, c_void};
+#[cfg(target_os = "macos")]
 use std::ptr::null_mut;
 
+#[cfg(all(target_os = "macos", not(feature = "pregenerated-bindings")))]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+#[cfg(all(target_os = "macos", feature = "pregenerated-bindings"))]
+include!("bindings.rs");
+
+/// Port names in XPCValue, same as the SDK's on macOS
+#[cfg(not(target_os = "macos"))]
+pub type mach_port_t = std::os::raw::c_uint;
+
 //
 pub mod csr;
+pub mod domain_target;
 pub mod enums;
 pub mod objects;
+pub mod routines;
 pub mod traits;
+pub mod transport;
+pub mod xpc_serde;
 //
 
+#[cfg(target_os = "macos")]
 pub type xpc_pipe_t = *mut c_void;
 
 // Some extra private API definitions. Thanks:
 //
 // https://developer.apple.com/documentation/kernel/mach
 // https://chromium.googlesource.com/chromium/src.git/+/47.0.2507.2/sandbox/mac/xpc_private_stubs.sig
+#[cfg(target_os = "macos")]
 extern "C" {
     // Can decode i64 returned in "errors" for XPC responses
     pub fn xpc_strerror(err: c_int) -> *const c_char;
@@ -62,12 +82,14 @@ extern "C" {
     pub static _os_alloc_once_table: [_os_alloc_once_s; 10];
 }
 
+#[cfg(target_os = "macos")]
 #[repr(C)]
 pub struct _os_alloc_once_s {
     pub once: c_long,
     pub ptr: *mut c_void,
 }
 
+#[cfg(target_os = "macos")]
 #[repr(C)]
 pub struct xpc_global_data {
     pub a: u64,
@@ -76,6 +98,7 @@ pub struct xpc_global_data {
     pub xpc_bootstrap_pipe: xpc_pipe_t,
 }
 
+#[cfg(target_os = "macos")]
 pub fn rs_xpc_strerror(err: i32) -> String {
     unsafe {
         CStr::from_ptr(xpc_strerror(err))
@@ -89,6 +112,7 @@ pub fn rs_strerror(err: i32) -> String {
 }
 
 /// Attempt to yield existing bootstrap_port if not MACH_PORT_NULL
+#[cfg(target_os = "macos")]
 pub unsafe fn get_bootstrap_port() -> mach_port_t {
     if bootstrap_port == MACH_PORT_NULL as mach_port_t {
         log::debug!("Bootstrap port is null! Querying for port");
@@ -100,6 +124,7 @@ pub unsafe fn get_bootstrap_port() -> mach_port_t {
 }
 
 /// Look up bootstrap port for mach_task_self
+#[cfg(target_os = "macos")]
 pub unsafe fn lookup_bootstrap_port() -> mach_port_t {
     let mut num_ports: mach_msg_type_number_t = 0;
     let mut found_ports: *mut mach_port_t = null_mut();
@@ -131,6 +156,7 @@ pub unsafe fn lookup_bootstrap_port() -> mach_port_t {
 }
 
 /// Get xpc global data bootstrap pipe or find bootstrap port + create new pipe
+#[cfg(target_os = "macos")]
 pub unsafe fn get_xpc_bootstrap_pipe() -> xpc_pipe_t {
     match read_xpc_global_data() {
         Some(xpcgd) => {
@@ -149,11 +175,13 @@ pub unsafe fn get_xpc_bootstrap_pipe() -> xpc_pipe_t {
     }
 }
 
+#[cfg(target_os = "macos")]
 pub unsafe fn read_xpc_global_data() -> Option<&'static xpc_global_data> {
     let gd: *mut xpc_global_data = _os_alloc_once_table[1].ptr as *mut _;
     gd.as_ref()
 }
 
+#[cfg(target_os = "macos")]
 pub unsafe fn rs_sysctlbyname(name: &str) -> Result<String, String> {
     let name = CString::new(name).unwrap();
     let mut ret_buf: [c_char; 256] = [0; 256];
@@ -175,7 +203,7 @@ pub unsafe fn rs_sysctlbyname(name: &str) -> Result<String, String> {
     }
 }
 
-pub fn rs_geteuid() -> uid_t {
+pub fn rs_geteuid() -> libc::uid_t {
     unsafe { geteuid() }
 }
 
@@ -0,0 +1,177 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+/// What kind of failure a launchd error code is, for callers that
+/// want to react to it (e.g. offer sudo on NotPermitted)
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ErrorCategory {
+    NotPermitted,
+    AlreadyLoaded,
+    NotFound,
+    UnsupportedByDomain,
+    Disabled,
+    Other,
+}
+
+/// A numeric code from the "error" or "errors" key of a launchd reply.
+/// Codes below 107 are errno, the rest are launchd's own.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct LaunchdError {
+    /// None if launchd replied with something that isn't a code
+    pub code: Option<i64>,
+}
+
+impl LaunchdError {
+    pub fn new(code: i64) -> Self {
+        Self { code: Some(code) }
+    }
+
+    pub fn unknown() -> Self {
+        Self { code: None }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        let code = match self.code {
+            Some(code) => code,
+            None => return ErrorCategory::Other,
+        };
+
+        match code {
+            // EPERM, EACCES, lacks entitlement
+            1 | 13 | 144 => ErrorCategory::NotPermitted,
+            // EEXIST, EALREADY
+            17 | 37 => ErrorCategory::AlreadyLoaded,
+            // ENOENT, ESRCH, domain, service, user, group
+            2 | 3 | 112..=115 => ErrorCategory::NotFound,
+            // ENOTSUP, domain / request type / service doesn't support it
+            45 | 125 | 126 | 137 => ErrorCategory::UnsupportedByDomain,
+            119 => ErrorCategory::Disabled,
+            _ => ErrorCategory::Other,
+        }
+    }
+
+    /// Same text as xpc_strerror, from a table so it works without libxpc
+    pub fn message(&self) -> String {
+        match self.code {
+            Some(code) => strerror(code)
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| format!("Unknown error: {}", code)),
+            None => "Unknown error".to_string(),
+        }
+    }
+}
+
+impl Display for LaunchdError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "{}: {}", code, self.message()),
+            None => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+/// Offline xpc_strerror for the codes launchd is known to reply with
+pub fn strerror(code: i64) -> Option<&'static str> {
+    let message = match code {
+        1 => "Operation not permitted",
+        2 => "No such file or directory",
+        3 => "No such process",
+        5 => "Input/output error",
+        9 => "Bad file descriptor",
+        12 => "Cannot allocate memory",
+        13 => "Permission denied",
+        16 => "Resource busy",
+        17 => "File exists",
+        22 => "Invalid argument",
+        36 => "Operation now in progress",
+        37 => "Operation already in progress",
+        45 => "Operation not supported",
+        60 => "Operation timed out",
+        78 => "Function not implemented",
+        89 => "Operation canceled",
+        107 => "Malformed bundle",
+        108 => "Invalid path",
+        109 => "Invalid property list",
+        110 => "Invalid or missing service identifier",
+        111 => "Invalid or missing Program/ProgramArguments",
+        112 => "Could not find specified domain",
+        113 => "Could not find specified service",
+        114 => "The specified username does not exist",
+        115 => "The specified group does not exist",
+        116 => "Routine not yet implemented",
+        118 => "Bad response from server",
+        119 => "Service is disabled",
+        120 => "Bad subsystem destination for request",
+        121 => "Path not searched for services",
+        122 => "Path had bad ownership/permissions",
+        123 => "Path is whitelisted for domain",
+        124 => "Domain is tearing down",
+        125 => "Domain does not support specified action",
+        126 => "Request type is no longer supported",
+        127 => "The specified service did not ship with the operating system",
+        128 => "The specified path is not a bundle",
+        129 => "The service was superseded by a later version",
+        130 => "The system encountered a condition where behavior was undefined",
+        131 => "Out of order requests",
+        132 => "Request for stale data",
+        133 => "Multiple errors were returned; see stderr",
+        134 => "Service cannot load in requested session",
+        135 => "Process is not managed",
+        136 => "Action not allowed on singleton service",
+        137 => "Service does not support the specified action",
+        138 => "Service cannot be loaded on this hardware",
+        139 => "Service cannot presently execute",
+        140 => "Service name is reserved or invalid",
+        141 => "Reentrancy avoided",
+        142 => "Operation only supported on development",
+        143 => "Requested entry was cached",
+        144 => "Requestor lacks required entitlement",
+        _ => return None,
+    };
+
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::launchd_error::{ErrorCategory, LaunchdError};
+
+    #[test]
+    fn categories() {
+        assert_eq!(LaunchdError::new(1).category(), ErrorCategory::NotPermitted);
+        assert_eq!(
+            LaunchdError::new(37).category(),
+            ErrorCategory::AlreadyLoaded
+        );
+        assert_eq!(LaunchdError::new(113).category(), ErrorCategory::NotFound);
+        assert_eq!(
+            LaunchdError::new(125).category(),
+            ErrorCategory::UnsupportedByDomain
+        );
+        assert_eq!(LaunchdError::new(119).category(), ErrorCategory::Disabled);
+        assert_eq!(LaunchdError::new(5).category(), ErrorCategory::Other);
+        assert_eq!(LaunchdError::unknown().category(), ErrorCategory::Other);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            LaunchdError::new(1).to_string(),
+            "1: Operation not permitted"
+        );
+        assert_eq!(
+            LaunchdError::new(113).to_string(),
+            "113: Could not find specified service"
+        );
+        assert_eq!(
+            LaunchdError::new(9999).to_string(),
+            "9999: Unknown error: 9999"
+        );
+        assert_eq!(
+            LaunchdError::new(110).to_string(),
+            "110: Invalid or missing service identifier"
+        );
+        assert_eq!(LaunchdError::unknown().to_string(), "Unknown error");
+    }
+}
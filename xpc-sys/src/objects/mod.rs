@@ -1,12 +1,26 @@
 /// xpc_object_t
+#[cfg(target_os = "macos")]
 pub mod xpc_object;
 
 /// xpc_object_t XPC_TYPE_DICTIONARY
+#[cfg(target_os = "macos")]
 pub mod xpc_dictionary;
 
 /// xpc_object_t -> xpc_type_t
+#[cfg(target_os = "macos")]
 pub mod xpc_type;
 
+/// Owned XPC values that do not need libxpc
+pub mod xpc_value;
+
+/// xpc_copy_description text -> XPCValue
+pub mod xpc_description;
+
+/// launchd error codes, offline xpc_strerror
+pub mod launchd_error;
+
+#[cfg(target_os = "macos")]
 pub mod unix_fifo;
 pub mod xpc_error;
+#[cfg(target_os = "macos")]
 pub mod xpc_shmem;
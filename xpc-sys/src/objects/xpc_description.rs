@@ -0,0 +1,399 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::objects::xpc_error::XPCError;
+use crate::objects::xpc_error::XPCError::ValueError;
+use crate::objects::xpc_value::XPCValue;
+
+/// Parse the output of xpc_copy_description (and XPCObject's Display)
+/// back into an XPCValue. The whole input must be a single value, and
+/// dictionary / array counts must match their contents.
+///
+/// Descriptors and shmem regions can't be recovered from text: fds
+/// become XPCValue::Fd(-1), shmem becomes XPCValue::Shmem(0, size).
+pub fn parse_description(description: &str) -> Result<XPCValue, XPCError> {
+    let mut parser = DescriptionParser::new(description, 0, false);
+    let value = parser.value()?;
+
+    parser.skip_whitespace();
+    if parser.is_eof() {
+        Ok(value)
+    } else {
+        Err(parser.error("Unexpected trailing input"))
+    }
+}
+
+/// Find every top level dictionary in text such as a debugger
+/// transcript or doc/launchctl_messages.md. Truncated dumps (missing
+/// entries or closing braces) are accepted.
+pub fn find_descriptions(text: &str) -> Vec<XPCValue> {
+    let mut values = vec![];
+    let mut from = 0;
+
+    while let Some(offset) = text[from..].find("<dictionary:") {
+        let start = from + offset;
+        let mut parser = DescriptionParser::new(text, start, true);
+
+        match parser.value() {
+            Ok(value) => {
+                values.push(value);
+                from = parser.pos;
+            }
+            Err(e) => {
+                log::debug!("Skipping description at {}: {}", start, e);
+                from = start + 1;
+            }
+        }
+    }
+
+    values
+}
+
+impl FromStr for XPCValue {
+    type Err = XPCError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_description(s)
+    }
+}
+
+struct DescriptionParser<'a> {
+    src: &'a str,
+    pos: usize,
+    /// Stop containers at the first line that isn't an entry
+    lenient: bool,
+}
+
+impl<'a> DescriptionParser<'a> {
+    fn new(src: &'a str, pos: usize, lenient: bool) -> Self {
+        Self { src, pos, lenient }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn is_eof(&self) -> bool {
+        self.pos >= self.src.len()
+    }
+
+    fn error<S: AsRef<str>>(&self, message: S) -> XPCError {
+        let consumed = &self.src[..self.pos];
+        let line = consumed.matches('\n').count() + 1;
+        let column = consumed.len() - consumed.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+
+        ValueError(format!(
+            "{} at line {}, column {}",
+            message.as_ref(),
+            line,
+            column
+        ))
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), XPCError> {
+        if self.rest().starts_with(literal) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected {:?}", literal)))
+        }
+    }
+
+    /// Consume up to (not including) pattern
+    fn take_until(&mut self, pattern: &str) -> Result<&'a str, XPCError> {
+        match self.rest().find(pattern) {
+            Some(len) => {
+                let taken = &self.rest()[..len];
+                self.pos += len;
+                Ok(taken)
+            }
+            None => Err(self.error(format!("Expected {:?}", pattern))),
+        }
+    }
+
+    /// ": 3" up to the end of the line
+    fn scalar(&mut self) -> Result<&'a str, XPCError> {
+        self.expect(":")?;
+        let line = self.rest().split('\n').next().unwrap_or("");
+        self.pos += line.len();
+        Ok(line.trim())
+    }
+
+    fn parse_scalar<T: FromStr>(&mut self, type_name: &str) -> Result<T, XPCError> {
+        let start = self.pos;
+        let scalar = self.scalar()?;
+
+        scalar.parse().map_err(|_| {
+            self.pos = start;
+            self.error(format!("Cannot parse {:?} as {}", scalar, type_name))
+        })
+    }
+
+    /// " { name = 1799, right = send, urefs = 5 }"
+    fn fields(&mut self) -> Result<BTreeMap<&'a str, &'a str>, XPCError> {
+        self.skip_whitespace();
+        self.expect("{")?;
+        let body = self.take_until("}")?;
+        self.expect("}")?;
+
+        Ok(body
+            .split(", ")
+            .filter_map(|field| {
+                let mut kv = field.splitn(2, " = ");
+                Some((kv.next()?.trim(), kv.next()?.trim()))
+            })
+            .collect())
+    }
+
+    /// Header up to "contents =", returns count
+    fn container_header(&mut self) -> Result<usize, XPCError> {
+        self.skip_whitespace();
+        self.expect("{")?;
+        let header = self.take_until("contents =")?;
+        self.expect("contents =")?;
+
+        header
+            .split(',')
+            .filter_map(|field| field.trim().strip_prefix("count = "))
+            .next()
+            .and_then(|count| count.trim().parse().ok())
+            .ok_or_else(|| self.error("Missing count"))
+    }
+
+    /// true if the container is done, errors if strict and there is junk
+    fn container_end(&mut self, entry_start: char) -> Result<bool, XPCError> {
+        self.skip_whitespace();
+
+        match self.rest().chars().next() {
+            Some('}') => {
+                self.pos += 1;
+                Ok(true)
+            }
+            Some(c) if c == entry_start || (entry_start == '0' && c.is_ascii_digit()) => Ok(false),
+            _ if self.lenient => Ok(true),
+            None => Err(self.error("Unexpected end of input")),
+            Some(_) => Err(self.error("Expected entry or \"}\"")),
+        }
+    }
+
+    fn check_count(&self, expected: usize, actual: usize) -> Result<(), XPCError> {
+        if self.lenient || expected == actual {
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected {} entries, found {}", expected, actual)))
+        }
+    }
+
+    fn value(&mut self) -> Result<XPCValue, XPCError> {
+        self.expect("<")?;
+        let type_name = self.take_until(":")?;
+        self.expect(":")?;
+        self.take_until(">")?;
+        self.expect(">")?;
+
+        match type_name {
+            "dictionary" => self.dictionary(),
+            "array" => self.array(),
+            "string" => self.string(),
+            "bool" => self.parse_scalar("bool").map(XPCValue::Bool),
+            "int64" => self.parse_scalar("int64").map(XPCValue::Int64),
+            "uint64" => self.parse_scalar("uint64").map(XPCValue::UInt64),
+            "double" => self.parse_scalar("double").map(XPCValue::Double),
+            "null" => self.null(),
+            "fd" => self.fields().map(|_| XPCValue::Fd(-1)),
+            "mach send right" => self.port_name().map(XPCValue::MachSend),
+            "mach recv right" | "mach receive right" => self.port_name().map(XPCValue::MachRecv),
+            "shmem" => self.shmem(),
+            other => Err(self.error(format!("Unsupported type {}", other))),
+        }
+    }
+
+    fn dictionary(&mut self) -> Result<XPCValue, XPCError> {
+        let count = self.container_header()?;
+        let mut map = BTreeMap::new();
+
+        while !self.container_end('"')? {
+            self.expect("\"")?;
+            let key = self.take_until("\" => ")?;
+            self.expect("\" => ")?;
+            map.insert(key.to_string(), self.value()?);
+        }
+
+        self.check_count(count, map.len())?;
+        Ok(XPCValue::Dictionary(map))
+    }
+
+    fn array(&mut self) -> Result<XPCValue, XPCError> {
+        let count = self.container_header()?;
+        let mut items = vec![];
+
+        while !self.container_end('0')? {
+            let index = self.take_until(":")?;
+            if index.parse() != Ok(items.len()) {
+                return Err(self.error(format!("Unexpected index {}", index)));
+            }
+
+            self.expect(":")?;
+            self.skip_whitespace();
+            items.push(self.value()?);
+        }
+
+        self.check_count(count, items.len())?;
+        Ok(XPCValue::Array(items))
+    }
+
+    /// Contents aren't escaped, so rely on length instead of the closing quote
+    fn string(&mut self) -> Result<XPCValue, XPCError> {
+        self.skip_whitespace();
+        self.expect("{ length = ")?;
+        let length: usize = self
+            .take_until(",")?
+            .parse()
+            .map_err(|_| self.error("Bad string length"))?;
+        self.expect(", contents = \"")?;
+
+        let contents = self
+            .rest()
+            .get(..length)
+            .ok_or_else(|| self.error("String shorter than its length"))?;
+
+        self.pos += length;
+        self.expect("\" }")?;
+
+        Ok(XPCValue::String(contents.to_string()))
+    }
+
+    /// Sometimes followed by ": null"
+    fn null(&mut self) -> Result<XPCValue, XPCError> {
+        if self.rest().starts_with(':') {
+            self.scalar()?;
+        }
+
+        Ok(XPCValue::Null)
+    }
+
+    fn port_name(&mut self) -> Result<u32, XPCError> {
+        let fields = self.fields()?;
+
+        fields
+            .get("name")
+            .and_then(|name| name.parse().ok())
+            .ok_or_else(|| self.error("Missing port name"))
+    }
+
+    /// ": 20971520 bytes (5121 pages)"
+    fn shmem(&mut self) -> Result<XPCValue, XPCError> {
+        let scalar = self.scalar()?;
+
+        scalar
+            .split(' ')
+            .next()
+            .and_then(|size| size.parse().ok())
+            .map(|size| XPCValue::Shmem(0, size))
+            .ok_or_else(|| self.error(format!("Cannot parse {:?} as shmem", scalar)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::xpc_description::{find_descriptions, parse_description};
+    use crate::objects::xpc_value::XPCValue;
+
+    const LAUNCHCTL_MESSAGES: &str = include_str!("../../../doc/launchctl_messages.md");
+
+    const LIST_RESPONSE: &str = r#"<dictionary: 0x7fcc6462db60> { count = 3, transaction: 0, voucher = 0x0, contents =
+	"Sockets" => <dictionary: 0x7fcc6462dc80> { count = 1, transaction: 0, voucher = 0x0, contents =
+		"Listeners" => <array: 0x7fcc64630370> { count = 1, capacity = 1, contents =
+			0: <fd: 0x7fcc6462e3c0> { type = (invalid descriptor), path = (invalid path) }
+		}
+	}
+	"Label" => <string: 0x7fcc6462de00> { length = 20, contents = "com.apple."quoted" }" }
+	"PID" => <int64: 0x97301c3994c9c1b1>: 165
+}"#;
+
+    #[test]
+    fn parse_nested() {
+        let value = parse_description(LIST_RESPONSE).unwrap();
+
+        assert_eq!(value.get(["PID"]).unwrap(), &XPCValue::Int64(165));
+        assert_eq!(
+            value.get(["Label"]).unwrap(),
+            &XPCValue::String("com.apple.\"quoted\" }".to_string())
+        );
+        assert_eq!(
+            value.get(["Sockets", "Listeners"]).unwrap(),
+            &XPCValue::Array(vec![XPCValue::Fd(-1)])
+        );
+    }
+
+    #[test]
+    fn parse_strict_count() {
+        let truncated = LIST_RESPONSE.replace("count = 3", "count = 4");
+        assert!(parse_description(&truncated).is_err());
+    }
+
+    #[test]
+    fn parse_strict_unclosed() {
+        let unclosed = LIST_RESPONSE.trim_end_matches('}');
+        let err = parse_description(unclosed).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unexpected end of input at line 9, column 1"
+        );
+    }
+
+    #[test]
+    fn parse_from_str() {
+        let value: XPCValue = "<bool: 0x7fff800130b0>: true".parse().unwrap();
+        assert_eq!(value, XPCValue::Bool(true));
+    }
+
+    #[test]
+    fn launchctl_messages() {
+        let messages = find_descriptions(LAUNCHCTL_MESSAGES);
+
+        assert_eq!(
+            messages.len(),
+            LAUNCHCTL_MESSAGES.matches("<dictionary:").count()
+        );
+
+        let print_system = &messages[0];
+        assert_eq!(
+            print_system.get(["routine"]).unwrap(),
+            &XPCValue::UInt64(828)
+        );
+        assert_eq!(print_system.get(["fd"]).unwrap(), &XPCValue::Fd(-1));
+
+        let with_port = messages
+            .iter()
+            .find(|m| m.get(["domain-port"]).is_ok())
+            .unwrap();
+        assert_eq!(
+            with_port.get(["domain-port"]).unwrap(),
+            &XPCValue::MachSend(1799)
+        );
+
+        let unload = messages
+            .iter()
+            .find(|m| m.get(["legacy-load"]).is_ok())
+            .unwrap();
+        assert_eq!(
+            unload.get(["paths"]).unwrap(),
+            &XPCValue::Array(vec![XPCValue::String(
+                "/Users/mach/Library/LaunchAgents/homebrew.mxcl.elasticsearch.plist".to_string()
+            )])
+        );
+
+        let dumpstate = messages.iter().find(|m| m.get(["shmem"]).is_ok()).unwrap();
+        assert_eq!(
+            dumpstate.get(["shmem"]).unwrap(),
+            &XPCValue::Shmem(0, 20971520)
+        );
+        assert_eq!(dumpstate.get(["routine"]).unwrap(), &XPCValue::UInt64(834));
+    }
+}
@@ -210,7 +210,8 @@ mod tests {
 
         let XPCDictionary(map) = raw_dict.try_into().unwrap();
         if let Some(xpc_object) = map.get("test") {
-            assert_eq!(value, xpc_object.xpc_value().unwrap());
+            let rs_value: i64 = xpc_object.xpc_value().unwrap();
+            assert_eq!(value, rs_value);
         } else {
             panic!("Unable to get value from map");
         }
@@ -1,19 +1,47 @@
+use crate::objects::launchd_error::{ErrorCategory, LaunchdError};
 use crate::objects::xpc_error::XPCError::{
     DictionaryError, IOError, PipeError, QueryError, ValueError,
 };
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum XPCError {
     DictionaryError(String),
     PipeError(String),
     ValueError(String),
     QueryError(String),
     IOError(String),
+    /// Reply had an "error" key
+    LaunchdError(LaunchdError),
+    /// Reply had a non-empty "errors" dictionary, keyed by path or label
+    LaunchdErrors(BTreeMap<String, LaunchdError>),
     NotFound,
 }
 
+impl XPCError {
+    /// Category of a launchd error reply. For "errors", the category
+    /// they all share, or Other if they differ.
+    pub fn category(&self) -> Option<ErrorCategory> {
+        match self {
+            XPCError::LaunchdError(e) => Some(e.category()),
+            XPCError::LaunchdErrors(errors) => {
+                let mut categories = errors.values().map(|e| e.category());
+                let first = categories.next()?;
+
+                if categories.all(|c| c == first) {
+                    Some(first)
+                } else {
+                    Some(ErrorCategory::Other)
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
 impl Display for XPCError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let err = match self {
@@ -22,6 +50,15 @@ impl Display for XPCError {
             QueryError(e) => e,
             ValueError(e) => e,
             IOError(e) => e,
+            XPCError::LaunchdError(e) => return write!(f, "{}", e),
+            XPCError::LaunchdErrors(errors) => {
+                let lines: Vec<String> = errors
+                    .iter()
+                    .map(|(key, e)| format!("{}: {}", key, e))
+                    .collect();
+
+                return write!(f, "{}", lines.join("\n"));
+            }
             _ => "",
         };
 
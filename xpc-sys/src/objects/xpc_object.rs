@@ -1,12 +1,12 @@
 use libc::c_int;
 
+use crate::mach_port_t;
 use crate::objects::xpc_type::XPCType;
 use crate::{
     xpc_array_append_value, xpc_array_create, xpc_bool_create, xpc_copy, xpc_copy_description,
     xpc_double_create, xpc_fd_create, xpc_int64_create, xpc_mach_recv_create, xpc_mach_send_create,
     xpc_object_t, xpc_release, xpc_string_create, xpc_uint64_create,
 };
-use libc::mach_port_t;
 use std::ffi::{CStr, CString};
 use std::os::unix::prelude::RawFd;
 use std::ptr::null_mut;
@@ -128,12 +128,7 @@ impl From<f64> for XPCObject {
     }
 }
 
-/// Enum used for selecting between _xpc_type_mach_send and _xpc_type_mach_recv
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub enum MachPortType {
-    Send,
-    Recv,
-}
+pub use crate::enums::MachPortType;
 
 impl From<(MachPortType, mach_port_t)> for XPCObject {
     /// Create XPCObject via xpc_mach_send_create or xpc_mach_recv_create
@@ -217,7 +212,7 @@ impl Drop for XPCObject {
 
         if *ptr == null_mut() {
             log::info!("XPCObject xpc_object_t is NULL, not calling xpc_release()");
-            return 
+            return;
         }
 
         log::info!(
@@ -238,7 +233,7 @@ impl Drop for XPCObject {
 mod tests {
     use std::os::unix::prelude::RawFd;
 
-    use libc::mach_port_t;
+    use crate::mach_port_t;
 
     use crate::get_bootstrap_port;
     use crate::objects::xpc_dictionary::XPCDictionary;
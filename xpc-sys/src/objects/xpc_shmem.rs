@@ -2,10 +2,17 @@ use crate::objects::xpc_error::XPCError;
 use crate::objects::xpc_object::XPCObject;
 use crate::{rs_strerror, vm_allocate, xpc_shmem_create};
 use libc::{mach_port_t, mach_task_self_, vm_address_t, vm_deallocate, vm_size_t};
+use std::collections::HashMap;
 use std::ffi::c_void;
 use std::os::raw::c_int;
 use std::ptr::null_mut;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+
+lazy_static! {
+    /// Regions of live XPCShmems (address -> size), the only memory an
+    /// XPCValue::Shmem can be made into an XPCObject for
+    static ref LIVE_REGIONS: Mutex<HashMap<usize, vm_size_t>> = Mutex::new(HashMap::new());
+}
 
 /// Wrapper around vm_allocate() vm_deallocate() with an XPCObject
 /// member of XPC type _xpc_type_shmem
@@ -45,6 +52,11 @@ impl XPCShmem {
                 xpc_object.as_ptr()
             );
 
+            LIVE_REGIONS
+                .lock()
+                .expect("Must lock live regions")
+                .insert(region as usize, size);
+
             Ok(XPCShmem {
                 task,
                 size,
@@ -60,6 +72,19 @@ impl XPCShmem {
     pub fn new_task_self(size: vm_size_t, flags: c_int) -> Result<XPCShmem, XPCError> {
         unsafe { Self::new(mach_task_self_, size, flags) }
     }
+
+    /// A new _xpc_type_shmem for region..region + size, None unless
+    /// that's inside the region of an XPCShmem that's not dropped
+    pub(crate) fn object_for(region: usize, size: usize) -> Option<XPCObject> {
+        let regions = LIVE_REGIONS.lock().expect("Must lock live regions");
+        let allocated = *regions.get(&region)?;
+
+        if size > allocated {
+            return None;
+        }
+
+        Some(unsafe { xpc_shmem_create(region as *mut c_void, size).into() })
+    }
 }
 
 impl Drop for XPCShmem {
@@ -76,6 +101,11 @@ impl Drop for XPCShmem {
             xpc_object.as_ptr()
         );
 
+        LIVE_REGIONS
+            .lock()
+            .expect("Must lock live regions")
+            .remove(&(*region as usize));
+
         let ok = unsafe { vm_deallocate(*task, *region as vm_address_t, *size) };
 
         if ok != 0 {
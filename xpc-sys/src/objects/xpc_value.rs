@@ -0,0 +1,447 @@
+use std::collections::{BTreeMap, HashMap};
+use std::os::unix::prelude::RawFd;
+
+use serde::{Deserialize, Serialize};
+
+use crate::enums::MachPortType;
+use crate::mach_port_t;
+use crate::objects::xpc_error::XPCError;
+use crate::objects::xpc_error::XPCError::ValueError;
+
+#[cfg(target_os = "macos")]
+use {
+    crate::objects::xpc_dictionary::XPCDictionary,
+    crate::objects::xpc_object::XPCObject,
+    crate::objects::xpc_shmem::XPCShmem,
+    crate::objects::xpc_type,
+    crate::traits::xpc_value::TryXPCValue,
+    crate::{
+        xpc_data_create, xpc_data_get_bytes_ptr, xpc_data_get_length, xpc_date_create,
+        xpc_date_get_value, xpc_null_create, xpc_uuid_create, xpc_uuid_get_bytes,
+    },
+    std::convert::{TryFrom, TryInto},
+    std::ffi::c_void,
+    std::slice,
+    std::sync::Arc,
+};
+
+/// An owned copy of an XPC object tree. Unlike XPCObject there is no
+/// xpc_object_t underneath, so values can be built, compared and
+/// inspected without libxpc.
+///
+/// Serializes externally tagged by type_name(), e.g. {"uint64": 3}, so
+/// recorded sessions keep int64 / uint64 / data etc. apart
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum XPCValue {
+    #[serde(rename = "null")]
+    Null,
+    #[serde(rename = "bool")]
+    Bool(bool),
+    #[serde(rename = "int64")]
+    Int64(i64),
+    #[serde(rename = "uint64")]
+    UInt64(u64),
+    #[serde(rename = "double")]
+    Double(f64),
+    #[serde(rename = "string")]
+    String(String),
+    #[serde(rename = "data")]
+    Data(Vec<u8>),
+    /// Nanoseconds since the UNIX epoch
+    #[serde(rename = "date")]
+    Date(i64),
+    #[serde(rename = "uuid")]
+    Uuid([u8; 16]),
+    #[serde(rename = "array")]
+    Array(Vec<XPCValue>),
+    #[serde(rename = "dictionary")]
+    Dictionary(BTreeMap<String, XPCValue>),
+    /// Placeholder for _xpc_type_fd, the descriptor only has meaning
+    /// in the process that owns it. Copies of received objects are -1,
+    /// the descriptor isn't duplicated
+    #[serde(rename = "fd")]
+    Fd(RawFd),
+    /// Placeholder for _xpc_type_mach_send (port name)
+    #[serde(rename = "mach_send")]
+    MachSend(u32),
+    /// Placeholder for _xpc_type_mach_recv (port name)
+    #[serde(rename = "mach_recv")]
+    MachRecv(u32),
+    /// Placeholder for _xpc_type_shmem (region address, size), see XPCShmem.
+    /// Only the region of a live XPCShmem makes it into an XPCObject
+    #[serde(rename = "shmem")]
+    Shmem(usize, usize),
+}
+
+impl XPCValue {
+    /// Empty XPC_TYPE_DICTIONARY, to use with QueryBuilder
+    pub fn new_dictionary() -> Self {
+        XPCValue::Dictionary(BTreeMap::new())
+    }
+
+    /// Same names as xpc_type_get_name
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            XPCValue::Null => "null",
+            XPCValue::Bool(_) => "bool",
+            XPCValue::Int64(_) => "int64",
+            XPCValue::UInt64(_) => "uint64",
+            XPCValue::Double(_) => "double",
+            XPCValue::String(_) => "string",
+            XPCValue::Data(_) => "data",
+            XPCValue::Date(_) => "date",
+            XPCValue::Uuid(_) => "uuid",
+            XPCValue::Array(_) => "array",
+            XPCValue::Dictionary(_) => "dictionary",
+            XPCValue::Fd(_) => "fd",
+            XPCValue::MachSend(_) => "mach_send",
+            XPCValue::MachRecv(_) => "mach_recv",
+            XPCValue::Shmem(_, _) => "shmem",
+        }
+    }
+
+    /// Get value from a dictionary with support for nesting
+    pub fn get<I, S>(&self, items: I) -> Result<&XPCValue, XPCError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut iter = items.into_iter().peekable();
+
+        if iter.peek().is_none() {
+            return Err(ValueError("Not enough elements".to_string()));
+        }
+
+        iter.try_fold(self, |value, key| {
+            let key = key.as_ref();
+
+            match value {
+                XPCValue::Dictionary(map) => map
+                    .get(key)
+                    .ok_or_else(|| XPCError::DictionaryError(format!("Can't get {}", key))),
+                other => Err(XPCError::DictionaryError(format!(
+                    "Can't get {} from {}",
+                    key,
+                    other.type_name()
+                ))),
+            }
+        })
+    }
+
+    /// Retrieve a dictionary
+    pub fn get_as_dictionary<I, S>(&self, items: I) -> Result<&BTreeMap<String, XPCValue>, XPCError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        match self.get(items)? {
+            XPCValue::Dictionary(map) => Ok(map),
+            other => Err(XPCError::DictionaryError(format!(
+                "Only XPC_TYPE_DICTIONARY allowed, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+}
+
+impl From<i64> for XPCValue {
+    fn from(value: i64) -> Self {
+        XPCValue::Int64(value)
+    }
+}
+
+impl From<u64> for XPCValue {
+    fn from(value: u64) -> Self {
+        XPCValue::UInt64(value)
+    }
+}
+
+impl From<f64> for XPCValue {
+    fn from(value: f64) -> Self {
+        XPCValue::Double(value)
+    }
+}
+
+impl From<bool> for XPCValue {
+    fn from(value: bool) -> Self {
+        XPCValue::Bool(value)
+    }
+}
+
+impl From<&str> for XPCValue {
+    fn from(value: &str) -> Self {
+        XPCValue::String(value.to_string())
+    }
+}
+
+impl From<String> for XPCValue {
+    fn from(value: String) -> Self {
+        XPCValue::String(value)
+    }
+}
+
+impl From<RawFd> for XPCValue {
+    fn from(value: RawFd) -> Self {
+        XPCValue::Fd(value)
+    }
+}
+
+impl From<(MachPortType, mach_port_t)> for XPCValue {
+    fn from((mpt, value): (MachPortType, mach_port_t)) -> Self {
+        match mpt {
+            MachPortType::Send => XPCValue::MachSend(value),
+            MachPortType::Recv => XPCValue::MachRecv(value),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl From<&XPCShmem> for XPCValue {
+    /// Refer to the region of an existing XPCShmem, which must outlive
+    /// any XPCObject made from this value
+    fn from(shmem: &XPCShmem) -> Self {
+        XPCValue::Shmem(shmem.region as usize, shmem.size)
+    }
+}
+
+impl<O: Into<XPCValue>> From<Vec<O>> for XPCValue {
+    fn from(value: Vec<O>) -> Self {
+        XPCValue::Array(value.into_iter().map(|o| o.into()).collect())
+    }
+}
+
+impl<S: Into<String>, O: Into<XPCValue>> From<BTreeMap<S, O>> for XPCValue {
+    fn from(value: BTreeMap<S, O>) -> Self {
+        XPCValue::Dictionary(
+            value
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
+impl<S: Into<String>, O: Into<XPCValue>> From<HashMap<S, O>> for XPCValue {
+    fn from(value: HashMap<S, O>) -> Self {
+        XPCValue::Dictionary(
+            value
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl TryFrom<&XPCObject> for XPCValue {
+    type Error = XPCError;
+
+    /// Deep copy an XPC object into an owned value
+    fn try_from(object: &XPCObject) -> Result<XPCValue, XPCError> {
+        let t = object.xpc_type();
+        let ptr = object.as_ptr();
+
+        if t == *xpc_type::Null {
+            Ok(XPCValue::Null)
+        } else if t == *xpc_type::Bool {
+            object.xpc_value().map(XPCValue::Bool)
+        } else if t == *xpc_type::Int64 {
+            object.xpc_value().map(XPCValue::Int64)
+        } else if t == *xpc_type::UInt64 {
+            object.xpc_value().map(XPCValue::UInt64)
+        } else if t == *xpc_type::Double {
+            object.xpc_value().map(XPCValue::Double)
+        } else if t == *xpc_type::String {
+            object.xpc_value().map(XPCValue::String)
+        } else if t == *xpc_type::Data {
+            let bytes = unsafe {
+                let len = xpc_data_get_length(ptr);
+                let bytes = xpc_data_get_bytes_ptr(ptr) as *const u8;
+
+                if bytes.is_null() || len == 0 {
+                    vec![]
+                } else {
+                    slice::from_raw_parts(bytes, len).to_vec()
+                }
+            };
+
+            Ok(XPCValue::Data(bytes))
+        } else if t == *xpc_type::Date {
+            Ok(XPCValue::Date(unsafe { xpc_date_get_value(ptr) }))
+        } else if t == *xpc_type::Uuid {
+            let mut uuid = [0u8; 16];
+            uuid.copy_from_slice(unsafe { slice::from_raw_parts(xpc_uuid_get_bytes(ptr), 16) });
+            Ok(XPCValue::Uuid(uuid))
+        } else if t == *xpc_type::Array {
+            let items: Vec<Arc<XPCObject>> = object.xpc_value()?;
+            items
+                .iter()
+                .map(|o| XPCValue::try_from(&**o))
+                .collect::<Result<Vec<XPCValue>, XPCError>>()
+                .map(XPCValue::Array)
+        } else if t == *xpc_type::Dictionary {
+            let dict: XPCDictionary = object.try_into()?;
+            XPCValue::try_from(&dict)
+        } else if t == *xpc_type::Fd {
+            // xpc_fd_dup would hand over a descriptor nothing closes
+            Ok(XPCValue::Fd(-1))
+        } else if t == *xpc_type::MachSend || t == *xpc_type::MachRecv {
+            let port: (MachPortType, mach_port_t) = object.xpc_value()?;
+            Ok(port.into())
+        } else {
+            Err(ValueError(format!("Cannot copy {} into XPCValue", t)))
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl TryFrom<XPCObject> for XPCValue {
+    type Error = XPCError;
+
+    fn try_from(object: XPCObject) -> Result<XPCValue, XPCError> {
+        XPCValue::try_from(&object)
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl TryFrom<Arc<XPCObject>> for XPCValue {
+    type Error = XPCError;
+
+    fn try_from(object: Arc<XPCObject>) -> Result<XPCValue, XPCError> {
+        XPCValue::try_from(&*object)
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl TryFrom<&XPCDictionary> for XPCValue {
+    type Error = XPCError;
+
+    fn try_from(XPCDictionary(hm): &XPCDictionary) -> Result<XPCValue, XPCError> {
+        hm.iter()
+            .map(|(k, v)| XPCValue::try_from(&**v).map(|v| (k.clone(), v)))
+            .collect::<Result<BTreeMap<String, XPCValue>, XPCError>>()
+            .map(XPCValue::Dictionary)
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl From<&XPCValue> for XPCObject {
+    /// Create a new XPC object tree from an owned value
+    fn from(value: &XPCValue) -> Self {
+        match value {
+            XPCValue::Null => unsafe { xpc_null_create().into() },
+            XPCValue::Bool(b) => (*b).into(),
+            XPCValue::Int64(i) => (*i).into(),
+            XPCValue::UInt64(u) => (*u).into(),
+            XPCValue::Double(d) => (*d).into(),
+            XPCValue::String(s) => s.as_str().into(),
+            XPCValue::Data(bytes) => unsafe {
+                xpc_data_create(bytes.as_ptr() as *const c_void, bytes.len()).into()
+            },
+            XPCValue::Date(ns) => unsafe { xpc_date_create(*ns).into() },
+            XPCValue::Uuid(uuid) => unsafe { xpc_uuid_create(uuid.as_ptr()).into() },
+            XPCValue::Array(items) => items
+                .iter()
+                .map(XPCObject::from)
+                .collect::<Vec<XPCObject>>()
+                .into(),
+            XPCValue::Dictionary(map) => map
+                .iter()
+                .map(|(k, v)| (k.clone(), Arc::new(XPCObject::from(v))))
+                .collect::<HashMap<String, Arc<XPCObject>>>()
+                .into(),
+            XPCValue::Fd(fd) => (*fd).into(),
+            XPCValue::MachSend(port) => (MachPortType::Send, *port).into(),
+            XPCValue::MachRecv(port) => (MachPortType::Recv, *port).into(),
+            XPCValue::Shmem(region, size) => {
+                XPCShmem::object_for(*region, *size).unwrap_or_else(|| {
+                    log::error!("No XPCShmem at {:#x} for {} bytes", region, size);
+                    unsafe { xpc_null_create().into() }
+                })
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl From<XPCValue> for XPCObject {
+    fn from(value: XPCValue) -> Self {
+        (&value).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::objects::xpc_error::XPCError;
+    use crate::objects::xpc_value::XPCValue;
+    use crate::traits::xpc_value::TryXPCValue;
+
+    fn service_reply() -> XPCValue {
+        let mut service: BTreeMap<&str, XPCValue> = BTreeMap::new();
+        service.insert("PID", XPCValue::Int64(165));
+        service.insert("Label", "com.apple.usbmuxd".into());
+        service.insert("ProgramArguments", vec!["usbmuxd", "-launchd"].into());
+
+        let mut reply: BTreeMap<&str, XPCValue> = BTreeMap::new();
+        reply.insert("service", service.into());
+        reply.into()
+    }
+
+    #[test]
+    fn get_nested() {
+        let reply = service_reply();
+        let pid: i64 = reply.get(["service", "PID"]).unwrap().xpc_value().unwrap();
+        assert_eq!(pid, 165);
+    }
+
+    #[test]
+    fn get_missing() {
+        let reply = service_reply();
+
+        assert_eq!(
+            reply.get(["service", "Nope"]).err().unwrap(),
+            XPCError::DictionaryError("Can't get Nope".to_string())
+        );
+
+        assert_eq!(
+            reply.get(["service", "PID", "Nope"]).err().unwrap(),
+            XPCError::DictionaryError("Can't get Nope from int64".to_string())
+        );
+    }
+
+    #[test]
+    fn value_with_wrong_type() {
+        let as_u64: Result<u64, XPCError> = XPCValue::Int64(42).xpc_value();
+
+        assert_eq!(
+            as_u64.err().unwrap(),
+            XPCError::ValueError("Cannot get int64 as uint64".to_string())
+        );
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn round_trip_xpc_object() {
+        use crate::objects::xpc_object::XPCObject;
+        use std::convert::TryFrom;
+
+        let mut dict: BTreeMap<&str, XPCValue> = BTreeMap::new();
+        dict.insert("null", XPCValue::Null);
+        dict.insert("bool", true.into());
+        dict.insert("i64", std::i64::MIN.into());
+        dict.insert("u64", std::u64::MAX.into());
+        dict.insert("f64", 4.2f64.into());
+        dict.insert("string", "polizei".into());
+        dict.insert("data", XPCValue::Data(vec![0xde, 0xad, 0xbe, 0xef]));
+        dict.insert("date", XPCValue::Date(1_621_382_697_000_000_000));
+        dict.insert("uuid", XPCValue::Uuid([7; 16]));
+        dict.insert("array", vec![service_reply(), XPCValue::Int64(1)].into());
+
+        let value: XPCValue = dict.into();
+        let object = XPCObject::from(&value);
+
+        assert_eq!(XPCValue::try_from(&object).unwrap(), value);
+    }
+}
@@ -0,0 +1,417 @@
+use std::fmt;
+
+use crate::objects::xpc_error::XPCError;
+use crate::objects::xpc_value::XPCValue;
+use crate::traits::query_builder::QueryBuilder;
+use crate::traits::xpc_transport::XPCTransport;
+use crate::traits::xpc_value::TryXPCValue;
+
+/// A request key and its XPCValue::type_name()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoutineKey {
+    pub name: &'static str,
+    pub type_name: &'static str,
+}
+
+const fn key(name: &'static str, type_name: &'static str) -> RoutineKey {
+    RoutineKey { name, type_name }
+}
+
+const TYPE: RoutineKey = key("type", "uint64");
+const HANDLE: RoutineKey = key("handle", "uint64");
+const DOMAIN_PORT: RoutineKey = key("domain-port", "mach_send");
+const LEGACY: RoutineKey = key("legacy", "bool");
+const FD: RoutineKey = key("fd", "fd");
+const SHMEM: RoutineKey = key("shmem", "shmem");
+
+/// What comes back, besides "error" on failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyShape {
+    /// "services" (label -> service dictionary), or "service" if "name" was sent
+    Services,
+    /// "errors" keyed by path or label, empty on success
+    Errors,
+    /// Text goes to the request's fd, or its shmem with "bytes-written" (uint64)
+    Output,
+    /// The port right for "name" in "process"
+    Port,
+}
+
+/// Everything known about a routine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoutineSpec {
+    pub subsystem: u64,
+    pub routine: u64,
+    /// launchctl command that sends it
+    pub command: &'static str,
+    pub required: &'static [RoutineKey],
+    /// At least one of these is needed (where output goes)
+    pub one_of: &'static [RoutineKey],
+    pub optional: &'static [RoutineKey],
+    pub reply: ReplyShape,
+}
+
+/// launchd routines used by launchctl, see doc/launchctl_messages.md
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Routine {
+    List,
+    Load,
+    Unload,
+    Enable,
+    Disable,
+    Dumpstate,
+    Dumpjpcategory,
+    Procinfo,
+    PrintService,
+    PrintDomain,
+    PrintCache,
+    PortLookup,
+}
+
+impl Routine {
+    pub const ALL: [Routine; 12] = [
+        Routine::List,
+        Routine::Load,
+        Routine::Unload,
+        Routine::Enable,
+        Routine::Disable,
+        Routine::Dumpstate,
+        Routine::Dumpjpcategory,
+        Routine::Procinfo,
+        Routine::PrintService,
+        Routine::PrintDomain,
+        Routine::PrintCache,
+        Routine::PortLookup,
+    ];
+
+    pub fn spec(self) -> &'static RoutineSpec {
+        match self {
+            Routine::List => &LIST,
+            Routine::Load => &LOAD,
+            Routine::Unload => &UNLOAD,
+            Routine::Enable => &ENABLE,
+            Routine::Disable => &DISABLE,
+            Routine::Dumpstate => &DUMPSTATE,
+            Routine::Dumpjpcategory => &DUMPJPCATEGORY,
+            Routine::Procinfo => &PROCINFO,
+            Routine::PrintService => &PRINT_SERVICE,
+            Routine::PrintDomain => &PRINT_DOMAIN,
+            Routine::PrintCache => &PRINT_CACHE,
+            Routine::PortLookup => &PORT_LOOKUP,
+        }
+    }
+
+    /// Which routine a request is for. 708 is procinfo if there is a
+    /// "pid", print otherwise.
+    pub fn from_request(request: &XPCValue) -> Option<Routine> {
+        match numbers(request)? {
+            (2, 708) if request.get(["pid"]).is_ok() => Some(Routine::Procinfo),
+            (2, 708) => Some(Routine::PrintService),
+            found => Routine::ALL.iter().copied().find(|r| {
+                let spec = r.spec();
+                (spec.subsystem, spec.routine) == found
+            }),
+        }
+    }
+
+    /// Dictionary with only "subsystem" and "routine", to extend with QueryBuilder
+    pub fn request(self) -> XPCValue {
+        let spec = self.spec();
+
+        XPCValue::new_dictionary()
+            .entry("subsystem", spec.subsystem)
+            .entry("routine", spec.routine)
+    }
+
+    /// Check subsystem / routine, that required keys are present and that
+    /// known keys have the right type
+    pub fn validate(self, request: &XPCValue) -> Result<(), XPCError> {
+        let spec = self.spec();
+
+        if numbers(request) != Some((spec.subsystem, spec.routine)) {
+            return Err(XPCError::QueryError(format!(
+                "{}: not a request for subsystem {} routine {}",
+                self, spec.subsystem, spec.routine
+            )));
+        }
+
+        let missing: Vec<&str> = spec
+            .required
+            .iter()
+            .filter(|k| request.get([k.name]).is_err())
+            .map(|k| k.name)
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(XPCError::QueryError(format!(
+                "{}: missing required {}",
+                self,
+                missing.join(", ")
+            )));
+        }
+
+        if !spec.one_of.is_empty() && spec.one_of.iter().all(|k| request.get([k.name]).is_err()) {
+            let names: Vec<&str> = spec.one_of.iter().map(|k| k.name).collect();
+            return Err(XPCError::QueryError(format!(
+                "{}: needs one of {}",
+                self,
+                names.join(", ")
+            )));
+        }
+
+        let known = spec
+            .required
+            .iter()
+            .chain(spec.one_of.iter())
+            .chain(spec.optional.iter());
+
+        for k in known {
+            match request.get([k.name]) {
+                Ok(value) if value.type_name() != k.type_name => {
+                    return Err(XPCError::QueryError(format!(
+                        "{}: {} must be {}, got {}",
+                        self,
+                        k.name,
+                        k.type_name,
+                        value.type_name()
+                    )))
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate, then send with pipe_routine_with_error_handling
+    pub fn pipe<T: XPCTransport + ?Sized>(
+        self,
+        transport: &T,
+        request: &XPCValue,
+    ) -> Result<XPCValue, XPCError> {
+        self.validate(request)?;
+        transport.pipe_routine_with_error_handling(request)
+    }
+}
+
+impl fmt::Display for Routine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let spec = self.spec();
+        write!(f, "{} ({})", spec.command, spec.routine)
+    }
+}
+
+fn numbers(request: &XPCValue) -> Option<(u64, u64)> {
+    let subsystem: u64 = request.get(["subsystem"]).ok()?.xpc_value().ok()?;
+    let routine: u64 = request.get(["routine"]).ok()?.xpc_value().ok()?;
+    Some((subsystem, routine))
+}
+
+const LOAD_OPTIONS: &[RoutineKey] = &[
+    key("session", "string"),
+    DOMAIN_PORT,
+    LEGACY,
+    key("legacy-load", "bool"),
+    key("enable", "bool"),
+    key("disable", "bool"),
+    key("no-einprogress", "bool"),
+];
+
+const LIST: RoutineSpec = RoutineSpec {
+    subsystem: 3,
+    routine: 815,
+    command: "list",
+    required: &[TYPE, HANDLE],
+    one_of: &[],
+    optional: &[key("name", "string"), LEGACY, DOMAIN_PORT],
+    reply: ReplyShape::Services,
+};
+
+const LOAD: RoutineSpec = RoutineSpec {
+    subsystem: 3,
+    routine: 800,
+    command: "load",
+    required: &[TYPE, HANDLE, key("paths", "array")],
+    one_of: &[],
+    optional: LOAD_OPTIONS,
+    reply: ReplyShape::Errors,
+};
+
+const UNLOAD: RoutineSpec = RoutineSpec {
+    subsystem: 3,
+    routine: 801,
+    command: "unload",
+    required: &[TYPE, HANDLE, key("paths", "array")],
+    one_of: &[],
+    optional: LOAD_OPTIONS,
+    reply: ReplyShape::Errors,
+};
+
+const ENABLE: RoutineSpec = RoutineSpec {
+    subsystem: 3,
+    routine: 808,
+    command: "enable",
+    required: &[TYPE, HANDLE, key("names", "array")],
+    one_of: &[],
+    optional: &[key("name", "string"), DOMAIN_PORT],
+    reply: ReplyShape::Errors,
+};
+
+const DISABLE: RoutineSpec = RoutineSpec {
+    subsystem: 3,
+    routine: 809,
+    command: "disable",
+    required: &[TYPE, HANDLE, key("names", "array")],
+    one_of: &[],
+    optional: &[key("name", "string"), DOMAIN_PORT],
+    reply: ReplyShape::Errors,
+};
+
+const DUMPSTATE: RoutineSpec = RoutineSpec {
+    subsystem: 3,
+    routine: 834,
+    command: "dumpstate",
+    required: &[SHMEM],
+    one_of: &[],
+    optional: &[TYPE, HANDLE],
+    reply: ReplyShape::Output,
+};
+
+const DUMPJPCATEGORY: RoutineSpec = RoutineSpec {
+    subsystem: 3,
+    routine: 837,
+    command: "dumpjpcategory",
+    required: &[],
+    one_of: &[FD, SHMEM],
+    optional: &[TYPE, HANDLE],
+    reply: ReplyShape::Output,
+};
+
+const PROCINFO: RoutineSpec = RoutineSpec {
+    subsystem: 2,
+    routine: 708,
+    command: "procinfo",
+    required: &[key("pid", "int64")],
+    one_of: &[FD, SHMEM],
+    optional: &[],
+    reply: ReplyShape::Output,
+};
+
+const PRINT_SERVICE: RoutineSpec = RoutineSpec {
+    subsystem: 2,
+    routine: 708,
+    command: "print",
+    required: &[TYPE, HANDLE, key("name", "string")],
+    one_of: &[FD, SHMEM],
+    optional: &[],
+    reply: ReplyShape::Output,
+};
+
+const PRINT_DOMAIN: RoutineSpec = RoutineSpec {
+    subsystem: 3,
+    routine: 828,
+    command: "print",
+    required: &[TYPE, HANDLE],
+    one_of: &[FD, SHMEM],
+    optional: &[],
+    reply: ReplyShape::Output,
+};
+
+const PRINT_CACHE: RoutineSpec = RoutineSpec {
+    subsystem: 3,
+    routine: 803,
+    command: "print-cache",
+    required: &[TYPE, HANDLE],
+    one_of: &[],
+    optional: &[FD, SHMEM],
+    reply: ReplyShape::Output,
+};
+
+const PORT_LOOKUP: RoutineSpec = RoutineSpec {
+    subsystem: 3,
+    routine: 822,
+    command: "procinfo",
+    required: &[TYPE, HANDLE, key("process", "int64"), key("name", "uint64")],
+    one_of: &[],
+    optional: &[],
+    reply: ReplyShape::Port,
+};
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::xpc_error::XPCError;
+    use crate::objects::xpc_value::XPCValue;
+    use crate::routines::Routine;
+    use crate::traits::query_builder::QueryBuilder;
+
+    #[test]
+    fn from_request() {
+        for routine in Routine::ALL.iter().copied() {
+            let request = match routine {
+                Routine::Procinfo => routine.request().entry("pid", 1_i64),
+                _ => routine.request(),
+            };
+
+            assert_eq!(Routine::from_request(&request), Some(routine));
+        }
+
+        let unknown = XPCValue::new_dictionary()
+            .entry("subsystem", 3_u64)
+            .entry("routine", 1_u64);
+
+        assert_eq!(Routine::from_request(&unknown), None);
+    }
+
+    #[test]
+    fn validate_required() {
+        let request = Routine::Dumpstate
+            .request()
+            .entry("type", 1_u64)
+            .entry("handle", 0_u64);
+
+        assert_eq!(
+            Routine::Dumpstate.validate(&request),
+            Err(XPCError::QueryError(
+                "dumpstate (834): missing required shmem".to_string()
+            ))
+        );
+
+        let request = request.entry("shmem", XPCValue::Shmem(0, 0x1400000));
+        assert!(Routine::Dumpstate.validate(&request).is_ok());
+        assert!(Routine::Dumpjpcategory.validate(&request).is_err());
+    }
+
+    #[test]
+    fn validate_one_of() {
+        let request = Routine::PrintDomain
+            .request()
+            .entry("type", 1_u64)
+            .entry("handle", 0_u64);
+
+        assert!(Routine::PrintDomain.validate(&request).is_err());
+        assert!(Routine::PrintDomain
+            .validate(&request.entry("fd", XPCValue::Fd(1)))
+            .is_ok());
+    }
+
+    #[test]
+    fn validate_types() {
+        let request = Routine::PortLookup
+            .request()
+            .entry("type", 1_u64)
+            .entry("handle", 0_u64)
+            .entry("process", 7578_i64)
+            .entry("name", "3335");
+
+        assert_eq!(
+            Routine::PortLookup.validate(&request),
+            Err(XPCError::QueryError(
+                "procinfo (822): name must be uint64, got string".to_string()
+            ))
+        );
+
+        assert!(Routine::PortLookup
+            .validate(&request.entry("name", 3335_u64))
+            .is_ok());
+    }
+}
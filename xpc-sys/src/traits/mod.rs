@@ -1,3 +1,5 @@
 pub mod query_builder;
+#[cfg(target_os = "macos")]
 pub mod xpc_pipeable;
+pub mod xpc_transport;
 pub mod xpc_value;
@@ -1,29 +1,41 @@
+use crate::domain_target::DomainTarget;
 use crate::enums::{DomainType, SessionType};
-use crate::get_bootstrap_port;
-use crate::objects::xpc_dictionary::XPCDictionary;
-use crate::objects::xpc_object::MachPortType;
-use crate::objects::xpc_object::XPCObject;
-use libc::mach_port_t;
-
-/// Builder methods for XPCDictionary to make querying easier
-pub trait QueryBuilder {
+use crate::objects::xpc_value::XPCValue;
+
+#[cfg(target_os = "macos")]
+use {
+    crate::enums::MachPortType, crate::get_bootstrap_port, crate::mach_port_t,
+    crate::objects::xpc_dictionary::XPCDictionary, crate::objects::xpc_object::XPCObject,
+};
+
+/// Builder methods for XPCDictionary (or XPCValue) to make querying easier
+pub trait QueryBuilder: Sized {
+    /// What entries are converted into, XPCObject or XPCValue
+    type Value;
+
     /// Add entry to query
-    fn entry<S: Into<String>, O: Into<XPCObject>>(self, key: S, value: O) -> XPCDictionary;
+    fn entry<S: Into<String>, O: Into<Self::Value>>(self, key: S, value: O) -> Self;
 
     /// Add entry if option is Some()
-    fn entry_if_present<S: Into<String>, O: Into<XPCObject>>(
+    fn entry_if_present<S: Into<String>, O: Into<Self::Value>>(
         self,
         key: S,
         value: Option<O>,
-    ) -> XPCDictionary;
+    ) -> Self {
+        match value {
+            Some(value) => self.entry(key, value),
+            None => self,
+        }
+    }
 
-    /// Extend an existing XPCDictionary
-    fn extend(self, other: &XPCDictionary) -> XPCDictionary;
+    /// Extend an existing query
+    fn extend(self, other: &Self) -> Self;
 
     /// Adds "domain_port" with get_bootstrap_port() -> _xpc_type_mach_send
-    fn with_domain_port_as_bootstrap_port(self) -> XPCDictionary
+    #[cfg(target_os = "macos")]
+    fn with_domain_port_as_bootstrap_port(self) -> Self
     where
-        Self: Sized,
+        Self::Value: From<(MachPortType, mach_port_t)>,
     {
         self.entry(
             "domain-port",
@@ -34,31 +46,44 @@ pub trait QueryBuilder {
     }
 
     /// Adds provided session type or falls back on Aqua
-    fn with_session_type_or_default(self, session: Option<SessionType>) -> XPCDictionary
+    fn with_session_type_or_default(self, session: Option<SessionType>) -> Self
     where
-        Self: Sized,
+        Self::Value: From<String>,
     {
         self.entry("session", session.unwrap_or(SessionType::Aqua).to_string())
     }
 
     /// Adds provided handle or falls back on 0
-    fn with_handle_or_default(self, handle: Option<u64>) -> XPCDictionary
+    fn with_handle_or_default(self, handle: Option<u64>) -> Self
     where
-        Self: Sized,
+        Self::Value: From<u64>,
     {
         self.entry("handle", handle.unwrap_or(0))
     }
 
     /// Adds provided DomainType, falls back on 7 (requestor's domain)
-    fn with_domain_type_or_default(self, t: Option<DomainType>) -> XPCDictionary
+    fn with_domain_type_or_default(self, t: Option<DomainType>) -> Self
     where
-        Self: Sized,
+        Self::Value: From<u64>,
     {
         self.entry("type", t.unwrap_or(DomainType::RequestorDomain) as u64)
     }
+
+    /// Adds "type" and "handle" of the target, and "name" if it has a label
+    fn with_domain_target(self, target: &DomainTarget) -> Self
+    where
+        Self::Value: From<u64> + From<String>,
+    {
+        self.entry("type", target.domain_type.clone() as u64)
+            .entry("handle", target.handle)
+            .entry_if_present("name", target.label.clone())
+    }
 }
 
+#[cfg(target_os = "macos")]
 impl QueryBuilder for XPCDictionary {
+    type Value = XPCObject;
+
     fn entry<S: Into<String>, O: Into<XPCObject>>(mut self, key: S, value: O) -> XPCDictionary {
         let Self(hm) = &mut self;
         let xpc_object: XPCObject = value.into();
@@ -66,18 +91,6 @@ impl QueryBuilder for XPCDictionary {
         self
     }
 
-    fn entry_if_present<S: Into<String>, O: Into<XPCObject>>(
-        self,
-        key: S,
-        value: Option<O>,
-    ) -> XPCDictionary {
-        if value.is_none() {
-            self
-        } else {
-            self.entry(key, value.unwrap())
-        }
-    }
-
     fn extend(mut self, other: &XPCDictionary) -> XPCDictionary {
         let Self(self_hm) = &mut self;
         let Self(other_hm) = other;
@@ -85,3 +98,57 @@ impl QueryBuilder for XPCDictionary {
         self
     }
 }
+
+impl QueryBuilder for XPCValue {
+    type Value = XPCValue;
+
+    /// Panics if not XPCValue::Dictionary
+    fn entry<S: Into<String>, O: Into<XPCValue>>(mut self, key: S, value: O) -> XPCValue {
+        match &mut self {
+            XPCValue::Dictionary(map) => map.insert(key.into(), value.into()),
+            other => panic!("Cannot add entry to {}", other.type_name()),
+        };
+
+        self
+    }
+
+    /// Panics if either side is not XPCValue::Dictionary
+    fn extend(mut self, other: &XPCValue) -> XPCValue {
+        match (&mut self, other) {
+            (XPCValue::Dictionary(self_map), XPCValue::Dictionary(other_map)) => {
+                self_map.extend(other_map.iter().map(|(s, v)| (s.clone(), v.clone())))
+            }
+            (s, o) => panic!("Cannot extend {} with {}", s.type_name(), o.type_name()),
+        };
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::enums::DomainType;
+    use crate::objects::xpc_value::XPCValue;
+    use crate::traits::query_builder::QueryBuilder;
+    use crate::traits::xpc_value::TryXPCValue;
+
+    #[test]
+    fn value_query() {
+        let proto = XPCValue::new_dictionary()
+            .entry("subsystem", 3_u64)
+            .entry("routine", 815_u64);
+
+        let query = XPCValue::new_dictionary()
+            .extend(&proto)
+            .with_domain_type_or_default(Some(DomainType::System))
+            .with_handle_or_default(None)
+            .entry_if_present("name", None as Option<String>);
+
+        let routine: u64 = query.get(["routine"]).unwrap().xpc_value().unwrap();
+        let domain_type: u64 = query.get(["type"]).unwrap().xpc_value().unwrap();
+
+        assert_eq!(routine, 815);
+        assert_eq!(domain_type, 1);
+        assert!(query.get(["name"]).is_err());
+    }
+}
@@ -1,13 +1,16 @@
+use crate::objects::launchd_error::LaunchdError;
 use crate::objects::xpc_dictionary::XPCDictionary;
 use crate::objects::xpc_error::XPCError;
 use crate::objects::xpc_error::XPCError::PipeError;
 use crate::objects::xpc_object::XPCObject;
+use crate::objects::xpc_value::XPCValue;
 use crate::{
     get_xpc_bootstrap_pipe, rs_xpc_strerror, xpc_object_t, xpc_pipe_routine,
     xpc_pipe_routine_with_flags,
 };
 
 use crate::traits::xpc_value::TryXPCValue;
+use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::ptr::null_mut;
 
@@ -29,26 +32,25 @@ pub trait XPCPipeable {
 
         if hm.contains_key("error") {
             let errcode: i64 = response.get(&["error"])?.xpc_value()?;
-            Err(XPCError::QueryError(format!(
-                "{}: {}",
-                errcode,
-                rs_xpc_strerror(errcode as i32)
-            )))
+            Err(XPCError::LaunchdError(LaunchdError::new(errcode)))
         } else if hm.contains_key("errors") {
             let XPCDictionary(errors_hm) = response.get_as_dictionary(&["errors"])?;
             if errors_hm.is_empty() {
                 return Ok(response);
             }
 
-            let errors: Vec<String> = errors_hm
+            let errors: BTreeMap<String, LaunchdError> = errors_hm
                 .iter()
-                .flat_map(|(_, e)| {
+                .map(|(key, e)| {
                     let e: Result<i64, XPCError> = e.xpc_value();
-                    e.map(|e_i64| format!("{}: {}", e_i64, rs_xpc_strerror(e_i64 as i32)))
+                    let e = e
+                        .map(LaunchdError::new)
+                        .unwrap_or_else(|_| LaunchdError::unknown());
+                    (key.clone(), e)
                 })
                 .collect();
 
-            Err(XPCError::QueryError(errors.join("\n")))
+            Err(XPCError::LaunchdErrors(errors))
         } else {
             Ok(response)
         }
@@ -93,3 +95,15 @@ impl XPCPipeable for XPCDictionary {
         xpc_object.pipe_routine_with_flags(flags)
     }
 }
+
+impl XPCPipeable for XPCValue {
+    fn pipe_routine(&self) -> XPCPipeResult {
+        let xpc_object: XPCObject = self.into();
+        xpc_object.pipe_routine()
+    }
+
+    fn pipe_routine_with_flags(&self, flags: u64) -> XPCPipeResult {
+        let xpc_object: XPCObject = self.into();
+        xpc_object.pipe_routine_with_flags(flags)
+    }
+}
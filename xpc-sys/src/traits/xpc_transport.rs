@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+
+use crate::objects::launchd_error::LaunchdError;
+use crate::objects::xpc_error::XPCError;
+use crate::objects::xpc_value::XPCValue;
+use crate::traits::xpc_value::TryXPCValue;
+
+/// Something that answers XPC requests: launchd over the bootstrap pipe
+/// (see BootstrapPipe), or FakeLaunchd in tests
+pub trait XPCTransport: Send + Sync {
+    /// Send request, returning the reply
+    fn pipe_routine(&self, request: &XPCValue) -> Result<XPCValue, XPCError>;
+
+    /// Pipe routine expecting XPC dictionary reply, with checking of "error" and "errors" keys
+    fn pipe_routine_with_error_handling(&self, request: &XPCValue) -> Result<XPCValue, XPCError> {
+        handle_reply_errors(self.pipe_routine(request)?)
+    }
+}
+
+/// Turn "error" and "errors" keys of a reply into XPCError::LaunchdError(s)
+pub fn handle_reply_errors(response: XPCValue) -> Result<XPCValue, XPCError> {
+    let map = match &response {
+        XPCValue::Dictionary(map) => map,
+        other => {
+            return Err(XPCError::ValueError(format!(
+                "Expected dictionary reply, got {}",
+                other.type_name()
+            )))
+        }
+    };
+
+    if map.contains_key("error") {
+        let errcode: i64 = response.get(["error"])?.xpc_value()?;
+        Err(XPCError::LaunchdError(LaunchdError::new(errcode)))
+    } else if map.contains_key("errors") {
+        let errors_map = response.get_as_dictionary(["errors"])?;
+        if errors_map.is_empty() {
+            return Ok(response);
+        }
+
+        let errors: BTreeMap<String, LaunchdError> = errors_map
+            .iter()
+            .map(|(key, e)| {
+                // Not dropped, a job that failed must not look like it loaded
+                let e: Result<i64, XPCError> = e.xpc_value();
+                let e = e
+                    .map(LaunchdError::new)
+                    .unwrap_or_else(|_| LaunchdError::unknown());
+                (key.clone(), e)
+            })
+            .collect();
+
+        Err(XPCError::LaunchdErrors(errors))
+    } else {
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::objects::launchd_error::LaunchdError;
+    use crate::objects::xpc_error::XPCError;
+    use crate::objects::xpc_value::XPCValue;
+    use crate::traits::xpc_transport::handle_reply_errors;
+
+    #[test]
+    fn errors_that_arent_codes() {
+        let mut errors = BTreeMap::new();
+        errors.insert("/a.plist".to_string(), XPCValue::from(113_i64));
+        errors.insert("/b.plist".to_string(), XPCValue::from("huh"));
+
+        let mut reply = BTreeMap::new();
+        reply.insert("errors".to_string(), XPCValue::Dictionary(errors));
+
+        match handle_reply_errors(XPCValue::Dictionary(reply)) {
+            Err(XPCError::LaunchdErrors(errors)) => {
+                assert_eq!(errors["/a.plist"], LaunchdError::new(113));
+                assert_eq!(errors["/b.plist"], LaunchdError::unknown());
+            }
+            other => panic!("expected LaunchdErrors, got {:?}", other),
+        }
+    }
+}
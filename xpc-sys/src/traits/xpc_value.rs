@@ -1,21 +1,26 @@
-use block::ConcreteBlock;
-use std::cell::RefCell;
-use std::ffi::CStr;
-use std::rc::Rc;
-
-use crate::objects::xpc_object::{MachPortType, XPCObject};
-use crate::objects::xpc_type;
-use crate::{
-    xpc_array_apply, xpc_bool_get_value, xpc_double_get_value, xpc_int64_get_value,
-    xpc_mach_send_get_right, xpc_object_t, xpc_string_get_string_ptr, xpc_type_get_name,
-    xpc_uint64_get_value,
-};
-use libc::mach_port_t;
+use crate::enums::MachPortType;
+use crate::mach_port_t;
+use crate::objects::xpc_value::XPCValue;
 
 use crate::objects::xpc_error::XPCError;
 use crate::objects::xpc_error::XPCError::ValueError;
-use crate::objects::xpc_type::check_xpc_type;
-use std::sync::Arc;
+
+#[cfg(target_os = "macos")]
+use {
+    crate::objects::xpc_object::XPCObject,
+    crate::objects::xpc_type,
+    crate::objects::xpc_type::check_xpc_type,
+    crate::{
+        xpc_array_apply, xpc_bool_get_value, xpc_double_get_value, xpc_int64_get_value,
+        xpc_mach_send_get_right, xpc_object_t, xpc_string_get_string_ptr, xpc_type_get_name,
+        xpc_uint64_get_value,
+    },
+    block::ConcreteBlock,
+    std::cell::RefCell,
+    std::ffi::CStr,
+    std::rc::Rc,
+    std::sync::Arc,
+};
 
 /// Implement to get data out of xpc_type_t and into
 /// a Rust native data type
@@ -23,6 +28,7 @@ pub trait TryXPCValue<Out> {
     fn xpc_value(&self) -> Result<Out, XPCError>;
 }
 
+#[cfg(target_os = "macos")]
 impl TryXPCValue<i64> for XPCObject {
     #[must_use]
     fn xpc_value(&self) -> Result<i64, XPCError> {
@@ -31,6 +37,7 @@ impl TryXPCValue<i64> for XPCObject {
     }
 }
 
+#[cfg(target_os = "macos")]
 impl TryXPCValue<u64> for XPCObject {
     #[must_use]
     fn xpc_value(&self) -> Result<u64, XPCError> {
@@ -39,6 +46,7 @@ impl TryXPCValue<u64> for XPCObject {
     }
 }
 
+#[cfg(target_os = "macos")]
 impl TryXPCValue<f64> for XPCObject {
     #[must_use]
     fn xpc_value(&self) -> Result<f64, XPCError> {
@@ -47,6 +55,7 @@ impl TryXPCValue<f64> for XPCObject {
     }
 }
 
+#[cfg(target_os = "macos")]
 impl TryXPCValue<String> for XPCObject {
     #[must_use]
     fn xpc_value(&self) -> Result<String, XPCError> {
@@ -57,6 +66,7 @@ impl TryXPCValue<String> for XPCObject {
     }
 }
 
+#[cfg(target_os = "macos")]
 impl TryXPCValue<bool> for XPCObject {
     #[must_use]
     fn xpc_value(&self) -> Result<bool, XPCError> {
@@ -65,6 +75,7 @@ impl TryXPCValue<bool> for XPCObject {
     }
 }
 
+#[cfg(target_os = "macos")]
 impl TryXPCValue<(MachPortType, mach_port_t)> for XPCObject {
     #[must_use]
     fn xpc_value(&self) -> Result<(MachPortType, mach_port_t), XPCError> {
@@ -88,6 +99,7 @@ impl TryXPCValue<(MachPortType, mach_port_t)> for XPCObject {
     }
 }
 
+#[cfg(target_os = "macos")]
 impl TryXPCValue<Vec<Arc<XPCObject>>> for XPCObject {
     #[must_use]
     fn xpc_value(&self) -> Result<Vec<Arc<XPCObject>>, XPCError> {
@@ -119,7 +131,79 @@ impl TryXPCValue<Vec<Arc<XPCObject>>> for XPCObject {
     }
 }
 
-#[cfg(test)]
+/// Runtime type check for XPCValue, same messages as check_xpc_type
+fn value_type_error(value: &XPCValue, requested: &str) -> XPCError {
+    ValueError(format!("Cannot get {} as {}", value.type_name(), requested))
+}
+
+impl TryXPCValue<i64> for XPCValue {
+    fn xpc_value(&self) -> Result<i64, XPCError> {
+        match self {
+            XPCValue::Int64(i) => Ok(*i),
+            other => Err(value_type_error(other, "int64")),
+        }
+    }
+}
+
+impl TryXPCValue<u64> for XPCValue {
+    fn xpc_value(&self) -> Result<u64, XPCError> {
+        match self {
+            XPCValue::UInt64(u) => Ok(*u),
+            other => Err(value_type_error(other, "uint64")),
+        }
+    }
+}
+
+impl TryXPCValue<f64> for XPCValue {
+    fn xpc_value(&self) -> Result<f64, XPCError> {
+        match self {
+            XPCValue::Double(d) => Ok(*d),
+            other => Err(value_type_error(other, "double")),
+        }
+    }
+}
+
+impl TryXPCValue<String> for XPCValue {
+    fn xpc_value(&self) -> Result<String, XPCError> {
+        match self {
+            XPCValue::String(s) => Ok(s.clone()),
+            other => Err(value_type_error(other, "string")),
+        }
+    }
+}
+
+impl TryXPCValue<bool> for XPCValue {
+    fn xpc_value(&self) -> Result<bool, XPCError> {
+        match self {
+            XPCValue::Bool(b) => Ok(*b),
+            other => Err(value_type_error(other, "bool")),
+        }
+    }
+}
+
+impl TryXPCValue<(MachPortType, mach_port_t)> for XPCValue {
+    fn xpc_value(&self) -> Result<(MachPortType, mach_port_t), XPCError> {
+        match self {
+            XPCValue::MachSend(port) => Ok((MachPortType::Send, *port)),
+            XPCValue::MachRecv(port) => Ok((MachPortType::Recv, *port)),
+            other => Err(ValueError(format!(
+                "Object is {} and neither _xpc_type_mach_send nor _xpc_type_mach_recv",
+                other.type_name()
+            ))),
+        }
+    }
+}
+
+impl TryXPCValue<Vec<XPCValue>> for XPCValue {
+    fn xpc_value(&self) -> Result<Vec<XPCValue>, XPCError> {
+        match self {
+            XPCValue::Array(items) => Ok(items.clone()),
+            other => Err(value_type_error(other, "array")),
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "macos"))]
 mod tests {
     use crate::get_bootstrap_port;
     use crate::objects::xpc_error::XPCError;
@@ -0,0 +1,18 @@
+use std::convert::TryFrom;
+
+use crate::objects::xpc_error::XPCError;
+use crate::objects::xpc_value::XPCValue;
+use crate::traits::xpc_pipeable::XPCPipeable;
+use crate::traits::xpc_transport::XPCTransport;
+
+/// Talk to launchd with xpc_pipe_routine on get_xpc_bootstrap_pipe(),
+/// the same as XPCPipeable
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BootstrapPipe;
+
+impl XPCTransport for BootstrapPipe {
+    fn pipe_routine(&self, request: &XPCValue) -> Result<XPCValue, XPCError> {
+        let reply = request.pipe_routine()?;
+        XPCValue::try_from(&reply)
+    }
+}
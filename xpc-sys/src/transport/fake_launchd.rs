@@ -0,0 +1,498 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::enums::{DomainType, SessionType};
+use crate::objects::xpc_error::XPCError;
+use crate::objects::xpc_value::XPCValue;
+use crate::routines::Routine;
+use crate::traits::xpc_transport::XPCTransport;
+use crate::traits::xpc_value::TryXPCValue;
+
+/// ENOENT
+const NO_SUCH_FILE: i64 = 2;
+/// EALREADY
+const ALREADY_LOADED: i64 = 37;
+/// Could not find specified service
+const NO_SUCH_SERVICE: i64 = 113;
+/// Service is disabled
+const SERVICE_DISABLED: i64 = 119;
+
+pub type FakeHandler =
+    Box<dyn Fn(&XPCValue, &mut FakeState) -> Result<XPCValue, XPCError> + Send + Sync>;
+
+/// A job FakeLaunchd knows about
+#[derive(Debug, Clone, PartialEq)]
+pub struct FakeService {
+    pub label: String,
+    pub plist_path: Option<String>,
+    pub domain: DomainType,
+    pub session: SessionType,
+    pub pid: i64,
+    pub loaded: bool,
+}
+
+impl FakeService {
+    /// Unloaded service in the System domain
+    pub fn new<S: Into<String>>(label: S) -> Self {
+        Self {
+            label: label.into(),
+            plist_path: None,
+            domain: DomainType::System,
+            session: SessionType::System,
+            pid: 0,
+            loaded: false,
+        }
+    }
+
+    pub fn with_plist_path<S: Into<String>>(mut self, path: S) -> Self {
+        self.plist_path = Some(path.into());
+        self
+    }
+
+    pub fn with_domain(mut self, domain: DomainType, session: SessionType) -> Self {
+        self.domain = domain;
+        self.session = session;
+        self
+    }
+
+    /// Loaded, and running if pid > 0
+    pub fn loaded(mut self, pid: i64) -> Self {
+        self.loaded = true;
+        self.pid = pid;
+        self
+    }
+
+    /// The "service" dictionary of a list reply
+    pub fn to_xpc_value(&self) -> XPCValue {
+        let mut map = BTreeMap::new();
+        map.insert("Label".to_string(), XPCValue::from(self.label.clone()));
+        map.insert(
+            "LimitLoadToSessionType".to_string(),
+            XPCValue::from(self.session.to_string()),
+        );
+
+        if self.pid > 0 {
+            map.insert("PID".to_string(), XPCValue::from(self.pid));
+        }
+
+        XPCValue::Dictionary(map)
+    }
+}
+
+/// Everything FakeLaunchd handlers can read and change
+#[derive(Debug, Default)]
+pub struct FakeState {
+    pub services: BTreeMap<String, FakeService>,
+    /// Labels disabled with routine 809, like launchd's override database
+    pub disabled: BTreeSet<String>,
+    /// Text written into the shmem of a (subsystem, routine), e.g. dumpstate
+    pub shmem_text: HashMap<(u64, u64), String>,
+    /// Every request seen, in order
+    pub requests: Vec<XPCValue>,
+}
+
+/// Answers launchd requests from in-memory state. Handlers are keyed by
+/// (subsystem, routine) and can be replaced with on() to script replies.
+///
+/// Out of the box: list (815), load (800), unload (801), enable (808),
+/// disable (809), dumpstate (834), dumpjpcategory (837), procinfo (2, 708)
+pub struct FakeLaunchd {
+    state: Mutex<FakeState>,
+    handlers: Mutex<HashMap<(u64, u64), FakeHandler>>,
+}
+
+impl Default for FakeLaunchd {
+    fn default() -> Self {
+        let fake = FakeLaunchd {
+            state: Mutex::new(FakeState::default()),
+            handlers: Mutex::new(HashMap::new()),
+        };
+
+        fake.on_routine(Routine::List, list);
+        fake.on_routine(Routine::Load, |r, s| load_paths(r, s, true));
+        fake.on_routine(Routine::Unload, |r, s| load_paths(r, s, false));
+        fake.on_routine(Routine::Enable, |r, s| enable_names(r, s, true));
+        fake.on_routine(Routine::Disable, |r, s| enable_names(r, s, false));
+
+        fake.on_routine(Routine::Dumpstate, |r, s| {
+            write_shmem(r, s, Routine::Dumpstate)
+        });
+        fake.on_routine(Routine::Dumpjpcategory, |r, s| {
+            write_shmem(r, s, Routine::Dumpjpcategory)
+        });
+        fake.on_routine(Routine::Procinfo, |r, s| {
+            write_shmem(r, s, Routine::Procinfo)
+        });
+
+        fake
+    }
+}
+
+impl FakeLaunchd {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_service(self, service: FakeService) -> Self {
+        self.state().services.insert(service.label.clone(), service);
+        self
+    }
+
+    /// Text to write into the request's shmem for (subsystem, routine)
+    ///
+    /// # Safety
+    ///
+    /// The region of every XPCValue::Shmem sent for this routine must be
+    /// mapped and writable for its size, as with XPCShmem
+    pub unsafe fn with_shmem_text<S: Into<String>>(
+        self,
+        subsystem: u64,
+        routine: u64,
+        text: S,
+    ) -> Self {
+        self.state()
+            .shmem_text
+            .insert((subsystem, routine), text.into());
+        self
+    }
+
+    /// Replace the handler for (subsystem, routine)
+    pub fn on<F>(&self, subsystem: u64, routine: u64, handler: F)
+    where
+        F: Fn(&XPCValue, &mut FakeState) -> Result<XPCValue, XPCError> + Send + Sync + 'static,
+    {
+        self.handlers
+            .lock()
+            .expect("Must lock handlers")
+            .insert((subsystem, routine), Box::new(handler));
+    }
+
+    /// Replace the handler for a routine from the catalog
+    pub fn on_routine<F>(&self, routine: Routine, handler: F)
+    where
+        F: Fn(&XPCValue, &mut FakeState) -> Result<XPCValue, XPCError> + Send + Sync + 'static,
+    {
+        let spec = routine.spec();
+        self.on(spec.subsystem, spec.routine, handler);
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, FakeState> {
+        self.state.lock().expect("Must lock state")
+    }
+
+    pub fn requests(&self) -> Vec<XPCValue> {
+        self.state().requests.clone()
+    }
+
+    pub fn service<S: AsRef<str>>(&self, label: S) -> Option<FakeService> {
+        self.state().services.get(label.as_ref()).cloned()
+    }
+
+    /// Reply with only an "error" key, how launchd reports most failures
+    pub fn error_reply(code: i64) -> XPCValue {
+        let mut map = BTreeMap::new();
+        map.insert("error".to_string(), XPCValue::Int64(code));
+        XPCValue::Dictionary(map)
+    }
+}
+
+impl XPCTransport for FakeLaunchd {
+    fn pipe_routine(&self, request: &XPCValue) -> Result<XPCValue, XPCError> {
+        let subsystem: u64 = request.get(["subsystem"])?.xpc_value()?;
+        let routine: u64 = request.get(["routine"])?.xpc_value()?;
+
+        let handlers = self.handlers.lock().expect("Must lock handlers");
+        let mut state = self.state();
+        state.requests.push(request.clone());
+
+        let handler = handlers.get(&(subsystem, routine)).ok_or_else(|| {
+            XPCError::PipeError(format!(
+                "FakeLaunchd has no handler for subsystem {} routine {}",
+                subsystem, routine
+            ))
+        })?;
+
+        handler(request, &mut state)
+    }
+}
+
+fn domain_type(request: &XPCValue) -> Result<DomainType, XPCError> {
+    let domain_type: u64 = request.get(["type"])?.xpc_value()?;
+    Ok(domain_type.into())
+}
+
+fn strings(request: &XPCValue, key: &str) -> Result<Vec<String>, XPCError> {
+    let items: Vec<XPCValue> = request.get([key])?.xpc_value()?;
+    items.iter().map(|i| i.xpc_value()).collect()
+}
+
+fn errors_reply(errors: BTreeMap<String, XPCValue>) -> XPCValue {
+    let mut map = BTreeMap::new();
+    map.insert("errors".to_string(), XPCValue::Dictionary(errors));
+    XPCValue::Dictionary(map)
+}
+
+/// Routine 815, every loaded service in the domain or the one named
+fn list(request: &XPCValue, state: &mut FakeState) -> Result<XPCValue, XPCError> {
+    let domain = domain_type(request)?;
+    let mut loaded = state
+        .services
+        .values()
+        .filter(|s| s.loaded && s.domain == domain);
+
+    let mut map = BTreeMap::new();
+
+    match request.get(["name"]) {
+        Ok(name) => {
+            let name: String = name.xpc_value()?;
+
+            match loaded.find(|s| s.label == name) {
+                Some(service) => map.insert("service".to_string(), service.to_xpc_value()),
+                None => return Ok(FakeLaunchd::error_reply(NO_SUCH_SERVICE)),
+            };
+        }
+        Err(_) => {
+            let services = loaded
+                .map(|s| (s.label.clone(), s.to_xpc_value()))
+                .collect();
+
+            map.insert("services".to_string(), XPCValue::Dictionary(services));
+        }
+    }
+
+    Ok(XPCValue::Dictionary(map))
+}
+
+/// Routines 800 and 801, by plist path
+fn load_paths(request: &XPCValue, state: &mut FakeState, load: bool) -> Result<XPCValue, XPCError> {
+    let domain = domain_type(request)?;
+    let session = request
+        .get(["session"])
+        .and_then(|s| s.xpc_value())
+        .map(|s: String| SessionType::from(s))
+        .ok();
+
+    let mut errors = BTreeMap::new();
+
+    for path in strings(request, "paths")? {
+        let service = state
+            .services
+            .values_mut()
+            .find(|s| s.plist_path.as_deref() == Some(path.as_str()));
+
+        let error = match service {
+            None if load => Some(NO_SUCH_FILE),
+            None => Some(NO_SUCH_SERVICE),
+            Some(s) if load && state.disabled.contains(&s.label) => Some(SERVICE_DISABLED),
+            Some(s) if load && s.loaded => Some(ALREADY_LOADED),
+            Some(s) if !load && (!s.loaded || s.domain != domain) => Some(NO_SUCH_SERVICE),
+            Some(s) => {
+                s.loaded = load;
+                s.domain = domain.clone();
+                if let Some(session) = &session {
+                    s.session = session.clone();
+                }
+                if !load {
+                    s.pid = 0;
+                }
+                None
+            }
+        };
+
+        if let Some(error) = error {
+            errors.insert(path, XPCValue::Int64(error));
+        }
+    }
+
+    Ok(errors_reply(errors))
+}
+
+/// Routines 808 and 809, by label
+fn enable_names(
+    request: &XPCValue,
+    state: &mut FakeState,
+    enable: bool,
+) -> Result<XPCValue, XPCError> {
+    for name in strings(request, "names")? {
+        if enable {
+            state.disabled.remove(&name);
+        } else {
+            state.disabled.insert(name);
+        }
+    }
+
+    Ok(errors_reply(BTreeMap::new()))
+}
+
+/// dumpstate, dumpjpcategory, procinfo: fill shmem, reply with bytes-written
+fn write_shmem(
+    request: &XPCValue,
+    state: &mut FakeState,
+    routine: Routine,
+) -> Result<XPCValue, XPCError> {
+    let spec = routine.spec();
+    let (region, size) = match request.get(["shmem"])? {
+        XPCValue::Shmem(region, size) => (*region, *size),
+        other => {
+            return Err(XPCError::ValueError(format!(
+                "Expected shmem, got {}",
+                other.type_name()
+            )))
+        }
+    };
+
+    let bytes_written = match state.shmem_text.get(&(spec.subsystem, spec.routine)) {
+        Some(text) if region != 0 => {
+            let len = text.len().min(size);
+            // Safety: promised by the caller of with_shmem_text
+            unsafe { std::ptr::copy_nonoverlapping(text.as_ptr(), region as *mut u8, len) };
+            len
+        }
+        _ => 0,
+    };
+
+    let mut map = BTreeMap::new();
+    map.insert(
+        "bytes-written".to_string(),
+        XPCValue::UInt64(bytes_written as u64),
+    );
+
+    Ok(XPCValue::Dictionary(map))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::enums::{DomainType, SessionType};
+    use crate::objects::launchd_error::{ErrorCategory, LaunchdError};
+    use crate::objects::xpc_error::XPCError;
+    use crate::objects::xpc_value::XPCValue;
+    use crate::traits::query_builder::QueryBuilder;
+    use crate::traits::xpc_transport::XPCTransport;
+    use crate::traits::xpc_value::TryXPCValue;
+    use crate::transport::fake_launchd::{FakeLaunchd, FakeService};
+
+    fn fake() -> FakeLaunchd {
+        FakeLaunchd::new()
+            .with_service(
+                FakeService::new("com.apple.running")
+                    .with_plist_path("/System/Library/LaunchDaemons/com.apple.running.plist")
+                    .loaded(42),
+            )
+            .with_service(
+                FakeService::new("homebrew.mxcl.redis")
+                    .with_plist_path("/Users/me/Library/LaunchAgents/homebrew.mxcl.redis.plist")
+                    .with_domain(DomainType::RequestorDomain, SessionType::Aqua),
+            )
+    }
+
+    fn request(routine: u64, domain: DomainType) -> XPCValue {
+        XPCValue::new_dictionary()
+            .entry("subsystem", 3_u64)
+            .entry("routine", routine)
+            .with_domain_type_or_default(Some(domain))
+    }
+
+    #[test]
+    fn list_domain() {
+        let fake = fake();
+        let reply = fake
+            .pipe_routine_with_error_handling(&request(815, DomainType::System))
+            .unwrap();
+
+        let services = reply.get_as_dictionary(["services"]).unwrap();
+        assert_eq!(
+            services.keys().collect::<Vec<_>>(),
+            vec!["com.apple.running"]
+        );
+    }
+
+    #[test]
+    fn list_name() {
+        let fake = fake();
+        let reply = fake
+            .pipe_routine_with_error_handling(
+                &request(815, DomainType::System).entry("name", "com.apple.running"),
+            )
+            .unwrap();
+
+        let pid: i64 = reply.get(["service", "PID"]).unwrap().xpc_value().unwrap();
+        assert_eq!(pid, 42);
+
+        let missing = fake.pipe_routine_with_error_handling(
+            &request(815, DomainType::System).entry("name", "homebrew.mxcl.redis"),
+        );
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn load_unload() {
+        let fake = fake();
+        let path = "/Users/me/Library/LaunchAgents/homebrew.mxcl.redis.plist";
+
+        let load = request(800, DomainType::RequestorDomain)
+            .entry("session", "Aqua")
+            .entry("paths", vec![path]);
+
+        fake.pipe_routine_with_error_handling(&load).unwrap();
+        assert!(fake.service("homebrew.mxcl.redis").unwrap().loaded);
+        assert!(fake.pipe_routine_with_error_handling(&load).is_err());
+
+        let unload = request(801, DomainType::RequestorDomain).entry("paths", vec![path]);
+        fake.pipe_routine_with_error_handling(&unload).unwrap();
+        assert!(!fake.service("homebrew.mxcl.redis").unwrap().loaded);
+
+        assert_eq!(fake.requests().len(), 3);
+    }
+
+    #[test]
+    fn disabled_cannot_load() {
+        let fake = fake();
+
+        let disable = request(809, DomainType::RequestorDomain)
+            .entry("name", "homebrew.mxcl.redis")
+            .entry("names", vec!["homebrew.mxcl.redis"]);
+        fake.pipe_routine_with_error_handling(&disable).unwrap();
+
+        let load = request(800, DomainType::RequestorDomain).entry(
+            "paths",
+            vec!["/Users/me/Library/LaunchAgents/homebrew.mxcl.redis.plist"],
+        );
+        let err = fake.pipe_routine_with_error_handling(&load).unwrap_err();
+        assert_eq!(err.category(), Some(ErrorCategory::Disabled));
+        assert_eq!(
+            err.to_string(),
+            "/Users/me/Library/LaunchAgents/homebrew.mxcl.redis.plist: 119: Service is disabled"
+        );
+    }
+
+    #[test]
+    fn scripted_handler() {
+        let fake = fake();
+        fake.on(3, 815, |_, _| Ok(FakeLaunchd::error_reply(1)));
+
+        assert_eq!(
+            fake.pipe_routine_with_error_handling(&request(815, DomainType::System)),
+            Err(XPCError::LaunchdError(LaunchdError::new(1)))
+        );
+        assert!(fake
+            .pipe_routine(&request(999, DomainType::System))
+            .is_err());
+    }
+
+    #[test]
+    fn dumpstate_shmem() {
+        let mut buf = vec![0_u8; 16];
+        let fake = unsafe { FakeLaunchd::new().with_shmem_text(3, 834, "com.apple.xpc.launchd") };
+
+        let reply = fake
+            .pipe_routine_with_error_handling(&request(834, DomainType::System).entry(
+                "shmem",
+                XPCValue::Shmem(buf.as_mut_ptr() as usize, buf.len()),
+            ))
+            .unwrap();
+
+        let written: u64 = reply.get(["bytes-written"]).unwrap().xpc_value().unwrap();
+        assert_eq!(written, 16);
+        assert_eq!(&buf, b"com.apple.xpc.la");
+    }
+}
@@ -0,0 +1,15 @@
+/// launchd via xpc_pipe_routine
+#[cfg(target_os = "macos")]
+pub mod bootstrap_pipe;
+
+/// In-memory launchd for tests
+pub mod fake_launchd;
+
+/// Record exchanges with another transport to a file
+pub mod recording;
+
+/// Serve recorded exchanges back
+pub mod replay;
+
+/// Recorded exchanges, saved as JSON or plist
+pub mod session;
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::objects::xpc_error::XPCError;
+use crate::objects::xpc_value::XPCValue;
+use crate::traits::xpc_transport::{handle_reply_errors, XPCTransport};
+use crate::transport::session::{XPCExchange, XPCSession};
+
+/// Pass requests through to another transport, writing every exchange
+/// to a session file as it happens
+pub struct RecordingTransport<T: XPCTransport> {
+    inner: T,
+    path: PathBuf,
+    session: Mutex<XPCSession>,
+}
+
+impl<T: XPCTransport> RecordingTransport<T> {
+    pub fn new<P: Into<PathBuf>>(inner: T, path: P) -> Self {
+        Self {
+            inner,
+            path: path.into(),
+            session: Mutex::new(XPCSession::default()),
+        }
+    }
+
+    pub fn session(&self) -> XPCSession {
+        self.session.lock().expect("Must lock session").clone()
+    }
+
+    fn record(&self, exchange: XPCExchange) {
+        let mut session = self.session.lock().expect("Must lock session");
+        session.exchanges.push(exchange);
+
+        if let Err(e) = session.save(&self.path) {
+            log::error!("Cannot save session to {:?}: {}", self.path, e);
+        }
+    }
+}
+
+impl<T: XPCTransport> XPCTransport for RecordingTransport<T> {
+    fn pipe_routine(&self, request: &XPCValue) -> Result<XPCValue, XPCError> {
+        let reply = self.inner.pipe_routine(request);
+
+        self.record(XPCExchange {
+            request: request.clone(),
+            reply: reply.clone(),
+            result: None,
+        });
+
+        reply
+    }
+
+    fn pipe_routine_with_error_handling(&self, request: &XPCValue) -> Result<XPCValue, XPCError> {
+        let reply = self.inner.pipe_routine(request);
+        let result = reply.clone().and_then(handle_reply_errors);
+
+        self.record(XPCExchange {
+            request: request.clone(),
+            reply,
+            result: Some(result.clone()),
+        });
+
+        result
+    }
+}
@@ -0,0 +1,194 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::objects::xpc_error::XPCError;
+use crate::objects::xpc_value::XPCValue;
+use crate::traits::xpc_transport::{handle_reply_errors, XPCTransport};
+use crate::transport::session::{XPCExchange, XPCExchangeKey, XPCSession};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayMode {
+    /// Serve exchanges in recorded order, failing if a request's
+    /// subsystem / routine / type / name differs from the recording
+    InOrder,
+    /// Serve the first unused exchange with the same subsystem / routine /
+    /// type / name, repeating the last one once they are used up
+    Matched,
+}
+
+/// Answer requests from a recorded XPCSession
+pub struct ReplayTransport {
+    exchanges: Vec<XPCExchange>,
+    mode: ReplayMode,
+    used: Mutex<Vec<bool>>,
+}
+
+impl ReplayTransport {
+    pub fn new(session: XPCSession, mode: ReplayMode) -> Self {
+        let used = vec![false; session.exchanges.len()];
+
+        Self {
+            exchanges: session.exchanges,
+            mode,
+            used: Mutex::new(used),
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P, mode: ReplayMode) -> Result<Self, XPCError> {
+        XPCSession::load(path).map(|s| Self::new(s, mode))
+    }
+
+    /// Exchanges that were never served
+    pub fn remaining(&self) -> Vec<&XPCExchange> {
+        let used = self.used.lock().expect("Must lock");
+
+        self.exchanges
+            .iter()
+            .zip(used.iter())
+            .filter(|(_, used)| !**used)
+            .map(|(e, _)| e)
+            .collect()
+    }
+
+    fn next_exchange(&self, request: &XPCValue) -> Result<&XPCExchange, XPCError> {
+        let key = XPCExchangeKey::from(request);
+        let mut used = self.used.lock().expect("Must lock");
+
+        let index = match self.mode {
+            ReplayMode::InOrder => {
+                let index = used.iter().position(|u| !u).ok_or_else(|| {
+                    XPCError::PipeError(format!("Replay exhausted, got {:?}", key))
+                })?;
+
+                let expected = self.exchanges[index].key();
+                if expected != key {
+                    return Err(XPCError::PipeError(format!(
+                        "Replay expected {:?}, got {:?}",
+                        expected, key
+                    )));
+                }
+
+                index
+            }
+            ReplayMode::Matched => {
+                let matching: Vec<usize> = (0..self.exchanges.len())
+                    .filter(|&i| self.exchanges[i].key() == key)
+                    .collect();
+
+                let unused = matching.iter().copied().find(|&i| !used[i]);
+                unused
+                    .or_else(|| matching.last().copied())
+                    .ok_or_else(|| XPCError::PipeError(format!("Nothing recorded for {:?}", key)))?
+            }
+        };
+
+        used[index] = true;
+        Ok(&self.exchanges[index])
+    }
+}
+
+impl XPCTransport for ReplayTransport {
+    fn pipe_routine(&self, request: &XPCValue) -> Result<XPCValue, XPCError> {
+        self.next_exchange(request)?.reply.clone()
+    }
+
+    /// Recorded result if there is one, so errors read the same as
+    /// on the machine that recorded them
+    fn pipe_routine_with_error_handling(&self, request: &XPCValue) -> Result<XPCValue, XPCError> {
+        let exchange = self.next_exchange(request)?;
+
+        match &exchange.result {
+            Some(result) => result.clone(),
+            None => exchange.reply.clone().and_then(handle_reply_errors),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::enums::DomainType;
+    use crate::objects::xpc_error::XPCError;
+    use crate::objects::xpc_value::XPCValue;
+    use crate::traits::query_builder::QueryBuilder;
+    use crate::traits::xpc_transport::XPCTransport;
+    use crate::transport::fake_launchd::FakeLaunchd;
+    use crate::transport::recording::RecordingTransport;
+    use crate::transport::replay::{ReplayMode, ReplayTransport};
+    use crate::transport::session::XPCSession;
+
+    fn list(domain: DomainType, name: Option<&str>) -> XPCValue {
+        XPCValue::new_dictionary()
+            .entry("subsystem", 3_u64)
+            .entry("routine", 815_u64)
+            .with_domain_type_or_default(Some(domain))
+            .entry_if_present("name", name)
+    }
+
+    fn record(extension: &str) -> (XPCSession, Vec<Result<XPCValue, XPCError>>) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(format!("session.{}", extension));
+
+        let fake = FakeLaunchd::new();
+        fake.on(3, 815, |request, _| match request.get(["type"]) {
+            // EPERM
+            Ok(XPCValue::UInt64(2)) => Ok(FakeLaunchd::error_reply(1)),
+            _ => Ok(XPCValue::new_dictionary().entry("services", XPCValue::new_dictionary())),
+        });
+
+        let recorder = RecordingTransport::new(fake, &path);
+        let results = vec![
+            recorder.pipe_routine_with_error_handling(&list(DomainType::System, None)),
+            recorder.pipe_routine_with_error_handling(&list(DomainType::User, None)),
+            recorder.pipe_routine(&list(DomainType::System, Some("nope"))),
+        ];
+
+        let session = XPCSession::load(&path).unwrap();
+
+        assert_eq!(session, recorder.session());
+        (session, results)
+    }
+
+    #[test]
+    fn replay_in_order_json() {
+        let (session, results) = record("json");
+        assert_eq!(session.exchanges.len(), 3);
+        assert!(results[1].is_err());
+
+        let replay = ReplayTransport::new(session, ReplayMode::InOrder);
+        assert_eq!(
+            replay.pipe_routine_with_error_handling(&list(DomainType::System, None)),
+            results[0]
+        );
+        assert_eq!(
+            replay.pipe_routine_with_error_handling(&list(DomainType::User, None)),
+            results[1]
+        );
+
+        // Out of order
+        assert!(replay
+            .pipe_routine(&list(DomainType::System, None))
+            .is_err());
+        assert_eq!(replay.remaining().len(), 1);
+    }
+
+    #[test]
+    fn replay_matched_plist() {
+        let (session, results) = record("plist");
+        let replay = ReplayTransport::new(session, ReplayMode::Matched);
+
+        assert_eq!(
+            replay.pipe_routine(&list(DomainType::System, Some("nope"))),
+            results[2]
+        );
+
+        // Repeats once used up
+        for _ in 0..2 {
+            assert_eq!(
+                replay.pipe_routine_with_error_handling(&list(DomainType::User, None)),
+                results[1]
+            );
+        }
+
+        assert!(replay.pipe_routine(&list(DomainType::PID, None)).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::objects::xpc_error::XPCError;
+use crate::objects::xpc_value::XPCValue;
+use crate::traits::xpc_value::TryXPCValue;
+
+/// One request and what came back
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct XPCExchange {
+    pub request: XPCValue,
+    /// From XPCTransport::pipe_routine
+    pub reply: Result<XPCValue, XPCError>,
+    /// From XPCTransport::pipe_routine_with_error_handling, if it was used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Result<XPCValue, XPCError>>,
+}
+
+impl XPCExchange {
+    pub fn key(&self) -> XPCExchangeKey {
+        XPCExchangeKey::from(&self.request)
+    }
+}
+
+/// What ReplayTransport matches requests on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct XPCExchangeKey {
+    pub subsystem: Option<u64>,
+    pub routine: Option<u64>,
+    pub domain_type: Option<u64>,
+    pub name: Option<String>,
+}
+
+impl From<&XPCValue> for XPCExchangeKey {
+    fn from(request: &XPCValue) -> Self {
+        let uint = |key: &str| request.get([key]).and_then(|v| v.xpc_value()).ok();
+
+        Self {
+            subsystem: uint("subsystem"),
+            routine: uint("routine"),
+            domain_type: uint("type"),
+            name: request.get(["name"]).and_then(|v| v.xpc_value()).ok(),
+        }
+    }
+}
+
+/// Exchanges in the order they happened. Saved as a plist if the
+/// path ends in .plist, JSON otherwise. Shmem contents are not
+/// recorded, only the bytes-written reply.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct XPCSession {
+    pub exchanges: Vec<XPCExchange>,
+}
+
+impl XPCSession {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, XPCError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| XPCError::IOError(e.to_string()))?;
+
+        if is_plist(path) {
+            plist::from_reader(file).map_err(|e| XPCError::ValueError(e.to_string()))
+        } else {
+            serde_json::from_reader(BufReader::new(file))
+                .map_err(|e| XPCError::ValueError(e.to_string()))
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), XPCError> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| XPCError::IOError(e.to_string()))?;
+
+        if is_plist(path) {
+            plist::to_writer_xml(BufWriter::new(file), self)
+                .map_err(|e| XPCError::ValueError(e.to_string()))
+        } else {
+            serde_json::to_writer_pretty(BufWriter::new(file), self)
+                .map_err(|e| XPCError::ValueError(e.to_string()))
+        }
+    }
+}
+
+fn is_plist(path: &Path) -> bool {
+    path.extension().map(|e| e == "plist").unwrap_or(false)
+}
@@ -0,0 +1,251 @@
+use std::collections::btree_map;
+use std::slice;
+
+use serde::de::value::StrDeserializer;
+use serde::de::{
+    DeserializeSeed, EnumAccess, Error, IntoDeserializer, MapAccess, SeqAccess, Unexpected,
+    VariantAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+use crate::objects::xpc_value::XPCValue;
+use crate::xpc_serde::error::{PathSegment, XPCSerdeError};
+
+/// Deserialize borrowing from an XPCValue
+pub struct XPCValueDeserializer<'de> {
+    value: &'de XPCValue,
+}
+
+impl<'de> XPCValueDeserializer<'de> {
+    pub fn new(value: &'de XPCValue) -> Self {
+        Self { value }
+    }
+
+    fn unexpected(&self) -> Unexpected<'de> {
+        match self.value {
+            XPCValue::Null => Unexpected::Unit,
+            XPCValue::Bool(b) => Unexpected::Bool(*b),
+            XPCValue::Int64(i) => Unexpected::Signed(*i),
+            XPCValue::UInt64(u) => Unexpected::Unsigned(*u),
+            XPCValue::Double(d) => Unexpected::Float(*d),
+            XPCValue::String(s) => Unexpected::Str(s),
+            XPCValue::Data(d) => Unexpected::Bytes(d),
+            XPCValue::Array(_) => Unexpected::Seq,
+            XPCValue::Dictionary(_) => Unexpected::Map,
+            other => Unexpected::Other(other.type_name()),
+        }
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for XPCValueDeserializer<'de> {
+    type Error = XPCSerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, XPCSerdeError> {
+        match self.value {
+            XPCValue::Null => visitor.visit_unit(),
+            XPCValue::Bool(b) => visitor.visit_bool(*b),
+            XPCValue::Int64(i) => visitor.visit_i64(*i),
+            XPCValue::UInt64(u) => visitor.visit_u64(*u),
+            XPCValue::Double(d) => visitor.visit_f64(*d),
+            XPCValue::String(s) => visitor.visit_borrowed_str(s),
+            XPCValue::Data(d) => visitor.visit_borrowed_bytes(d),
+            XPCValue::Date(ns) => visitor.visit_i64(*ns),
+            XPCValue::Uuid(uuid) => visitor.visit_borrowed_bytes(uuid),
+            XPCValue::Array(items) => visitor.visit_seq(XPCSeqAccess::new(items)),
+            XPCValue::Dictionary(map) => visitor.visit_map(XPCMapAccess::new(map)),
+            XPCValue::Fd(fd) => visitor.visit_i32(*fd),
+            XPCValue::MachSend(port) | XPCValue::MachRecv(port) => visitor.visit_u32(*port),
+            XPCValue::Shmem(_, _) => Err(XPCSerdeError::invalid_type(
+                self.unexpected(),
+                &"a value that can be copied out of XPC",
+            )),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, XPCSerdeError> {
+        match self.value {
+            XPCValue::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, XPCSerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Unit variants are strings, others a dictionary with a single key
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, XPCSerdeError> {
+        match self.value {
+            XPCValue::String(s) => visitor.visit_enum(s.as_str().into_deserializer()),
+            XPCValue::Dictionary(map) if map.len() == 1 => {
+                let (variant, value) = map.iter().next().unwrap();
+                visitor
+                    .visit_enum(XPCEnumAccess { variant, value })
+                    .map_err(|e| e.prefix(PathSegment::Key(variant.clone())))
+            }
+            _ => Err(XPCSerdeError::invalid_type(
+                self.unexpected(),
+                &"string or dictionary with a single key",
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct XPCSeqAccess<'de> {
+    iter: slice::Iter<'de, XPCValue>,
+    index: usize,
+}
+
+impl<'de> XPCSeqAccess<'de> {
+    fn new(items: &'de [XPCValue]) -> Self {
+        Self {
+            iter: items.iter(),
+            index: 0,
+        }
+    }
+}
+
+impl<'de> SeqAccess<'de> for XPCSeqAccess<'de> {
+    type Error = XPCSerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, XPCSerdeError> {
+        match self.iter.next() {
+            Some(value) => {
+                let index = self.index;
+                self.index += 1;
+
+                seed.deserialize(XPCValueDeserializer::new(value))
+                    .map(Some)
+                    .map_err(|e| e.prefix(PathSegment::Index(index)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct XPCMapAccess<'de> {
+    iter: btree_map::Iter<'de, String, XPCValue>,
+    next_value: Option<(&'de String, &'de XPCValue)>,
+}
+
+impl<'de> XPCMapAccess<'de> {
+    fn new(map: &'de btree_map::BTreeMap<String, XPCValue>) -> Self {
+        Self {
+            iter: map.iter(),
+            next_value: None,
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for XPCMapAccess<'de> {
+    type Error = XPCSerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, XPCSerdeError> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.next_value = Some((key, value));
+                let key: StrDeserializer<XPCSerdeError> = key.as_str().into_deserializer();
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, XPCSerdeError> {
+        let (key, value) = self
+            .next_value
+            .take()
+            .ok_or_else(|| XPCSerdeError::new("next_value_seed called before next_key_seed"))?;
+
+        seed.deserialize(XPCValueDeserializer::new(value))
+            .map_err(|e| e.prefix(PathSegment::Key(key.clone())))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct XPCEnumAccess<'de> {
+    variant: &'de String,
+    value: &'de XPCValue,
+}
+
+impl<'de> EnumAccess<'de> for XPCEnumAccess<'de> {
+    type Error = XPCSerdeError;
+    type Variant = XPCValueDeserializer<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), XPCSerdeError> {
+        let variant: StrDeserializer<XPCSerdeError> = self.variant.as_str().into_deserializer();
+        let variant = seed.deserialize(variant)?;
+        Ok((variant, XPCValueDeserializer::new(self.value)))
+    }
+}
+
+impl<'de> VariantAccess<'de> for XPCValueDeserializer<'de> {
+    type Error = XPCSerdeError;
+
+    fn unit_variant(self) -> Result<(), XPCSerdeError> {
+        match self.value {
+            XPCValue::Null => Ok(()),
+            _ => Err(XPCSerdeError::invalid_type(
+                self.unexpected(),
+                &"unit variant",
+            )),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, XPCSerdeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, XPCSerdeError> {
+        serde::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, XPCSerdeError> {
+        serde::Deserializer::deserialize_map(self, visitor)
+    }
+}
@@ -0,0 +1,87 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use crate::objects::xpc_error::XPCError;
+
+/// Error from (de)serializing, with the path to the offending
+/// value, e.g. service.ProgramArguments[1]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct XPCSerdeError {
+    pub path: Vec<PathSegment>,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+impl XPCSerdeError {
+    pub fn new<S: Into<String>>(message: S) -> Self {
+        Self {
+            path: vec![],
+            message: message.into(),
+        }
+    }
+
+    /// Errors bubble up from the leaves, so every level prepends itself
+    pub(crate) fn prefix(mut self, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+
+    pub fn path_string(&self) -> String {
+        let mut path = String::new();
+
+        for segment in &self.path {
+            match segment {
+                PathSegment::Key(k) if path.is_empty() => path.push_str(k),
+                PathSegment::Key(k) => {
+                    path.push('.');
+                    path.push_str(k)
+                }
+                PathSegment::Index(i) => path.push_str(&format!("[{}]", i)),
+            }
+        }
+
+        path
+    }
+}
+
+impl Display for XPCSerdeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path_string(), self.message)
+        }
+    }
+}
+
+impl Error for XPCSerdeError {}
+
+impl serde::de::Error for XPCSerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        XPCSerdeError::new(msg.to_string())
+    }
+}
+
+impl serde::ser::Error for XPCSerdeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        XPCSerdeError::new(msg.to_string())
+    }
+}
+
+impl From<XPCSerdeError> for XPCError {
+    fn from(e: XPCSerdeError) -> Self {
+        XPCError::ValueError(e.to_string())
+    }
+}
+
+impl From<XPCError> for XPCSerdeError {
+    fn from(e: XPCError) -> Self {
+        XPCSerdeError::new(e.to_string())
+    }
+}
@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+
+use crate::objects::xpc_value::XPCValue;
+use crate::xpc_serde::de::XPCValueDeserializer;
+use crate::xpc_serde::error::XPCSerdeError;
+use crate::xpc_serde::ser::XPCValueSerializer;
+
+#[cfg(target_os = "macos")]
+use {
+    crate::objects::xpc_dictionary::XPCDictionary, crate::objects::xpc_object::XPCObject,
+    serde::de::DeserializeOwned, std::convert::TryFrom,
+};
+
+/// Deserializer borrowing from XPCValue
+pub mod de;
+/// Errors with the path to the offending value
+pub mod error;
+/// Serializer into XPCValue
+pub mod ser;
+
+/// Serialize T into an XPCValue
+pub fn to_xpc_value<T: Serialize + ?Sized>(value: &T) -> Result<XPCValue, XPCSerdeError> {
+    value.serialize(XPCValueSerializer)
+}
+
+/// Deserialize T from an XPCValue
+pub fn from_xpc_value<'de, T: Deserialize<'de>>(value: &'de XPCValue) -> Result<T, XPCSerdeError> {
+    T::deserialize(XPCValueDeserializer::new(value))
+}
+
+/// Serialize T into an XPCDictionary, T must serialize to a map
+#[cfg(target_os = "macos")]
+pub fn to_xpc_dictionary<T: Serialize + ?Sized>(value: &T) -> Result<XPCDictionary, XPCSerdeError> {
+    match to_xpc_value(value)? {
+        v @ XPCValue::Dictionary(_) => {
+            let object: XPCObject = v.into();
+            XPCDictionary::try_from(object).map_err(|e| e.into())
+        }
+        other => Err(XPCSerdeError::new(format!(
+            "Expected dictionary, got {}",
+            other.type_name()
+        ))),
+    }
+}
+
+/// Deserialize T from an XPCDictionary (e.g. a pipe_routine reply)
+#[cfg(target_os = "macos")]
+pub fn from_xpc_dictionary<T: DeserializeOwned>(dict: &XPCDictionary) -> Result<T, XPCSerdeError> {
+    let value = XPCValue::try_from(dict)?;
+    from_xpc_value(&value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::objects::xpc_value::XPCValue;
+    use crate::traits::query_builder::QueryBuilder;
+    use crate::xpc_serde::error::PathSegment;
+    use crate::xpc_serde::{from_xpc_value, to_xpc_value};
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Service {
+        #[serde(rename = "PID")]
+        pid: i64,
+        #[serde(rename = "ProgramArguments")]
+        program_arguments: Vec<String>,
+        #[serde(rename = "LimitLoadToSessionType")]
+        session: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Reply {
+        service: Service,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    enum Status {
+        Running,
+        Exited(i64),
+        Waiting { reason: String },
+    }
+
+    fn reply() -> XPCValue {
+        let service = XPCValue::new_dictionary()
+            .entry("PID", 123_i64)
+            .entry("ProgramArguments", vec!["/bin/sh", "-c"])
+            .entry("LimitLoadToSessionType", "Aqua");
+
+        XPCValue::new_dictionary().entry("service", service)
+    }
+
+    #[test]
+    fn deserialize_struct() {
+        let value = reply();
+        let reply: Reply = from_xpc_value(&value).unwrap();
+
+        assert_eq!(reply.service.pid, 123);
+        assert_eq!(reply.service.program_arguments, vec!["/bin/sh", "-c"]);
+        assert_eq!(reply.service.session, Some("Aqua".to_string()));
+    }
+
+    #[test]
+    fn deserialize_error_path() {
+        let service = XPCValue::new_dictionary().entry("PID", 123_i64).entry(
+            "ProgramArguments",
+            vec![XPCValue::from("/bin/sh"), XPCValue::from(1_i64)],
+        );
+        let value = XPCValue::new_dictionary().entry("service", service);
+
+        let err = from_xpc_value::<Reply>(&value).unwrap_err();
+
+        assert_eq!(
+            err.path,
+            vec![
+                PathSegment::Key("service".to_string()),
+                PathSegment::Key("ProgramArguments".to_string()),
+                PathSegment::Index(1)
+            ]
+        );
+        assert_eq!(err.path_string(), "service.ProgramArguments[1]");
+    }
+
+    #[test]
+    fn serialize_round_trip() {
+        let reply = Reply {
+            service: Service {
+                pid: 1,
+                program_arguments: vec!["/usr/libexec/foo".to_string()],
+                session: None,
+            },
+        };
+
+        let value = to_xpc_value(&reply).unwrap();
+        assert_eq!(value.get(["service", "PID"]).unwrap(), &XPCValue::Int64(1));
+        assert_eq!(from_xpc_value::<Reply>(&value).unwrap(), reply);
+    }
+
+    #[test]
+    fn enum_round_trip() {
+        let statuses = vec![
+            Status::Running,
+            Status::Exited(78),
+            Status::Waiting {
+                reason: "throttled".to_string(),
+            },
+        ];
+
+        let value = to_xpc_value(&statuses).unwrap();
+        assert_eq!(from_xpc_value::<Vec<Status>>(&value).unwrap(), statuses);
+    }
+
+    #[test]
+    fn non_string_keys() {
+        let mut map: BTreeMap<i64, bool> = BTreeMap::new();
+        map.insert(1, true);
+
+        assert!(to_xpc_value(&map).is_err());
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn dictionary_round_trip() {
+        use crate::xpc_serde::{from_xpc_dictionary, to_xpc_dictionary};
+
+        let reply = Reply {
+            service: Service {
+                pid: 42,
+                program_arguments: vec!["/bin/echo".to_string(), "hi".to_string()],
+                session: Some("Background".to_string()),
+            },
+        };
+
+        let dict = to_xpc_dictionary(&reply).unwrap();
+        assert_eq!(from_xpc_dictionary::<Reply>(&dict).unwrap(), reply);
+    }
+}
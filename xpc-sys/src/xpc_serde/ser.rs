@@ -0,0 +1,532 @@
+use std::collections::BTreeMap;
+
+use serde::ser::{
+    Error, Impossible, Serialize, SerializeMap, SerializeSeq, SerializeStruct,
+    SerializeStructVariant, SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+};
+
+use crate::objects::xpc_value::XPCValue;
+use crate::xpc_serde::error::{PathSegment, XPCSerdeError};
+
+/// Serialize into an owned XPCValue
+pub struct XPCValueSerializer;
+
+impl serde::Serializer for XPCValueSerializer {
+    type Ok = XPCValue;
+    type Error = XPCSerdeError;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = VariantSerializer<SeqSerializer>;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = VariantSerializer<MapSerializer>;
+
+    fn serialize_bool(self, v: bool) -> Result<XPCValue, XPCSerdeError> {
+        Ok(XPCValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<XPCValue, XPCSerdeError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<XPCValue, XPCSerdeError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<XPCValue, XPCSerdeError> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<XPCValue, XPCSerdeError> {
+        Ok(XPCValue::Int64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<XPCValue, XPCSerdeError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<XPCValue, XPCSerdeError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<XPCValue, XPCSerdeError> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<XPCValue, XPCSerdeError> {
+        Ok(XPCValue::UInt64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<XPCValue, XPCSerdeError> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<XPCValue, XPCSerdeError> {
+        Ok(XPCValue::Double(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<XPCValue, XPCSerdeError> {
+        Ok(XPCValue::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<XPCValue, XPCSerdeError> {
+        Ok(XPCValue::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<XPCValue, XPCSerdeError> {
+        Ok(XPCValue::Data(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<XPCValue, XPCSerdeError> {
+        Ok(XPCValue::Null)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<XPCValue, XPCSerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<XPCValue, XPCSerdeError> {
+        Ok(XPCValue::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<XPCValue, XPCSerdeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<XPCValue, XPCSerdeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<XPCValue, XPCSerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<XPCValue, XPCSerdeError> {
+        let inner = value
+            .serialize(XPCValueSerializer)
+            .map_err(|e| e.prefix(PathSegment::Key(variant.to_string())))?;
+
+        let mut map = BTreeMap::new();
+        map.insert(variant.to_string(), inner);
+        Ok(XPCValue::Dictionary(map))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, XPCSerdeError> {
+        Ok(SeqSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, XPCSerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, XPCSerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer<SeqSerializer>, XPCSerdeError> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer, XPCSerdeError> {
+        Ok(MapSerializer {
+            map: BTreeMap::new(),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer, XPCSerdeError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer<MapSerializer>, XPCSerdeError> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+pub struct SeqSerializer {
+    items: Vec<XPCValue>,
+}
+
+impl SeqSerializer {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), XPCSerdeError> {
+        let index = self.items.len();
+        let value = value
+            .serialize(XPCValueSerializer)
+            .map_err(|e| e.prefix(PathSegment::Index(index)))?;
+
+        self.items.push(value);
+        Ok(())
+    }
+}
+
+impl SerializeSeq for SeqSerializer {
+    type Ok = XPCValue;
+    type Error = XPCSerdeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), XPCSerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<XPCValue, XPCSerdeError> {
+        Ok(XPCValue::Array(self.items))
+    }
+}
+
+impl SerializeTuple for SeqSerializer {
+    type Ok = XPCValue;
+    type Error = XPCSerdeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), XPCSerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<XPCValue, XPCSerdeError> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl SerializeTupleStruct for SeqSerializer {
+    type Ok = XPCValue;
+    type Error = XPCSerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), XPCSerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<XPCValue, XPCSerdeError> {
+        SerializeSeq::end(self)
+    }
+}
+
+pub struct MapSerializer {
+    map: BTreeMap<String, XPCValue>,
+    next_key: Option<String>,
+}
+
+impl MapSerializer {
+    fn insert<T: ?Sized + Serialize>(
+        &mut self,
+        key: String,
+        value: &T,
+    ) -> Result<(), XPCSerdeError> {
+        let value = value
+            .serialize(XPCValueSerializer)
+            .map_err(|e| e.prefix(PathSegment::Key(key.clone())))?;
+
+        self.map.insert(key, value);
+        Ok(())
+    }
+}
+
+impl SerializeMap for MapSerializer {
+    type Ok = XPCValue;
+    type Error = XPCSerdeError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), XPCSerdeError> {
+        self.next_key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), XPCSerdeError> {
+        let key = self
+            .next_key
+            .take()
+            .ok_or_else(|| XPCSerdeError::custom("serialize_value called before serialize_key"))?;
+
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<XPCValue, XPCSerdeError> {
+        Ok(XPCValue::Dictionary(self.map))
+    }
+}
+
+impl SerializeStruct for MapSerializer {
+    type Ok = XPCValue;
+    type Error = XPCSerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), XPCSerdeError> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<XPCValue, XPCSerdeError> {
+        SerializeMap::end(self)
+    }
+}
+
+/// Wraps tuple and struct variants as { variant: ... }
+pub struct VariantSerializer<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl<S> VariantSerializer<S> {
+    fn wrap(variant: &'static str, value: XPCValue) -> XPCValue {
+        let mut map = BTreeMap::new();
+        map.insert(variant.to_string(), value);
+        XPCValue::Dictionary(map)
+    }
+}
+
+impl SerializeTupleVariant for VariantSerializer<SeqSerializer> {
+    type Ok = XPCValue;
+    type Error = XPCSerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), XPCSerdeError> {
+        let variant = self.variant;
+        self.inner
+            .push(value)
+            .map_err(|e| e.prefix(PathSegment::Key(variant.to_string())))
+    }
+
+    fn end(self) -> Result<XPCValue, XPCSerdeError> {
+        Ok(Self::wrap(self.variant, SerializeSeq::end(self.inner)?))
+    }
+}
+
+impl SerializeStructVariant for VariantSerializer<MapSerializer> {
+    type Ok = XPCValue;
+    type Error = XPCSerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), XPCSerdeError> {
+        let variant = self.variant;
+        self.inner
+            .insert(key.to_string(), value)
+            .map_err(|e| e.prefix(PathSegment::Key(variant.to_string())))
+    }
+
+    fn end(self) -> Result<XPCValue, XPCSerdeError> {
+        Ok(Self::wrap(self.variant, SerializeMap::end(self.inner)?))
+    }
+}
+
+/// XPC dictionary keys can only be strings
+struct KeySerializer;
+
+impl KeySerializer {
+    fn key_error() -> XPCSerdeError {
+        XPCSerdeError::custom("dictionary keys must be strings")
+    }
+}
+
+impl serde::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = XPCSerdeError;
+
+    type SerializeSeq = Impossible<String, XPCSerdeError>;
+    type SerializeTuple = Impossible<String, XPCSerdeError>;
+    type SerializeTupleStruct = Impossible<String, XPCSerdeError>;
+    type SerializeTupleVariant = Impossible<String, XPCSerdeError>;
+    type SerializeMap = Impossible<String, XPCSerdeError>;
+    type SerializeStruct = Impossible<String, XPCSerdeError>;
+    type SerializeStructVariant = Impossible<String, XPCSerdeError>;
+
+    fn serialize_str(self, v: &str) -> Result<String, XPCSerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, XPCSerdeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, XPCSerdeError> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, XPCSerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_none(self) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_unit(self) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_seq(
+        self,
+        _len: Option<usize>,
+    ) -> Result<Impossible<String, XPCSerdeError>, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_tuple(
+        self,
+        _len: usize,
+    ) -> Result<Impossible<String, XPCSerdeError>, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Impossible<String, XPCSerdeError>, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Impossible<String, XPCSerdeError>, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_map(
+        self,
+        _len: Option<usize>,
+    ) -> Result<Impossible<String, XPCSerdeError>, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Impossible<String, XPCSerdeError>, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Impossible<String, XPCSerdeError>, XPCSerdeError> {
+        Err(Self::key_error())
+    }
+}